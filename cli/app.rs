@@ -11,7 +11,7 @@ use crate::{
     HISTORY_FILE,
 };
 use comfy_table::{Attribute, Cell, CellAlignment, ContentArrangement, Row, Table};
-use limbo_core::{Database, LimboError, Statement, StepResult, Value};
+use limbo_core::{Database, DisplayOptions, LimboError, Statement, StepResult, Value};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
@@ -303,11 +303,11 @@ impl Limbo {
 
         match res {
             Ok(_) => Ok(()),
-            Err(LimboError::Corrupt(x)) => {
+            Err(e @ (LimboError::Corrupt(_) | LimboError::CorruptRecord(_))) => {
                 // FIXME: SQLite at this point retry the query with a different
                 // order by, but for simplicity we are just ignoring for now
                 self.writeln("/****** CORRUPTION ERROR *******/")?;
-                Err(LimboError::Corrupt(x))
+                Err(e)
             }
             Err(x) => Err(x),
         }?;
@@ -668,6 +668,10 @@ impl Limbo {
         mut output: Result<Option<Statement>, LimboError>,
         mut statistics: Option<&mut QueryStatistics>,
     ) -> anyhow::Result<()> {
+        let display = DisplayOptions {
+            null_text: self.opts.null_value.clone(),
+            ..Default::default()
+        };
         match output {
             Ok(Some(ref mut rows)) => match self.opts.output_mode {
                 OutputMode::List => loop {
@@ -688,11 +692,8 @@ impl Limbo {
                                 if i > 0 {
                                     let _ = self.writer.write(b"|");
                                 }
-                                if matches!(value, Value::Null) {
-                                    let _ = self.writer.write(self.opts.null_value.as_bytes())?;
-                                } else {
-                                    let _ = self.writer.write(format!("{}", value).as_bytes())?;
-                                }
+                                let _ =
+                                    self.writer.write(value.format_with(&display).as_bytes())?;
                             }
                             let _ = self.writeln("");
                         }
@@ -759,22 +760,10 @@ impl Limbo {
                                 let mut row = Row::new();
                                 row.max_height(1);
                                 for (idx, value) in record.get_values().enumerate() {
-                                    let (content, alignment) = match value {
-                                        Value::Null => {
-                                            (self.opts.null_value.clone(), CellAlignment::Left)
-                                        }
-                                        Value::Integer(_) => {
-                                            (format!("{}", value), CellAlignment::Right)
-                                        }
-                                        Value::Float(_) => {
-                                            (format!("{}", value), CellAlignment::Right)
-                                        }
-                                        Value::Text(_) => {
-                                            (format!("{}", value), CellAlignment::Left)
-                                        }
-                                        Value::Blob(_) => {
-                                            (format!("{}", value), CellAlignment::Left)
-                                        }
+                                    let content = value.format_with(&display);
+                                    let alignment = match value {
+                                        Value::Integer(_) | Value::Float(_) => CellAlignment::Right,
+                                        _ => CellAlignment::Left,
                                     };
                                     row.add_cell(
                                         Cell::new(content)
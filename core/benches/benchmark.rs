@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use limbo_core::{Database, PlatformIO, IO};
+use limbo_core::{types::Record, Database, PlatformIO, Value, IO};
 use pprof::criterion::{Output, PProfProfiler};
 use std::sync::Arc;
 
@@ -233,9 +233,64 @@ fn bench_execute_select_count(criterion: &mut Criterion) {
     group.finish();
 }
 
+fn bench_serialize_record(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("Serialize record");
+
+    let records = [
+        ("50 nulls", Record::new(vec![Value::Null; 50])),
+        ("50 integers", Record::new(vec![Value::Integer(42); 50])),
+        (
+            "200 mixed columns",
+            Record::new(
+                (0..200)
+                    .map(|i| match i % 3 {
+                        0 => Value::Integer(i),
+                        1 => Value::Float(i as f64),
+                        _ => Value::build_text("x".repeat(64)),
+                    })
+                    .collect(),
+            ),
+        ),
+    ];
+    for (name, record) in records.iter() {
+        group.bench_with_input(BenchmarkId::new("serialize", name), record, |b, record| {
+            let mut buf = Vec::with_capacity(128);
+            b.iter(|| {
+                buf.clear();
+                record.serialize(&mut buf);
+                black_box(&buf);
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_build_records(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("Build records");
+
+    group.bench_function("1000 records of 4 columns", |b| {
+        b.iter(|| {
+            for i in 0..1000 {
+                let record: Record = [
+                    Value::Integer(i),
+                    Value::Float(i as f64),
+                    Value::Null,
+                    Value::Integer(-i),
+                ]
+                .into_iter()
+                .collect();
+                black_box(record);
+            }
+        });
+    });
+
+    group.finish();
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = bench_prepare_query, bench_execute_select_1, bench_execute_select_rows, bench_execute_select_count
+    targets = bench_prepare_query, bench_execute_select_1, bench_execute_select_rows, bench_execute_select_count, bench_serialize_record, bench_build_records
 }
 criterion_main!(benches);
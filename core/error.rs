@@ -4,6 +4,8 @@ use thiserror::Error;
 pub enum LimboError {
     #[error("Corrupt database: {0}")]
     Corrupt(String),
+    #[error("Corrupt record: {0}")]
+    CorruptRecord(String),
     #[error("File is not a database")]
     NotADB,
     #[error("Internal error: {0}")]
@@ -51,6 +53,8 @@ pub enum LimboError {
     ExtensionError(String),
     #[error("Runtime error: integer overflow")]
     IntegerOverflow,
+    #[error("Runtime error: division by zero")]
+    DivisionByZero,
     #[error("Schema is locked for write")]
     SchemaLocked,
     #[error("Database Connection is read-only")]
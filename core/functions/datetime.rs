@@ -698,6 +698,44 @@ fn format_time_duration(duration: &chrono::Duration) -> Value {
     Value::build_text(&result)
 }
 
+impl Value {
+    /// Builds the `YYYY-MM-DD HH:MM:SS` text that `datetime(secs, 'unixepoch')` produces.
+    /// Returns NULL when the timestamp falls outside the range SQLite supports.
+    pub fn from_unix_timestamp(secs: i64) -> Value {
+        match DateTime::from_timestamp(secs, 0) {
+            Some(dt) if dt.naive_utc() < get_max_datetime_exclusive() => {
+                format_dt(dt.naive_utc(), DateTimeOutput::DateTime, false)
+            }
+            _ => Value::Null,
+        }
+    }
+
+    /// Equivalent to `julianday(self)`: returns the Julian day number as a float,
+    /// or NULL if `self` is not a valid time value.
+    pub fn to_julian_day(&self) -> Value {
+        match parse_naive_date_time(self) {
+            Some(dt) => format_dt(dt, DateTimeOutput::JuliaDay, false),
+            None => Value::Null,
+        }
+    }
+
+    /// Formats `self` like `strftime(format, self)`, except that integers are read as
+    /// unix timestamps rather than Julian day numbers. Returns NULL if `self` is not a
+    /// valid time value.
+    pub fn strftime(&self, format: &str) -> Value {
+        let dt = match self {
+            Value::Integer(secs) => DateTime::from_timestamp(*secs, 0).map(|dt| dt.naive_utc()),
+            _ => parse_naive_date_time(self),
+        };
+        match dt {
+            Some(dt) if !is_leap_second(&dt) && dt < get_max_datetime_exclusive() => {
+                format_dt(dt, DateTimeOutput::StrfTime(format.to_string()), false)
+            }
+            _ => Value::Null,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1629,7 +1667,26 @@ mod tests {
     }
 
     #[test]
-    fn test_strftime() {}
+    fn test_strftime() {
+        let ts = Value::Integer(1_700_000_000);
+        assert_eq!(ts.strftime("%Y-%m-%d"), Value::build_text("2023-11-14"));
+        assert_eq!(ts.strftime("%H:%M:%S"), Value::build_text("22:13:20"));
+        assert_eq!(ts.strftime("%j %w"), Value::build_text("318 2"));
+        assert_eq!(ts.strftime("%s"), Value::build_text("1700000000"));
+        assert_eq!(ts.strftime("100%%"), Value::build_text("100%"));
+
+        let text = Value::build_text("2024-02-29 08:05:09");
+        assert_eq!(
+            text.strftime("%d/%m/%Y %H-%M-%S"),
+            Value::build_text("29/02/2024 08-05-09")
+        );
+        assert_eq!(text.strftime("%j"), Value::build_text("060"));
+        assert_eq!(text.strftime("%w"), Value::build_text("4"));
+
+        assert_eq!(Value::build_text("garbage").strftime("%Y"), Value::Null);
+        assert_eq!(Value::Null.strftime("%Y"), Value::Null);
+        assert_eq!(Value::Integer(i64::MAX).strftime("%Y"), Value::Null);
+    }
 
     #[test]
     fn test_exec_timediff() {
@@ -1693,4 +1750,40 @@ mod tests {
         let expected = Value::Null;
         assert_eq!(exec_timediff(&[Register::Value(start)]), expected);
     }
+
+    #[test]
+    fn test_from_unix_timestamp() {
+        assert_eq!(
+            Value::from_unix_timestamp(0),
+            Value::build_text("1970-01-01 00:00:00")
+        );
+        assert_eq!(
+            Value::from_unix_timestamp(1_700_000_000),
+            Value::build_text("2023-11-14 22:13:20")
+        );
+        assert_eq!(
+            Value::from_unix_timestamp(-86_400),
+            Value::build_text("1969-12-31 00:00:00")
+        );
+        assert_eq!(Value::from_unix_timestamp(i64::MAX), Value::Null);
+        assert_eq!(Value::from_unix_timestamp(253_402_300_800), Value::Null);
+    }
+
+    #[test]
+    fn test_to_julian_day() {
+        assert_eq!(
+            Value::from_unix_timestamp(0).to_julian_day(),
+            Value::Float(2440587.5)
+        );
+        assert_eq!(
+            Value::build_text("2000-01-01 12:00:00").to_julian_day(),
+            Value::Float(2451545.0)
+        );
+        assert_eq!(
+            Value::Float(2451545.0).to_julian_day(),
+            Value::Float(2451545.0)
+        );
+        assert_eq!(Value::build_text("not a date").to_julian_day(), Value::Null);
+        assert_eq!(Value::Null.to_julian_day(), Value::Null);
+    }
 }
@@ -1,8 +1,9 @@
 use crate::types::Value;
+use crate::util::cast_text_to_integer;
 use crate::vdbe::Register;
 use crate::LimboError;
 
-// TODO: Support %!.3s %i, %x, %X, %o, %e, %E, %c. flags: - + 0 ! ,
+// TODO: Support %!.3s %i, %o, %e, %E, %c, %w, %z. flags: - + 0 ! ,
 #[inline(always)]
 pub fn exec_printf(values: &[Register]) -> crate::Result<Value> {
     if values.is_empty() {
@@ -12,10 +13,24 @@ pub fn exec_printf(values: &[Register]) -> crate::Result<Value> {
         Value::Text(t) => t.as_str(),
         _ => return Ok(Value::Null),
     };
+    format_printf(format_str, values[1..].iter().map(|r| r.get_owned_value()))
+}
+
+/// Formats `args` according to `format_str` like SQLite's `printf()`/`format()`.
+pub fn sql_printf(format_str: &str, args: &[Value]) -> crate::Result<Value> {
+    format_printf(format_str, args.iter())
+}
 
+fn format_printf<'a>(
+    format_str: &str,
+    mut args: impl Iterator<Item = &'a Value>,
+) -> crate::Result<Value> {
     let mut result = String::new();
-    let mut args_index = 1;
     let mut chars = format_str.chars().peekable();
+    let mut next_arg = || {
+        args.next()
+            .ok_or_else(|| LimboError::InvalidArgument("not enough arguments".into()))
+    };
 
     while let Some(c) = chars.next() {
         if c != '%' {
@@ -29,39 +44,59 @@ pub fn exec_printf(values: &[Register]) -> crate::Result<Value> {
                 continue;
             }
             Some('d') => {
-                if args_index >= values.len() {
-                    return Err(LimboError::InvalidArgument("not enough arguments".into()));
-                }
-                let value = &values[args_index].get_owned_value();
+                let value = next_arg()?;
                 match value {
                     Value::Integer(_) => result.push_str(&format!("{}", value)),
                     Value::Float(_) => result.push_str(&format!("{}", value)),
                     _ => result.push_str("0".into()),
                 }
-                args_index += 1;
-            }
-            Some('s') => {
-                if args_index >= values.len() {
-                    return Err(LimboError::InvalidArgument("not enough arguments".into()));
-                }
-                match &values[args_index].get_owned_value() {
-                    Value::Text(t) => result.push_str(t.as_str()),
-                    Value::Null => result.push_str("(null)"),
-                    v => result.push_str(&format!("{}", v)),
-                }
-                args_index += 1;
             }
+            Some('s') => match next_arg()? {
+                Value::Text(t) => result.push_str(t.as_str()),
+                Value::Null => result.push_str("(null)"),
+                v => result.push_str(&format!("{}", v)),
+            },
             Some('f') => {
-                if args_index >= values.len() {
-                    return Err(LimboError::InvalidArgument("not enough arguments".into()));
-                }
-                let value = &values[args_index].get_owned_value();
+                let value = next_arg()?;
                 match value {
                     Value::Float(f) => result.push_str(&format!("{:.6}", f)),
                     Value::Integer(i) => result.push_str(&format!("{:.6}", *i as f64)),
                     _ => result.push_str("0.0".into()),
                 }
-                args_index += 1;
+            }
+            Some(spec @ ('x' | 'X')) => {
+                let int = match next_arg()? {
+                    Value::Integer(i) => *i,
+                    Value::Float(f) => *f as i64,
+                    Value::Text(t) => match cast_text_to_integer(t.as_str()) {
+                        Value::Integer(i) => i,
+                        _ => 0,
+                    },
+                    _ => 0,
+                };
+                if spec == 'x' {
+                    result.push_str(&format!("{:x}", int as u64));
+                } else {
+                    result.push_str(&format!("{:X}", int as u64));
+                }
+            }
+            Some(spec @ ('q' | 'Q')) => {
+                let text = match next_arg()? {
+                    Value::Null if spec == 'Q' => {
+                        result.push_str("NULL");
+                        continue;
+                    }
+                    Value::Null => "(NULL)".to_string(),
+                    v => v.to_string(),
+                };
+                let escaped = text.replace('\'', "''");
+                if spec == 'Q' {
+                    result.push('\'');
+                    result.push_str(&escaped);
+                    result.push('\'');
+                } else {
+                    result.push_str(&escaped);
+                }
             }
             None => {
                 return Err(LimboError::InvalidArgument(
@@ -269,4 +304,49 @@ mod tests {
             assert_eq!(exec_printf(&input).unwrap(), *expected.get_owned_value());
         }
     }
+
+    #[test]
+    fn test_printf_hex_formatting() {
+        let test_cases = vec![
+            (vec![text("%x"), integer(255)], text("ff")),
+            (vec![text("%X"), integer(255)], text("FF")),
+            (vec![text("%x"), integer(-1)], text("ffffffffffffffff")),
+            (vec![text("%x"), float(3.9)], text("3")),
+            (vec![text("%x"), text("12abc")], text("c")),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(exec_printf(&input).unwrap(), *expected.get_owned_value());
+        }
+    }
+
+    #[test]
+    fn test_printf_sql_quoting() {
+        let test_cases = vec![
+            (vec![text("%q"), text("it's")], text("it''s")),
+            (vec![text("'%q'"), text("a'b'c")], text("'a''b''c'")),
+            (vec![text("%Q"), text("it's")], text("'it''s'")),
+            (
+                vec![text("%q"), Register::Value(Value::Null)],
+                text("(NULL)"),
+            ),
+            (vec![text("%Q"), Register::Value(Value::Null)], text("NULL")),
+            (vec![text("%q"), integer(12)], text("12")),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(exec_printf(&input).unwrap(), *expected.get_owned_value());
+        }
+    }
+
+    #[test]
+    fn test_sql_printf() {
+        assert_eq!(
+            sql_printf("%d-%s", &[Value::Integer(5), Value::build_text("x")]).unwrap(),
+            Value::build_text("5-x")
+        );
+        assert_eq!(
+            sql_printf("SELECT '%q'", &[Value::build_text("O'Brien")]).unwrap(),
+            Value::build_text("SELECT 'O''Brien'")
+        );
+        assert!(sql_printf("%d", &[]).is_err());
+    }
 }
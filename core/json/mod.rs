@@ -634,6 +634,129 @@ pub fn json_quote(value: &Value) -> crate::Result<Value> {
     }
 }
 
+impl Value {
+    /// Returns 1 if the value is well-formed JSON, 0 if it isn't and NULL for NULL, like
+    /// SQLite's `json_valid()`.
+    pub fn json_valid(&self) -> Value {
+        is_json_valid(self)
+    }
+
+    /// Marks text holding well-formed JSON with [TextSubtype::Json] so that JSON functions
+    /// treat it as JSON rather than as a string. Other values are returned unchanged.
+    pub fn into_json_if_valid(self) -> Value {
+        let valid = matches!(self.json_valid(), Value::Integer(1));
+        match self {
+            Value::Text(mut text) if valid => {
+                text.subtype = TextSubtype::Json;
+                Value::Text(text)
+            }
+            other => other,
+        }
+    }
+
+    /// Renders the value as JSON text, the way `json_quote()` builds a JSON value: NULL becomes
+    /// `null`, numbers stay unquoted and text becomes an escaped JSON string, unless it is
+    /// already JSON, in which case it is used verbatim. Blobs are an error.
+    pub fn to_json(&self) -> crate::Result<Text> {
+        let json = match self {
+            Value::Null => "null".to_string(),
+            Value::Integer(i) => i.to_string(),
+            Value::Float(f) if f.is_nan() => "null".to_string(),
+            Value::Float(f) if f.is_infinite() => if f.is_sign_negative() {
+                "-9.0e+999"
+            } else {
+                "9.0e+999"
+            }
+            .to_string(),
+            Value::Float(_) => self.to_string(),
+            Value::Text(t) if t.subtype == TextSubtype::Json => t.as_str().to_string(),
+            Value::Text(t) => {
+                let mut escaped = String::with_capacity(t.value.len() + 2);
+                escaped.push('"');
+                for c in t.as_str().chars() {
+                    match c {
+                        '"' => escaped.push_str("\\\""),
+                        '\\' => escaped.push_str("\\\\"),
+                        '\n' => escaped.push_str("\\n"),
+                        '\r' => escaped.push_str("\\r"),
+                        '\t' => escaped.push_str("\\t"),
+                        '\u{0008}' => escaped.push_str("\\b"),
+                        '\u{000c}' => escaped.push_str("\\f"),
+                        c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => escaped.push(c),
+                    }
+                }
+                escaped.push('"');
+                escaped
+            }
+            Value::Blob(_) => crate::bail_constraint_error!("JSON cannot hold BLOB values"),
+        };
+        Ok(Text::json(json))
+    }
+}
+
+impl Text {
+    /// Builds a JSON array from `values` like `json_array()`. Values that are already JSON
+    /// are nested as-is rather than quoted as strings.
+    pub fn json_array(values: &[Value]) -> crate::Result<Text> {
+        let mut json = String::from("[");
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str(value.to_json()?.as_str());
+        }
+        json.push(']');
+        Ok(Text::json(json))
+    }
+
+    /// Builds a JSON object from label/value `pairs` like `json_object()`, keeping the pairs
+    /// in order. Values that are already JSON are nested as-is.
+    pub fn json_object(pairs: &[(String, Value)]) -> crate::Result<Text> {
+        let mut json = String::from("{");
+        for (i, (label, value)) in pairs.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str(Value::build_text(label).to_json()?.as_str());
+            json.push(':');
+            json.push_str(value.to_json()?.as_str());
+        }
+        json.push('}');
+        Ok(Text::json(json))
+    }
+
+    /// Evaluates a SQLite-style JSON `path` such as `$.a.b[0]` against this text, which must
+    /// hold well-formed JSON. Scalars come back as SQL values, objects and arrays as JSON text,
+    /// and a missing path as NULL.
+    pub fn json_extract(&self, path: &str) -> crate::Result<Value> {
+        json_extract(
+            &Value::Text(self.clone()),
+            &[Register::Value(Value::build_text(path))],
+            &JsonCacheCell::new(),
+        )
+    }
+
+    /// Returns the JSON type (`object`, `array`, `integer`, `real`, `true`, `false`, `null` or
+    /// `text`) of the root, or of the element at `path`. A missing path gives NULL.
+    pub fn json_type(&self, path: Option<&str>) -> crate::Result<Value> {
+        json_type(
+            &Value::Text(self.clone()),
+            path.map(Value::build_text).as_ref(),
+        )
+    }
+
+    /// Returns the number of elements in the root array, or in the array at `path`. Other JSON
+    /// values have length 0, and a missing path gives NULL.
+    pub fn json_array_length(&self, path: Option<&str>) -> crate::Result<Value> {
+        json_array_length(
+            &Value::Text(self.clone()),
+            path.map(Value::build_text).as_ref(),
+            &JsonCacheCell::new(),
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1506,4 +1629,135 @@ mod tests {
 
         assert_eq!(result.unwrap().to_text().unwrap(), r#"{"field":"value"}"#,);
     }
+
+    #[test]
+    fn test_text_json_extract() {
+        let json = Text::json(r#"{"a":{"b":[10,2.5,"x",{"c":null}]}}"#.to_string());
+        assert_eq!(json.json_extract("$.a.b[0]").unwrap(), Value::Integer(10));
+        assert_eq!(json.json_extract("$.a.b[1]").unwrap(), Value::Float(2.5));
+        assert_eq!(
+            json.json_extract("$.a.b[2]").unwrap(),
+            Value::build_text("x")
+        );
+        assert_eq!(json.json_extract("$.a.b[3].c").unwrap(), Value::Null);
+        assert_eq!(json.json_extract("$.missing").unwrap(), Value::Null);
+
+        let Value::Text(object) = json.json_extract("$.a").unwrap() else {
+            panic!("expected text");
+        };
+        assert_eq!(object.subtype, TextSubtype::Json);
+        assert_eq!(object.as_str(), r#"{"b":[10,2.5,"x",{"c":null}]}"#);
+
+        assert!(Text::json("{".to_string()).json_extract("$").is_err());
+    }
+
+    #[test]
+    fn test_text_json_type() {
+        let json = Text::json("[1,2]".to_string());
+        assert_eq!(json.json_type(None).unwrap().to_string(), "array");
+        assert_eq!(json.json_type(Some("$[0]")).unwrap().to_string(), "integer");
+        assert_eq!(json.json_type(Some("$[5]")).unwrap(), Value::Null);
+
+        let json = Text::json(r#"{"a":1.5,"b":"x","c":true,"d":null}"#.to_string());
+        assert_eq!(json.json_type(None).unwrap().to_string(), "object");
+        assert_eq!(json.json_type(Some("$.a")).unwrap().to_string(), "real");
+        assert_eq!(json.json_type(Some("$.b")).unwrap().to_string(), "text");
+        assert_eq!(json.json_type(Some("$.c")).unwrap().to_string(), "true");
+        assert_eq!(json.json_type(Some("$.d")).unwrap().to_string(), "null");
+    }
+
+    #[test]
+    fn test_text_json_array_length() {
+        let json = Text::json("[1,2,3]".to_string());
+        assert_eq!(json.json_array_length(None).unwrap(), Value::Integer(3));
+
+        let json = Text::json(r#"{"a":[1,2],"b":4}"#.to_string());
+        assert_eq!(json.json_array_length(None).unwrap(), Value::Integer(0));
+        assert_eq!(
+            json.json_array_length(Some("$.a")).unwrap(),
+            Value::Integer(2)
+        );
+        assert_eq!(
+            json.json_array_length(Some("$.b")).unwrap(),
+            Value::Integer(0)
+        );
+        assert_eq!(json.json_array_length(Some("$.c")).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_value_json_valid() {
+        assert_eq!(
+            Value::build_text(r#"{"a":[1,2]}"#).json_valid(),
+            Value::Integer(1)
+        );
+        assert_eq!(Value::build_text("[1,2").json_valid(), Value::Integer(0));
+        assert_eq!(Value::build_text("").json_valid(), Value::Integer(0));
+        assert_eq!(Value::Null.json_valid(), Value::Null);
+
+        let Value::Text(text) = Value::build_text("[1]").into_json_if_valid() else {
+            panic!("expected text");
+        };
+        assert_eq!(text.subtype, TextSubtype::Json);
+        let Value::Text(text) = Value::build_text("[1").into_json_if_valid() else {
+            panic!("expected text");
+        };
+        assert_eq!(text.subtype, TextSubtype::Text);
+        assert_eq!(Value::Integer(1).into_json_if_valid(), Value::Integer(1));
+    }
+
+    #[test]
+    fn test_value_to_json() {
+        let text = Value::build_text("a\"b\\c\n\u{1}é").to_json().unwrap();
+        assert_eq!(text.as_str(), r#""a\"b\\c\n\u0001é""#);
+        assert_eq!(text.subtype, TextSubtype::Json);
+        assert!(matches!(Value::Text(text).json_valid(), Value::Integer(1)));
+
+        assert_eq!(Value::Integer(-42).to_json().unwrap().as_str(), "-42");
+        assert_eq!(Value::Float(1.5).to_json().unwrap().as_str(), "1.5");
+        assert_eq!(
+            Value::Float(f64::INFINITY).to_json().unwrap().as_str(),
+            "9.0e+999"
+        );
+        assert_eq!(Value::Null.to_json().unwrap().as_str(), "null");
+
+        let json = Value::Text(Text::json("[1,2]".to_string()));
+        assert_eq!(json.to_json().unwrap().as_str(), "[1,2]");
+
+        assert!(Value::Blob(vec![1]).to_json().is_err());
+    }
+
+    #[test]
+    fn test_text_json_object_and_array() {
+        let array = Text::json_array(&[
+            Value::Integer(1),
+            Value::build_text("two"),
+            Value::Null,
+            Value::Float(2.5),
+        ])
+        .unwrap();
+        assert_eq!(array.as_str(), r#"[1,"two",null,2.5]"#);
+        assert_eq!(array.subtype, TextSubtype::Json);
+
+        let object = Text::json_object(&[
+            ("list".to_string(), Value::Text(array)),
+            ("quote\"d".to_string(), Value::build_text("a\\b")),
+            (
+                "empty".to_string(),
+                Value::Text(Text::json_array(&[]).unwrap()),
+            ),
+        ])
+        .unwrap();
+        assert_eq!(
+            object.as_str(),
+            r#"{"list":[1,"two",null,2.5],"quote\"d":"a\\b","empty":[]}"#
+        );
+        assert_eq!(object.subtype, TextSubtype::Json);
+        assert_eq!(
+            object.json_extract("$.list[1]").unwrap(),
+            Value::build_text("two")
+        );
+
+        assert_eq!(Text::json_object(&[]).unwrap().as_str(), "{}");
+        assert!(Text::json_array(&[Value::Blob(vec![1])]).is_err());
+    }
 }
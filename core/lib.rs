@@ -39,6 +39,7 @@ use crate::{fast_lock::SpinLock, translate::optimizer::optimize_plan};
 use core::str;
 pub use error::LimboError;
 use fallible_iterator::FallibleIterator;
+pub use functions::printf::sql_printf;
 pub use io::clock::{Clock, Instant};
 #[cfg(all(feature = "fs", target_family = "unix"))]
 pub use io::UnixIO;
@@ -48,6 +49,7 @@ pub use io::{
     Buffer, Completion, File, MemoryIO, OpenFlags, PlatformIO, SyscallIO, WriteCompletion, IO,
 };
 use limbo_sqlite3_parser::{ast, ast::Cmd, lexer::sql::Parser};
+pub use parameters::Params;
 use parking_lot::RwLock;
 use schema::Schema;
 use std::{
@@ -65,6 +67,7 @@ use storage::btree::{btree_init_page, BTreePageInner};
 #[cfg(feature = "fs")]
 use storage::database::DatabaseFile;
 pub use storage::pager::PagerCacheflushStatus;
+pub use storage::sqlite3_ondisk::records_in_page;
 pub use storage::{
     buffer_pool::BufferPool,
     database::DatabaseStorage,
@@ -79,11 +82,13 @@ use storage::{
 };
 use tracing::{instrument, Level};
 use translate::select::prepare_select_plan;
+pub use types::DisplayOptions;
 pub use types::RefValue;
 pub use types::Value;
 use util::parse_schema_rows;
 use vdbe::builder::QueryMode;
 use vdbe::builder::TableRefIdCounter;
+pub use vdbe::sorter::{MergedRuns, SpillableSorter};
 
 pub type Result<T, E = LimboError> = std::result::Result<T, E>;
 pub static DATABASE_VERSION: OnceLock<String> = OnceLock::new();
@@ -808,6 +813,22 @@ impl Statement {
         self.state.bind_at(index, value);
     }
 
+    /// Binds every value in `params`. Named values are looked up in the statement's
+    /// parameters, and an unknown name is an error.
+    pub fn bind_params(&mut self, params: &Params) -> Result<()> {
+        for (i, value) in params.values().iter().enumerate() {
+            let index = NonZero::new(i + 1).unwrap();
+            self.state.bind_at(index, value.clone());
+        }
+        for (name, value) in params.named() {
+            let index = self.program.parameters.index(name).ok_or_else(|| {
+                LimboError::InvalidArgument(format!("no such parameter: {}", name))
+            })?;
+            self.state.bind_at(index, value.clone());
+        }
+        Ok(())
+    }
+
     pub fn reset(&mut self) {
         self.state.reset();
     }
@@ -46,6 +46,15 @@ pub enum Numeric {
 }
 
 impl Numeric {
+    /// The value as a float, with NULL read as zero.
+    pub fn into_f64(self) -> f64 {
+        match self {
+            Numeric::Null => 0.0,
+            Numeric::Integer(v) => v as f64,
+            Numeric::Float(v) => v.into(),
+        }
+    }
+
     pub fn try_into_bool(&self) -> Option<bool> {
         match self {
             Numeric::Null => None,
@@ -1,5 +1,7 @@
 use std::num::NonZero;
 
+use crate::types::Value;
+
 pub const PARAM_PREFIX: &str = "__param_";
 
 #[derive(Clone, Debug)]
@@ -112,7 +114,7 @@ impl Parameters {
             index => {
                 // SAFETY: Guaranteed from parser that the index is bigger than 0.
                 let index: NonZero<usize> = index.parse().unwrap();
-                if index > self.index {
+                if index >= self.index {
                     self.index = index.checked_add(1).unwrap();
                 }
                 self.list.push(Parameter::Indexed(index));
@@ -122,3 +124,124 @@ impl Parameters {
         }
     }
 }
+
+/// Values collected for a statement's parameters before they are bound. Positional values
+/// are stored by their 1-based index; named values are resolved against the statement's
+/// [Parameters] when bound with [crate::Statement::bind_params].
+#[derive(Clone, Debug, Default)]
+pub struct Params {
+    positional: Vec<Value>,
+    named: Vec<(String, Value)>,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Binds any value convertible into a [Value], including `Option<T>` where `None`
+    /// binds NULL. Indices left unbound below the highest bound one read as NULL.
+    pub fn bind(&mut self, index: NonZero<usize>, value: impl Into<Value>) -> &mut Self {
+        let slot = index.get() - 1;
+        if slot >= self.positional.len() {
+            self.positional.resize(slot + 1, Value::Null);
+        }
+        self.positional[slot] = value.into();
+        self
+    }
+
+    pub fn bind_int(&mut self, index: NonZero<usize>, value: i64) -> &mut Self {
+        self.bind(index, value)
+    }
+
+    pub fn bind_text(&mut self, index: NonZero<usize>, value: impl Into<String>) -> &mut Self {
+        self.bind(index, value.into())
+    }
+
+    pub fn bind_blob(&mut self, index: NonZero<usize>, value: impl Into<Vec<u8>>) -> &mut Self {
+        self.bind(index, value.into())
+    }
+
+    pub fn bind_null(&mut self, index: NonZero<usize>) -> &mut Self {
+        self.bind(index, Value::Null)
+    }
+
+    /// Binds a named parameter such as `:name`, `@name` or `$name`. The name must include
+    /// its prefix, as it appears in the SQL text.
+    pub fn bind_named(&mut self, name: impl Into<String>, value: impl Into<Value>) -> &mut Self {
+        let name = name.into();
+        let value = value.into();
+        match self.named.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.named.push((name, value)),
+        }
+        self
+    }
+
+    pub fn get(&self, index: NonZero<usize>) -> Option<&Value> {
+        self.positional.get(index.get() - 1)
+    }
+
+    pub fn get_named(&self, name: &str) -> Option<&Value> {
+        self.named.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    /// The positional values, where element `i` is bound to parameter `i + 1`.
+    pub fn values(&self) -> &[Value] {
+        &self.positional
+    }
+
+    pub(crate) fn named(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.named.iter().map(|(n, v)| (n.as_str(), v))
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.positional
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idx(i: usize) -> NonZero<usize> {
+        NonZero::new(i).unwrap()
+    }
+
+    #[test]
+    fn test_params_bind_and_read_back() {
+        let mut params = Params::new();
+        params
+            .bind_int(idx(1), 42)
+            .bind_text(idx(2), "hello")
+            .bind_blob(idx(4), vec![1, 2, 3])
+            .bind(idx(5), None::<i64>)
+            .bind(idx(6), Some(1.5))
+            .bind_named(":name", "limbo");
+
+        assert_eq!(params.get(idx(1)), Some(&Value::Integer(42)));
+        assert_eq!(params.get(idx(2)), Some(&Value::build_text("hello")));
+        assert_eq!(params.get(idx(3)), Some(&Value::Null));
+        assert_eq!(params.get(idx(4)), Some(&Value::Blob(vec![1, 2, 3])));
+        assert_eq!(params.get(idx(5)), Some(&Value::Null));
+        assert_eq!(params.get(idx(6)), Some(&Value::Float(1.5)));
+        assert_eq!(params.get(idx(7)), None);
+        assert_eq!(params.get_named(":name"), Some(&Value::build_text("limbo")));
+        assert_eq!(params.get_named(":other"), None);
+
+        params.bind_null(idx(1)).bind_named(":name", 7);
+        assert_eq!(params.get(idx(1)), Some(&Value::Null));
+        assert_eq!(params.get_named(":name"), Some(&Value::Integer(7)));
+        assert_eq!(params.into_values().len(), 6);
+    }
+
+    #[test]
+    fn test_named_after_indexed_gets_next_index() {
+        let mut parameters = Parameters::new();
+        assert_eq!(parameters.push("1"), idx(1));
+        assert_eq!(parameters.push(":named"), idx(2));
+        assert_eq!(parameters.push("3"), idx(3));
+        assert_eq!(parameters.count(), 3);
+        assert_eq!(parameters.index(":named"), Some(idx(2)));
+    }
+}
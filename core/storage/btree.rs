@@ -16,7 +16,9 @@ use crate::{
 
 use crate::{
     return_corrupt,
-    types::{compare_immutable, CursorResult, ImmutableRecord, RefValue, SeekKey, SeekOp, Value},
+    types::{
+        compare_immutable, CursorResult, ImmutableRecord, Record, RefValue, SeekKey, SeekOp, Value,
+    },
     LimboError, Result,
 };
 
@@ -390,6 +392,7 @@ enum OverflowState {
 
 /// Holds a Record or RowId, so that these can be transformed into a SeekKey to restore
 /// cursor position to its previous location.
+#[derive(Clone)]
 enum CursorContext {
     TableRowId(i64),
 
@@ -4278,6 +4281,111 @@ impl BTreeCursor {
         self.reusable_immutable_record.borrow()
     }
 
+    /// Returns the rowid and record of the row a table cursor is on together, or `None` when
+    /// it is not on a row.
+    pub fn current(&self) -> Result<Option<(i64, Ref<ImmutableRecord>)>> {
+        let Some(rowid) = self.rowid()? else {
+            return Ok(None);
+        };
+        Ok(Ref::filter_map(self.record(), Option::as_ref)
+            .ok()
+            .map(|record| (rowid, record)))
+    }
+
+    /// Like [BTreeCursor::seek], but also reports how the entry the cursor landed on compares
+    /// to `key`: Equal for an exact match, Greater or Less for a range seek that landed past
+    /// it. When no entry qualifies there is nothing to compare; the returned ordering is then
+    /// that of the end the seek ran off, as if a sentinel entry sat past it: Greater for
+    /// EQ/GE/GT, which search forwards, and Less for LE/LT, which search backwards.
+    pub fn seek_cmp(
+        &mut self,
+        key: SeekKey<'_>,
+        op: SeekOp,
+    ) -> Result<CursorResult<(bool, std::cmp::Ordering)>> {
+        let found = return_if_io!(self.seek(key.clone(), op));
+        if !found {
+            let ordering = match op.iteration_direction() {
+                IterationDirection::Forwards => std::cmp::Ordering::Greater,
+                IterationDirection::Backwards => std::cmp::Ordering::Less,
+            };
+            return Ok(CursorResult::Ok((false, ordering)));
+        }
+        let ordering = match key {
+            SeekKey::TableRowId(rowid) => self
+                .rowid()?
+                .expect("cursor positioned by a successful seek has a rowid")
+                .cmp(&rowid),
+            SeekKey::IndexKey(index_key) => {
+                let record = self.record();
+                let record_values = record.as_ref().unwrap().get_values();
+                compare_immutable(
+                    &record_values[..index_key.get_values().len()],
+                    index_key.get_values(),
+                    self.key_sort_order(),
+                    &self.collations,
+                )
+            }
+        };
+        Ok(CursorResult::Ok((true, ordering)))
+    }
+
+    /// Seeks to the last entry satisfying `op` (which must be LE or LT) against `key`,
+    /// leaving the cursor there so that subsequent [BTreeCursor::prev] calls walk the
+    /// remaining entries in descending order, as `ORDER BY ... DESC` with a start bound does.
+    /// Returns false if no entry qualifies, and an error for a forward `op`.
+    pub fn seek_for_descending(
+        &mut self,
+        key: SeekKey<'_>,
+        op: SeekOp,
+    ) -> Result<CursorResult<bool>> {
+        if op.iteration_direction() != IterationDirection::Backwards {
+            return Err(LimboError::InternalError(format!(
+                "descending seek needs LE or LT, got {:?}",
+                op
+            )));
+        }
+        self.seek(key, op)
+    }
+
+    /// Returns an owned copy of the entry that the next call to [BTreeCursor::next] would
+    /// move to, without moving the cursor. Returns `None` when the cursor is not on an entry
+    /// or is already on the last one.
+    pub fn peek_next(&mut self) -> Result<CursorResult<Option<Record>>> {
+        assert!(self.mv_cursor.is_none());
+        // Finish repositioning left pending by an earlier peek that hit IO.
+        return_if_io!(self.restore_context());
+        if self.is_empty() {
+            return Ok(CursorResult::Ok(None));
+        }
+        self.save_context();
+        let saved = self.context.clone();
+        return_if_io!(self.next());
+        let peeked = if self.is_empty() {
+            None
+        } else {
+            self.record()
+                .as_ref()
+                .map(|record| record.get_values().iter().map(RefValue::to_owned).collect())
+        };
+        self.context = saved;
+        self.valid_state = CursorValidState::RequireSeek;
+        return_if_io!(self.restore_context());
+        Ok(CursorResult::Ok(peeked))
+    }
+
+    /// Point lookup by rowid: seeks to `rowid` and returns an owned copy of that row's record,
+    /// or `None` if the table has no row with that rowid.
+    pub fn get(&mut self, rowid: i64) -> Result<CursorResult<Option<Record>>> {
+        let found = return_if_io!(self.seek(SeekKey::TableRowId(rowid), SeekOp::EQ));
+        if !found || self.rowid()? != Some(rowid) {
+            return Ok(CursorResult::Ok(None));
+        }
+        let record = self.record();
+        Ok(CursorResult::Ok(record.as_ref().map(|record| {
+            record.get_values().iter().map(RefValue::to_owned).collect()
+        })))
+    }
+
     #[instrument(skip_all, level = Level::TRACE)]
     pub fn insert(
         &mut self,
@@ -4663,6 +4771,39 @@ impl BTreeCursor {
         }
     }
 
+    /// Looks up an index entry whose leading columns equal `key`, which holds the index
+    /// columns without the rowid, and returns the rowid stored with it. A UNIQUE constraint
+    /// check uses this to report the row a new key would conflict with. NULLs are distinct
+    /// under UNIQUE, so a key with a NULL column never conflicts and gives `None`.
+    pub fn find_unique(&mut self, key: &ImmutableRecord) -> Result<CursorResult<Option<i64>>> {
+        if key.get_values().iter().any(|v| matches!(v, RefValue::Null)) {
+            return Ok(CursorResult::Ok(None));
+        }
+        if !return_if_io!(self.seek(SeekKey::IndexKey(key), SeekOp::GE)) {
+            return Ok(CursorResult::Ok(None));
+        }
+        let record = self.record();
+        let Some(record) = record.as_ref() else {
+            return Ok(CursorResult::Ok(None));
+        };
+        let (values, key_values) = (record.get_values(), key.get_values());
+        if values.len() <= key_values.len()
+            || compare_immutable(
+                &values[..key_values.len()],
+                key_values,
+                self.key_sort_order(),
+                &self.collations,
+            )
+            .is_ne()
+        {
+            return Ok(CursorResult::Ok(None));
+        }
+        Ok(CursorResult::Ok(match values.last() {
+            Some(RefValue::Integer(rowid)) => Some(*rowid),
+            _ => None,
+        }))
+    }
+
     pub fn exists(&mut self, key: &Value) -> Result<CursorResult<bool>> {
         assert!(self.mv_cursor.is_none());
         let int_key = match key {
@@ -8293,6 +8434,273 @@ mod tests {
         );
     }
 
+    /// Builds a table btree holding rowids `1..=rows`, where row `i` is `(i, 'row{i}')`.
+    fn btree_with_rows(rows: i64) -> (Rc<Pager>, usize) {
+        let (pager, root_page) = empty_btree();
+        let mut cursor = BTreeCursor::new_table(None, pager.clone(), root_page);
+        for i in 1..=rows {
+            let value = ImmutableRecord::from_registers(&[
+                Register::Value(Value::Integer(i)),
+                Register::Value(Value::build_text(format!("row{i}"))),
+            ]);
+            run_until_done(
+                || cursor.move_to(SeekKey::TableRowId(i), SeekOp::EQ),
+                pager.deref(),
+            )
+            .unwrap();
+            run_until_done(
+                || cursor.insert(&BTreeKey::new_table_rowid(i, Some(&value)), true),
+                pager.deref(),
+            )
+            .unwrap();
+        }
+        (pager, root_page)
+    }
+
+    #[test]
+    fn test_cursor_seek_for_descending() {
+        let (pager, root_page) = btree_with_rows(500);
+        let mut cursor = BTreeCursor::new_table(None, pager.clone(), root_page);
+
+        let mut descending = |key: i64, op: SeekOp, n: usize| {
+            let mut rowids = Vec::new();
+            let found = run_until_done(
+                || cursor.seek_for_descending(SeekKey::TableRowId(key), op),
+                pager.deref(),
+            )
+            .unwrap();
+            if !found {
+                return rowids;
+            }
+            while rowids.len() < n {
+                let Some(rowid) = cursor.rowid().unwrap() else {
+                    break;
+                };
+                rowids.push(rowid);
+                run_until_done(|| cursor.prev(), pager.deref()).unwrap();
+            }
+            rowids
+        };
+
+        assert_eq!(descending(i64::MAX, SeekOp::LE, 3), vec![500, 499, 498]);
+        assert_eq!(descending(250, SeekOp::LE, 3), vec![250, 249, 248]);
+        assert_eq!(descending(250, SeekOp::LT, 3), vec![249, 248, 247]);
+        assert_eq!(descending(2, SeekOp::LE, 5), vec![2, 1]);
+        assert_eq!(descending(1, SeekOp::LT, 3), Vec::<i64>::new());
+
+        assert!(matches!(
+            cursor.seek_for_descending(SeekKey::TableRowId(250), SeekOp::GE),
+            Err(LimboError::InternalError(_))
+        ));
+    }
+
+    #[test]
+    fn test_cursor_peek_next() {
+        let (pager, root_page) = btree_with_rows(500);
+        let mut cursor = BTreeCursor::new_table(None, pager.clone(), root_page);
+        run_until_done(|| cursor.rewind(), pager.deref()).unwrap();
+
+        for expected in 1..=500 {
+            assert_eq!(cursor.rowid().unwrap(), Some(expected));
+            let peeked = run_until_done(|| cursor.peek_next(), pager.deref()).unwrap();
+            assert_eq!(cursor.rowid().unwrap(), Some(expected));
+            run_until_done(|| cursor.next(), pager.deref()).unwrap();
+            match peeked {
+                Some(record) => {
+                    let current = cursor.record();
+                    let current = current.as_ref().unwrap();
+                    assert_eq!(record.get_value(0), &Value::Integer(expected + 1));
+                    assert_eq!(
+                        *record.get_values(),
+                        current
+                            .get_values()
+                            .iter()
+                            .map(RefValue::to_owned)
+                            .collect::<Vec<_>>()
+                    );
+                }
+                None => {
+                    assert_eq!(expected, 500);
+                    assert!(cursor.is_empty());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cursor_current() {
+        let (pager, root_page) = btree_with_rows(300);
+        let mut cursor = BTreeCursor::new_table(None, pager.clone(), root_page);
+        assert!(cursor.current().unwrap().is_none());
+        run_until_done(|| cursor.rewind(), pager.deref()).unwrap();
+
+        for expected in 1..=300 {
+            {
+                let (rowid, record) = cursor.current().unwrap().unwrap();
+                assert_eq!(rowid, expected);
+                assert_eq!(Some(rowid), cursor.rowid().unwrap());
+                assert_eq!(
+                    record.get_payload(),
+                    cursor.record().as_ref().unwrap().get_payload()
+                );
+                assert_eq!(
+                    record.get_values()[1].to_owned(),
+                    Value::build_text(format!("row{expected}"))
+                );
+            }
+            run_until_done(|| cursor.next(), pager.deref()).unwrap();
+        }
+        assert!(cursor.current().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_cursor_find_unique() {
+        use crate::schema::IndexColumn;
+        use limbo_sqlite3_parser::ast::SortOrder;
+
+        let (pager, _) = empty_btree();
+        let root_page = pager.btree_create(&CreateBTreeFlags::new_index()) as usize;
+        let index = Index {
+            name: "t_key".to_string(),
+            table_name: "t".to_string(),
+            root_page,
+            columns: vec![IndexColumn {
+                name: "key".to_string(),
+                order: SortOrder::Asc,
+                pos_in_table: 0,
+                collation: None,
+            }],
+            unique: true,
+            ephemeral: false,
+            has_rowid: true,
+        };
+        let mut cursor = BTreeCursor::new_index(
+            None,
+            pager.clone(),
+            root_page,
+            &index,
+            vec![CollationSeq::Binary],
+        );
+        let index_key = |values: &[Value]| {
+            ImmutableRecord::from_registers(
+                &values
+                    .iter()
+                    .cloned()
+                    .map(Register::Value)
+                    .collect::<Vec<_>>(),
+            )
+        };
+        for rowid in 1..=101 {
+            let key = if rowid == 101 {
+                Value::Null
+            } else {
+                Value::build_text(format!("key{:03}", rowid * 2))
+            };
+            let entry = index_key(&[key, Value::Integer(rowid)]);
+            run_until_done(
+                || {
+                    cursor.insert(
+                        &BTreeKey::new_index_key(&entry),
+                        cursor.is_write_in_progress(),
+                    )
+                },
+                pager.deref(),
+            )
+            .unwrap();
+            cursor.move_to_root();
+        }
+
+        let duplicate = index_key(&[Value::build_text("key084")]);
+        let found = run_until_done(|| cursor.find_unique(&duplicate), pager.deref()).unwrap();
+        assert_eq!(found, Some(42));
+
+        let missing = index_key(&[Value::build_text("key085")]);
+        let found = run_until_done(|| cursor.find_unique(&missing), pager.deref()).unwrap();
+        assert_eq!(found, None);
+
+        let past_end = index_key(&[Value::build_text("key999")]);
+        let found = run_until_done(|| cursor.find_unique(&past_end), pager.deref()).unwrap();
+        assert_eq!(found, None);
+
+        // The index holds a NULL key, but NULLs never conflict under UNIQUE.
+        let null_key = index_key(&[Value::Null]);
+        let found = run_until_done(|| cursor.find_unique(&null_key), pager.deref()).unwrap();
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn test_cursor_seek_cmp() {
+        use std::cmp::Ordering;
+
+        let (pager, root_page) = empty_btree();
+        let mut cursor = BTreeCursor::new_table(None, pager.clone(), root_page);
+        for i in (10..=1000).step_by(10) {
+            let value = ImmutableRecord::from_registers(&[Register::Value(Value::Integer(i))]);
+            run_until_done(
+                || cursor.move_to(SeekKey::TableRowId(i), SeekOp::EQ),
+                pager.deref(),
+            )
+            .unwrap();
+            run_until_done(
+                || cursor.insert(&BTreeKey::new_table_rowid(i, Some(&value)), true),
+                pager.deref(),
+            )
+            .unwrap();
+        }
+
+        let mut seek_cmp = |rowid: i64, op: SeekOp| {
+            let result = run_until_done(
+                || cursor.seek_cmp(SeekKey::TableRowId(rowid), op),
+                pager.deref(),
+            )
+            .unwrap();
+            (result, cursor.rowid().unwrap())
+        };
+
+        assert_eq!(
+            seek_cmp(500, SeekOp::EQ),
+            ((true, Ordering::Equal), Some(500))
+        );
+        assert_eq!(
+            seek_cmp(500, SeekOp::GE),
+            ((true, Ordering::Equal), Some(500))
+        );
+        assert_eq!(
+            seek_cmp(500, SeekOp::GT),
+            ((true, Ordering::Greater), Some(510))
+        );
+        assert_eq!(
+            seek_cmp(505, SeekOp::GE),
+            ((true, Ordering::Greater), Some(510))
+        );
+        assert_eq!(
+            seek_cmp(505, SeekOp::LE),
+            ((true, Ordering::Less), Some(500))
+        );
+        assert_eq!(seek_cmp(1000, SeekOp::GT).0, (false, Ordering::Greater));
+        assert_eq!(seek_cmp(10, SeekOp::LT).0, (false, Ordering::Less));
+        assert_eq!(seek_cmp(505, SeekOp::EQ).0, (false, Ordering::Greater));
+    }
+
+    #[test]
+    fn test_cursor_get() {
+        let (pager, root_page) = btree_with_rows(500);
+        let mut cursor = BTreeCursor::new_table(None, pager.clone(), root_page);
+
+        let record = run_until_done(|| cursor.get(321), pager.deref())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            record.get_values(),
+            &[Value::Integer(321), Value::build_text("row321")]
+        );
+
+        let missing = run_until_done(|| cursor.get(501), pager.deref()).unwrap();
+        assert!(missing.is_none());
+        let missing = run_until_done(|| cursor.get(0), pager.deref()).unwrap();
+        assert!(missing.is_none());
+    }
+
     fn run_until_done<T>(
         mut action: impl FnMut() -> Result<CursorResult<T>>,
         pager: &Pager,
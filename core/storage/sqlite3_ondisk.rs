@@ -1091,6 +1091,38 @@ impl<'a, T: Default + Copy, const N: usize> Iterator for SmallVecIter<'a, T, N>
     }
 }
 
+/// Lazily decodes the records of a table leaf page. Each offset points at the start of a cell
+/// (payload size varint, rowid varint, payload) and the records are yielded in offset order.
+/// A cell that does not fit on the page, including one whose payload spills onto overflow
+/// pages, is reported as a corrupt record, since only the page itself is available here.
+pub fn records_in_page<'a>(
+    page: &'a [u8],
+    cell_offsets: &'a [usize],
+) -> impl Iterator<Item = Result<crate::types::Record>> + 'a {
+    cell_offsets.iter().map(move |&offset| {
+        let corrupt =
+            |reason: &str| LimboError::CorruptRecord(format!("cell at offset {offset} {reason}"));
+        let cell = page
+            .get(offset..)
+            .ok_or_else(|| corrupt("is past the end of the page"))?;
+        let (payload_size, nr) =
+            read_varint(cell).map_err(|_| corrupt("has an invalid payload size varint"))?;
+        let (_rowid, rowid_len) =
+            read_varint(&cell[nr..]).map_err(|_| corrupt("has an invalid rowid varint"))?;
+        let start = nr + rowid_len;
+        let payload = usize::try_from(payload_size)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .and_then(|end| cell.get(start..end))
+            .ok_or_else(|| {
+                corrupt(&format!(
+                    "has a {payload_size}-byte payload that does not fit on the page"
+                ))
+            })?;
+        crate::types::Record::deserialize(payload)
+    })
+}
+
 pub fn read_record(payload: &[u8], reuse_immutable: &mut ImmutableRecord) -> Result<()> {
     // Let's clear previous use
     reuse_immutable.invalidate();
@@ -1098,37 +1130,55 @@ pub fn read_record(payload: &[u8], reuse_immutable: &mut ImmutableRecord) -> Res
     // By reusing this immutable record we make it less allocation expensive.
     reuse_immutable.start_serialization(payload);
 
-    let mut pos = 0;
-    let (header_size, nr) = read_varint(payload)?;
-    assert!((header_size as usize) >= nr);
-    let mut header_size = (header_size as usize) - nr;
-    pos += nr;
+    let payload_len = payload.len();
+    let (header_size, nr) = read_varint(payload).map_err(|_| {
+        LimboError::CorruptRecord("invalid header size varint at offset 0".to_string())
+    })?;
+    let header_end = header_size as usize;
+    if header_end < nr || header_end > payload_len {
+        return Err(LimboError::CorruptRecord(format!(
+            "header size {header_size} does not fit a {payload_len}-byte payload"
+        )));
+    }
+    let mut pos = nr;
 
     let mut serial_types = SmallVec::<u64, 64>::new();
-    while header_size > 0 {
-        let (serial_type, nr) = read_varint(&reuse_immutable.get_payload()[pos..])?;
-        validate_serial_type(serial_type)?;
+    while pos < header_end {
+        let (serial_type, nr) = read_varint(&reuse_immutable.get_payload()[pos..header_end])
+            .map_err(|_| {
+                LimboError::CorruptRecord(format!(
+                    "serial type varint at offset {pos} runs past the header end at {header_end}"
+                ))
+            })?;
+        validate_serial_type(serial_type).map_err(|_| {
+            LimboError::CorruptRecord(format!("invalid serial type {serial_type} at offset {pos}"))
+        })?;
         serial_types.push(serial_type);
         pos += nr;
-        assert!(header_size >= nr);
-        header_size -= nr;
     }
 
-    for &serial_type in &serial_types.data[..serial_types.len.min(serial_types.data.len())] {
-        let (value, n) = read_value(&reuse_immutable.get_payload()[pos..], unsafe {
-            serial_type.assume_init().try_into()?
-        })?;
-        pos += n;
+    let mut column = 0;
+    let mut read_column = |serial_type: u64, pos: &mut usize| -> Result<()> {
+        let serial_type = SerialType::try_from(serial_type)?;
+        let size = serial_type.size();
+        if *pos + size > payload_len {
+            return Err(LimboError::CorruptRecord(format!(
+                "column {column} needs {size} bytes at offset {} but the payload is {payload_len} bytes",
+                *pos
+            )));
+        }
+        let (value, n) = read_value(&reuse_immutable.get_payload()[*pos..], serial_type)?;
+        *pos += n;
         reuse_immutable.add_value(value);
+        column += 1;
+        Ok(())
+    };
+    for &serial_type in &serial_types.data[..serial_types.len.min(serial_types.data.len())] {
+        read_column(unsafe { serial_type.assume_init() }, &mut pos)?;
     }
     if let Some(extra) = serial_types.extra_data.as_ref() {
-        for serial_type in extra {
-            let (value, n) = read_value(
-                &reuse_immutable.get_payload()[pos..],
-                (*serial_type).try_into()?,
-            )?;
-            pos += n;
-            reuse_immutable.add_value(value);
+        for &serial_type in extra {
+            read_column(serial_type, &mut pos)?;
         }
     }
 
@@ -1276,8 +1326,10 @@ pub fn read_varint(buf: &[u8]) -> Result<(u64, usize)> {
             }
         }
     }
-    v = (v << 8) + buf[8] as u64;
-    Ok((v, 9))
+    match buf.get(8) {
+        Some(&c) => Ok(((v << 8) + c as u64, 9)),
+        None => crate::bail_corrupt_error!("Invalid varint"),
+    }
 }
 
 pub fn write_varint(buf: &mut [u8], value: u64) -> usize {
@@ -1719,6 +1771,7 @@ pub fn read_u32(buf: &[u8], pos: usize) -> u32 {
 
 #[cfg(test)]
 mod tests {
+    use crate::types::Record;
     use crate::Value;
 
     use super::*;
@@ -1852,4 +1905,107 @@ mod tests {
 
         assert_eq!(small_vec.get(8), None);
     }
+
+    #[test]
+    fn test_records_in_page() {
+        let records = [
+            Record::new(vec![Value::Integer(1), Value::build_text("first")]),
+            Record::new(vec![
+                Value::Null,
+                Value::Float(2.5),
+                Value::Blob(vec![0xde, 0xad]),
+            ]),
+        ];
+        let mut page = vec![0u8; 64];
+        let mut offsets = Vec::new();
+        let mut pos = 8;
+        for (rowid, record) in records.iter().enumerate() {
+            let mut payload = Vec::new();
+            record.serialize(&mut payload);
+            let mut cell = Vec::new();
+            write_varint_to_vec(payload.len() as u64, &mut cell);
+            write_varint_to_vec(rowid as u64 + 1, &mut cell);
+            cell.extend_from_slice(&payload);
+            page[pos..pos + cell.len()].copy_from_slice(&cell);
+            offsets.push(pos);
+            pos += cell.len() + 3;
+        }
+
+        let decoded = records_in_page(&page, &offsets)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(decoded, records);
+
+        let reversed: Vec<usize> = offsets.iter().rev().copied().collect();
+        let mut iter = records_in_page(&page, &reversed);
+        assert_eq!(iter.next().unwrap().unwrap(), records[1]);
+        assert_eq!(iter.next().unwrap().unwrap(), records[0]);
+        assert!(iter.next().is_none());
+
+        let corrupt = |page: &[u8], offsets: &[usize]| {
+            matches!(
+                records_in_page(page, offsets).next(),
+                Some(Err(LimboError::CorruptRecord(_)))
+            )
+        };
+        assert!(corrupt(&page, &[page.len() + 1]));
+        assert!(corrupt(&page[..offsets[1] + 4], &offsets[1..]));
+        // A payload size near u64::MAX must not overflow the end offset.
+        let mut huge = Vec::new();
+        write_varint_to_vec(u64::MAX, &mut huge);
+        write_varint_to_vec(1, &mut huge);
+        assert!(corrupt(&huge, &[0]));
+        assert!(corrupt(&[0x81], &[0]));
+    }
+
+    fn assert_corrupt_record(payload: &[u8], context: &str) {
+        let mut record = ImmutableRecord::new(payload.len(), 0);
+        match read_record(payload, &mut record) {
+            Err(LimboError::CorruptRecord(msg)) => {
+                assert!(msg.contains(context), "unexpected message: {msg}")
+            }
+            other => panic!("expected a corrupt record error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_read_record_truncated_payload() {
+        let mut payload = Vec::new();
+        Record::new(vec![Value::Integer(1_000_000), Value::build_text("hello")])
+            .serialize(&mut payload);
+        for len in 1..payload.len() {
+            let mut record = ImmutableRecord::new(len, 0);
+            assert!(matches!(
+                read_record(&payload[..len], &mut record),
+                Err(LimboError::CorruptRecord(_))
+            ));
+        }
+        assert_corrupt_record(&payload[..payload.len() - 1], "column 1 needs 5 bytes");
+        assert_corrupt_record(&[], "header size varint");
+        assert_corrupt_record(&[0x81], "header size varint");
+        // The header claims more bytes than the payload holds.
+        assert_corrupt_record(&[5, 1], "header size 5");
+        // The last serial type varint is cut off by the header end.
+        assert_corrupt_record(&[3, 1, 0x81], "serial type varint at offset 2");
+        assert_corrupt_record(&[2, 10], "invalid serial type 10 at offset 1");
+    }
+
+    #[test]
+    fn test_read_record_overlong_text() {
+        // Serial type 13 + 2 * 100 is a 100-byte text, but only 3 bytes of content follow.
+        let mut payload = Vec::new();
+        write_varint_to_vec(3, &mut payload);
+        write_varint_to_vec(13 + 2 * 100, &mut payload);
+        payload.extend_from_slice(b"abc");
+        assert_corrupt_record(&payload, "column 0 needs 100 bytes at offset 3");
+
+        // A text that would run past the end of the payload after a valid column.
+        let mut payload = Vec::new();
+        write_varint_to_vec(3, &mut payload);
+        write_varint_to_vec(1, &mut payload);
+        write_varint_to_vec(13 + 2 * 10, &mut payload);
+        payload.push(7);
+        payload.extend_from_slice(b"short");
+        assert_corrupt_record(&payload, "column 1 needs 10 bytes at offset 4");
+    }
 }
@@ -14,7 +14,7 @@ use crate::{
     schema::{Index, IndexColumn, Schema, Table},
     translate::{expr::walk_expr_mut, plan::TerminationKey},
     types::SeekOp,
-    Result,
+    Result, Value,
 };
 
 use super::{
@@ -654,23 +654,11 @@ impl Optimizable for ast::Expr {
                 }
                 ast::Literal::String(s) => {
                     let without_quotes = s.trim_matches('\'');
-                    if let Ok(int_value) = without_quotes.parse::<i64>() {
-                        return Ok(Some(if int_value == 0 {
-                            AlwaysTrueOrFalse::AlwaysFalse
-                        } else {
-                            AlwaysTrueOrFalse::AlwaysTrue
-                        }));
-                    }
-
-                    if let Ok(float_value) = without_quotes.parse::<f64>() {
-                        return Ok(Some(if float_value == 0.0 {
-                            AlwaysTrueOrFalse::AlwaysFalse
-                        } else {
-                            AlwaysTrueOrFalse::AlwaysTrue
-                        }));
-                    }
-
-                    Ok(Some(AlwaysTrueOrFalse::AlwaysFalse))
+                    Ok(Some(if Value::build_text(without_quotes).is_truthy() {
+                        AlwaysTrueOrFalse::AlwaysTrue
+                    } else {
+                        AlwaysTrueOrFalse::AlwaysFalse
+                    }))
                 }
                 _ => Ok(None),
             },
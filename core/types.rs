@@ -3,20 +3,35 @@ use limbo_sqlite3_parser::ast::SortOrder;
 
 use crate::error::LimboError;
 use crate::ext::{ExtValue, ExtValueType};
+use crate::numeric::Numeric;
 use crate::pseudo::PseudoCursor;
-use crate::schema::Index;
+use crate::schema::{Affinity, Index};
 use crate::storage::btree::BTreeCursor;
-use crate::storage::sqlite3_ondisk::write_varint;
+use crate::storage::sqlite3_ondisk::{read_record, write_varint, write_varint_to_vec};
 use crate::translate::collate::CollationSeq;
 use crate::translate::plan::IterationDirection;
+use crate::util::{
+    cast_real_to_integer, cast_text_to_numeric, checked_cast_text_to_numeric,
+    checked_cast_text_to_numeric_exact, RoundToPrecision,
+};
+use crate::vdbe::likeop::exec_glob;
 use crate::vdbe::sorter::Sorter;
 use crate::vdbe::Register;
 use crate::vtab::VirtualTableCursor;
 use crate::Result;
+use std::borrow::Cow;
 use std::fmt::Display;
 
 const MAX_REAL_SIZE: u8 = 15;
 
+fn cast_text_to_numeric_f64(text: &str) -> f64 {
+    match cast_text_to_numeric(text) {
+        Value::Integer(i) => i as f64,
+        Value::Float(f) => f,
+        _ => unreachable!(),
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ValueType {
     Null,
@@ -42,18 +57,143 @@ impl Display for ValueType {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TextSubtype {
     Text,
     #[cfg(feature = "json")]
     Json,
 }
 
+/// A database text encoding, as stored in the header's text encoding field. Text is held as
+/// UTF-8 in memory, but BINARY comparisons follow the byte order of the stored encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16le,
+    Utf16be,
+}
+
+impl TryFrom<u32> for Encoding {
+    type Error = LimboError;
+
+    fn try_from(value: u32) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Utf8),
+            2 => Ok(Self::Utf16le),
+            3 => Ok(Self::Utf16be),
+            _ => Err(LimboError::InvalidArgument(format!(
+                "unknown text encoding {value}"
+            ))),
+        }
+    }
+}
+
+/// Rendering choices for [Value::format_with], for tools such as the CLI that print values.
+/// The default renders values the same way as [Display].
+#[derive(Debug, Clone, Default)]
+pub struct DisplayOptions {
+    /// Longest blob prefix to show, in bytes. Longer blobs are cut and end with "...".
+    pub max_blob_bytes: Option<usize>,
+    /// What to print for NULL.
+    pub null_text: String,
+    /// Fixed number of digits after the decimal point for finite floats.
+    pub float_precision: Option<usize>,
+}
+
+/// A column type allowed in a STRICT table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrictType {
+    Int,
+    Integer,
+    Real,
+    Text,
+    Blob,
+    Any,
+}
+
+impl Display for StrictType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Int => "INT",
+            Self::Integer => "INTEGER",
+            Self::Real => "REAL",
+            Self::Text => "TEXT",
+            Self::Blob => "BLOB",
+            Self::Any => "ANY",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for StrictType {
+    type Err = LimboError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_uppercase().as_str() {
+            "INT" => Ok(Self::Int),
+            "INTEGER" => Ok(Self::Integer),
+            "REAL" => Ok(Self::Real),
+            "TEXT" => Ok(Self::Text),
+            "BLOB" => Ok(Self::Blob),
+            "ANY" => Ok(Self::Any),
+            _ => Err(LimboError::ParseError(format!(
+                "unknown datatype for STRICT column: {s}"
+            ))),
+        }
+    }
+}
+
+impl StrictType {
+    /// Converts `value` for storage in a STRICT column of this type where that is lossless:
+    /// integral reals and integer text into INT columns, integers and numeric text into REAL
+    /// columns, and numbers into TEXT columns. NULL fits any column and ANY takes values
+    /// unchanged. Returns `None` for a value that cannot be stored in the column.
+    pub fn convert(self, value: &Value) -> Option<Value> {
+        match (self, value) {
+            (_, Value::Null) | (Self::Any, _) => Some(value.clone()),
+            (Self::Int | Self::Integer, _) => {
+                value.checked_cast_to_integer().ok().map(Value::Integer)
+            }
+            (Self::Real, Value::Integer(i)) => Some(Value::Float(*i as f64)),
+            (Self::Real, Value::Float(_)) => Some(value.clone()),
+            (Self::Real, Value::Text(t)) => match checked_cast_text_to_numeric_exact(t.as_str()) {
+                Ok(Value::Integer(i)) => Some(Value::Float(i as f64)),
+                Ok(Value::Float(f)) => Some(Value::Float(f)),
+                _ => None,
+            },
+            (Self::Text, Value::Integer(_) | Value::Float(_)) => {
+                Some(Value::build_text(value.to_string()))
+            }
+            (Self::Text, Value::Text(_)) | (Self::Blob, Value::Blob(_)) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Text {
+    #[cfg_attr(feature = "serde", serde(with = "serde_text"))]
     pub value: Vec<u8>,
     pub subtype: TextSubtype,
 }
 
+/// Text is stored as UTF-8 bytes but (de)serialized as a string so it stays readable in
+/// formats like JSON.
+#[cfg(feature = "serde")]
+mod serde_text {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(std::str::from_utf8(value).map_err(serde::ser::Error::custom)?)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Ok(String::deserialize(deserializer)?.into_bytes())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct TextRef {
     pub value: RawSlice,
@@ -99,6 +239,19 @@ impl From<String> for Text {
 }
 
 impl TextRef {
+    /// Borrows `value` without copying it.
+    ///
+    /// # Safety
+    ///
+    /// The returned reference does not track the borrow, so `value` must outlive it and every
+    /// [RefValue] built from it.
+    pub unsafe fn new(value: &str) -> Self {
+        Self {
+            value: RawSlice::new(value.as_ptr(), value.len()),
+            subtype: TextSubtype::Text,
+        }
+    }
+
     pub fn as_str(&self) -> &str {
         unsafe { std::str::from_utf8_unchecked(self.value.to_slice()) }
     }
@@ -108,8 +261,10 @@ impl TextRef {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
+    #[default]
     Null,
     Integer(i64),
     Float(f64),
@@ -138,6 +293,12 @@ impl Value {
         Self::Text(Text::new(text.as_ref()))
     }
 
+    /// Builds a blob Value holding a copy of `bytes`, the blob counterpart of
+    /// [Value::build_text].
+    pub fn build_blob(bytes: &[u8]) -> Self {
+        Self::Blob(bytes.to_vec())
+    }
+
     pub fn to_blob(&self) -> Option<&[u8]> {
         match self {
             Self::Blob(blob) => Some(blob),
@@ -145,6 +306,23 @@ impl Value {
         }
     }
 
+    /// Borrows `len` bytes of a blob starting at `offset`, as `sqlite3_blob_read` reads a
+    /// range of a blob. A range past the end of the blob or a non-blob value is an error.
+    pub fn blob_slice(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        let Value::Blob(blob) = self else {
+            return Err(type_mismatch("blob", self.type_name()));
+        };
+        offset
+            .checked_add(len)
+            .and_then(|end| blob.get(offset..end))
+            .ok_or_else(|| {
+                LimboError::InvalidArgument(format!(
+                    "cannot read {len} bytes at offset {offset} of a {}-byte blob",
+                    blob.len()
+                ))
+            })
+    }
+
     pub fn from_blob(data: Vec<u8>) -> Self {
         Value::Blob(data)
     }
@@ -160,6 +338,194 @@ impl Value {
         Value::Text(Text::new(text))
     }
 
+    /// Borrows the bytes of a text or blob value without copying them.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Text(t) => Some(&t.value),
+            Value::Blob(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Length in bytes of a text (UTF-8) or blob value, as `octet_length()` counts it.
+    pub fn byte_len(&self) -> Option<usize> {
+        self.as_bytes().map(<[u8]>::len)
+    }
+
+    /// Length in characters (Unicode scalar values) of a text value, as `length()` counts it.
+    pub fn char_len(&self) -> Option<usize> {
+        self.to_text().map(|text| text.chars().count())
+    }
+
+    /// The name `typeof()` reports for this value: "null", "integer", "real", "text" or "blob".
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "real",
+            Value::Text(_) => "text",
+            Value::Blob(_) => "blob",
+        }
+    }
+
+    /// Reads this value as a float for numeric functions such as the math functions: integers
+    /// and floats directly, and text only if it is entirely a number (surrounding whitespace
+    /// aside). NULL, blobs and other text give `None`.
+    pub fn try_as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            Value::Text(t) => match checked_cast_text_to_numeric_exact(t.as_str()) {
+                Ok(Value::Integer(i)) => Some(i as f64),
+                Ok(Value::Float(f)) => Some(f),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Reads this value as an integer the way `CAST(x AS INTEGER)` does: floats are truncated
+    /// toward zero and clamped to the i64 range, and text is read up to the end of its leading
+    /// integer, clamped likewise. NULL, blobs and text with no leading integer give `None`.
+    pub fn try_as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            Value::Float(f) if f.is_nan() => None,
+            Value::Float(f) => Some(*f as i64),
+            Value::Text(t) => {
+                let text = t.as_str().trim_start();
+                let (negative, unsigned) = match text.as_bytes().first() {
+                    Some(b'-') => (true, &text[1..]),
+                    Some(b'+') => (false, &text[1..]),
+                    _ => (false, text),
+                };
+                let digits = unsigned.bytes().take_while(u8::is_ascii_digit).count();
+                if digits == 0 {
+                    return None;
+                }
+                let prefix = &text[..text.len() - unsigned.len() + digits];
+                Some(
+                    prefix
+                        .parse::<i64>()
+                        .unwrap_or(if negative { i64::MIN } else { i64::MAX }),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads this value as a rowid key: an integer, a float with no fractional part that fits
+    /// an i64, or text that is an integer once surrounding whitespace is trimmed. Anything
+    /// else, including text such as "1.5" or "1.0", gives `None`.
+    pub fn as_rowid(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            Value::Float(f)
+                if f.fract() == 0.0 && *f >= i64::MIN as f64 && *f < i64::MAX as f64 =>
+            {
+                Some(*f as i64)
+            }
+            Value::Text(t) => match checked_cast_text_to_numeric_exact(t.as_str()) {
+                Ok(Value::Integer(i)) => Some(i),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Converts the value for storage in a STRICT `INTEGER` column. Integers pass through;
+    /// floats and numeric text are accepted only if they convert without loss, so 12.0 and
+    /// "12" give 12 while 12.5, "12.5" and "abc" are errors. NULL and blobs are errors too.
+    pub fn checked_cast_to_integer(&self) -> Result<i64> {
+        let number = match self {
+            Value::Text(t) => checked_cast_text_to_numeric_exact(t.as_str()).unwrap_or(Value::Null),
+            v => v.clone(),
+        };
+        match number {
+            Value::Integer(i) => Ok(i),
+            Value::Float(f) => cast_real_to_integer(f).map_err(|_| {
+                LimboError::ConversionError(format!(
+                    "cannot store {} value {self} in an INTEGER column",
+                    self.type_name()
+                ))
+            }),
+            _ => Err(type_mismatch("integer", self.type_name())),
+        }
+    }
+
+    /// Converts the value in place by `affinity`, as the `Affinity` instruction does before a
+    /// value is stored or compared. Blobs are never converted. Returns false when text or a
+    /// real is left as it is because it does not read as a number the affinity accepts.
+    pub fn apply_affinity(&mut self, affinity: Affinity) -> bool {
+        if matches!(self, Value::Blob(_)) {
+            return true;
+        }
+        match affinity {
+            Affinity::Blob => return true,
+            Affinity::Text => {
+                if matches!(self, Value::Text(_) | Value::Null) {
+                    return true;
+                }
+                let text = self.to_string();
+                *self = Value::Text(text.into());
+                return true;
+            }
+            Affinity::Integer | Affinity::Numeric => {
+                if matches!(self, Value::Integer(_)) {
+                    return true;
+                }
+                if !matches!(self, Value::Text(_) | Value::Float(_)) {
+                    return true;
+                }
+
+                if let Value::Float(fl) = *self {
+                    if let Ok(int) = cast_real_to_integer(fl).map(Value::Integer) {
+                        *self = int;
+                        return true;
+                    }
+                    return false;
+                }
+
+                let text = self.to_text().unwrap();
+                let Ok(num) = checked_cast_text_to_numeric(text) else {
+                    return false;
+                };
+
+                *self = match &num {
+                    Value::Float(fl) => cast_real_to_integer(*fl).map_or(num, Value::Integer),
+                    Value::Integer(_) if text.starts_with("0x") => {
+                        return false;
+                    }
+                    _ => num,
+                };
+            }
+
+            Affinity::Real => {
+                if let Value::Integer(i) = self {
+                    *self = Value::Float(*i as f64);
+                    return true;
+                } else if let Value::Text(t) = self {
+                    if t.as_str().starts_with("0x") {
+                        return false;
+                    }
+                    if let Ok(num) = checked_cast_text_to_numeric(t.as_str()) {
+                        *self = num;
+                        return true;
+                    } else {
+                        return false;
+                    }
+                }
+            }
+        };
+        true
+    }
+
+    /// Number of content bytes this value occupies in a serialized record,
+    /// excluding its serial type header varint.
+    pub fn serial_content_size(&self) -> usize {
+        SerialType::from(self).size()
+    }
+
     pub fn value_type(&self) -> ValueType {
         match self {
             Value::Null => ValueType::Null,
@@ -175,6 +541,7 @@ impl Value {
             Value::Integer(i) => {
                 let serial_type = SerialType::from(self);
                 match serial_type.kind() {
+                    SerialTypeKind::ConstInt0 | SerialTypeKind::ConstInt1 => {}
                     SerialTypeKind::I8 => out.extend_from_slice(&(*i as i8).to_be_bytes()),
                     SerialTypeKind::I16 => out.extend_from_slice(&(*i as i16).to_be_bytes()),
                     SerialTypeKind::I24 => out.extend_from_slice(&(*i as i32).to_be_bytes()[1..]), // remove most significant byte
@@ -189,1576 +556,5065 @@ impl Value {
             Value::Blob(b) => out.extend_from_slice(b),
         };
     }
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct ExternalAggState {
-    pub state: *mut AggCtx,
-    pub argc: usize,
-    pub step_fn: StepFunction,
-    pub finalize_fn: FinalizeFunction,
-    pub finalized_value: Option<Value>,
-}
 
-impl ExternalAggState {
-    pub fn cache_final_value(&mut self, value: Value) -> &Value {
-        self.finalized_value = Some(value);
-        self.finalized_value.as_ref().unwrap()
+    /// Uppercases the ASCII letters of a text value, leaving every other byte untouched.
+    /// This matches SQLite's built-in `upper()` when it is compiled without ICU.
+    /// Non-text values are returned unchanged.
+    pub fn to_upper(&self) -> Self {
+        match self {
+            Value::Text(t) => Value::Text(Text {
+                value: t.value.to_ascii_uppercase(),
+                subtype: TextSubtype::Text,
+            }),
+            v => v.clone(),
+        }
     }
-}
 
-/// Please use Display trait for all limbo output so we have single origin of truth
-/// When you need value as string:
-/// ---GOOD---
-/// format!("{}", value);
-/// ---BAD---
-/// match value {
-///   Value::Integer(i) => *i.as_str(),
-///   Value::Float(f) => *f.as_str(),
-///   ....
-/// }
-impl Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    /// Lowercases the ASCII letters of a text value, leaving every other byte untouched.
+    /// This matches SQLite's built-in `lower()` when it is compiled without ICU.
+    /// Non-text values are returned unchanged.
+    pub fn to_lower(&self) -> Self {
         match self {
-            Self::Null => write!(f, ""),
-            Self::Integer(i) => {
-                write!(f, "{}", i)
-            }
-            Self::Float(fl) => {
-                let fl = *fl;
-                if fl == f64::INFINITY {
-                    return write!(f, "Inf");
-                }
-                if fl == f64::NEG_INFINITY {
-                    return write!(f, "-Inf");
-                }
-                if fl.is_nan() {
-                    return write!(f, "");
-                }
-                // handle negative 0
-                if fl == -0.0 {
-                    return write!(f, "{:.1}", fl.abs());
-                }
+            Value::Text(t) => Value::Text(Text {
+                value: t.value.to_ascii_lowercase(),
+                subtype: TextSubtype::Text,
+            }),
+            v => v.clone(),
+        }
+    }
 
-                // handle scientific notation without trailing zeros
-                if (fl.abs() < 1e-4 || fl.abs() >= 1e15) && fl != 0.0 {
-                    let sci_notation = format!("{:.14e}", fl);
-                    let parts: Vec<&str> = sci_notation.split('e').collect();
+    /// Implements SQLite's `substr(X, Y, Z)`: `start` is 1-based and counts from the end when
+    /// negative, and a negative `len` selects the characters preceding `start`. Text is sliced
+    /// by characters and blobs by bytes. Other values yield NULL.
+    pub fn substr(&self, start: i64, len: Option<i64>) -> Self {
+        let total = match self {
+            Value::Text(t) => t.as_str().chars().count() as i64,
+            Value::Blob(b) => b.len() as i64,
+            _ => return Value::Null,
+        };
 
-                    if parts.len() == 2 {
-                        let mantissa = parts[0];
-                        let exponent = parts[1];
+        // Mirrors the index arithmetic in SQLite's substrFunc().
+        let mut p1 = start;
+        let (mut p2, negative_len) = match len {
+            Some(len) if len < 0 => (len.saturating_neg(), true),
+            Some(len) => (len, false),
+            None => (i64::MAX, false),
+        };
+        if p1 < 0 {
+            p1 = p1.saturating_add(total);
+            if p1 < 0 {
+                p2 = p2.saturating_add(p1).max(0);
+                p1 = 0;
+            }
+        } else if p1 > 0 {
+            p1 -= 1;
+        } else if p2 > 0 {
+            p2 -= 1;
+        }
+        if negative_len {
+            p1 -= p2;
+            if p1 < 0 {
+                p2 += p1;
+                p1 = 0;
+            }
+        }
+        let p1 = p1.min(total);
+        let p2 = p2.min(total - p1).max(0);
 
-                        let decimal_parts: Vec<&str> = mantissa.split('.').collect();
-                        if decimal_parts.len() == 2 {
-                            let whole = decimal_parts[0];
-                            // 1.{this part}
-                            let mut fraction = String::from(decimal_parts[1]);
+        match self {
+            Value::Text(t) => Value::build_text(
+                t.as_str()
+                    .chars()
+                    .skip(p1 as usize)
+                    .take(p2 as usize)
+                    .collect::<String>(),
+            ),
+            Value::Blob(b) => Value::Blob(b[p1 as usize..(p1 + p2) as usize].to_vec()),
+            _ => unreachable!(),
+        }
+    }
 
-                            //removing trailing 0 from fraction
-                            while fraction.ends_with('0') {
-                                fraction.pop();
-                            }
+    /// Implements SQLite's `trim(X, Y)`: strips any character contained in `chars` from both
+    /// ends. `chars` is a set of characters rather than a substring and defaults to a single
+    /// space. Numbers are trimmed as their text rendering, blobs are returned unchanged and
+    /// NULL yields NULL.
+    pub fn trim(&self, chars: Option<&str>) -> Self {
+        self.trim_ends(chars, true, true)
+    }
 
-                            let trimmed_mantissa = if fraction.is_empty() {
-                                whole.to_string()
-                            } else {
-                                format!("{}.{}", whole, fraction)
-                            };
-                            let (prefix, exponent) = if exponent.starts_with('-') {
-                                ("-0", &exponent[1..])
-                            } else {
-                                ("+", exponent)
-                            };
-                            return write!(f, "{}e{}{}", trimmed_mantissa, prefix, exponent);
-                        }
-                    }
+    /// Like [Value::trim], but only strips characters from the start.
+    pub fn ltrim(&self, chars: Option<&str>) -> Self {
+        self.trim_ends(chars, true, false)
+    }
 
-                    // fallback
-                    return write!(f, "{}", sci_notation);
-                }
+    /// Like [Value::trim], but only strips characters from the end.
+    pub fn rtrim(&self, chars: Option<&str>) -> Self {
+        self.trim_ends(chars, false, true)
+    }
 
-                // handle floating point max size is 15.
-                // If left > right && right + left > 15 go to sci notation
-                // If right > left && right + left > 15 truncate left so right + left == 15
-                let rounded = fl.round();
-                if (fl - rounded).abs() < 1e-14 {
-                    // if we very close to integer trim decimal part to 1 digit
-                    if rounded == rounded as i64 as f64 {
-                        return write!(f, "{:.1}", fl);
-                    }
-                }
+    fn trim_ends(&self, chars: Option<&str>, start: bool, end: bool) -> Self {
+        let text = match self {
+            Value::Null => return Value::Null,
+            Value::Blob(_) => return self.clone(),
+            Value::Text(t) => Cow::Borrowed(t.as_str()),
+            v => Cow::Owned(v.to_string()),
+        };
+        let set: Vec<char> = chars.unwrap_or(" ").chars().collect();
+        let mut trimmed: &str = &text;
+        if start {
+            trimmed = trimmed.trim_start_matches(&set[..]);
+        }
+        if end {
+            trimmed = trimmed.trim_end_matches(&set[..]);
+        }
+        Value::build_text(trimmed)
+    }
 
-                let fl_str = format!("{}", fl);
-                let splitted = fl_str.split('.').collect::<Vec<&str>>();
-                // fallback
-                if splitted.len() != 2 {
-                    return write!(f, "{:.14e}", fl);
-                }
+    /// Implements SQLite's `replace(X, Y, Z)`: every non-overlapping occurrence of `find` is
+    /// replaced with `replacement`. An empty `find` leaves the string unchanged. Non-text values
+    /// are cast to text first and NULL yields NULL.
+    pub fn replace(&self, find: &str, replacement: &str) -> Self {
+        let source = match self {
+            Value::Null => return Value::Null,
+            Value::Text(t) => Cow::Borrowed(t.as_str()),
+            v => Cow::Owned(v.to_string()),
+        };
+        if find.is_empty() {
+            return Value::build_text(source);
+        }
+        Value::build_text(source.replace(find, replacement))
+    }
 
-                let first_part = if fl < 0.0 {
-                    // remove -
-                    &splitted[0][1..]
+    /// Implements SQLite's `instr(X, Y)`: the 1-based position of the first occurrence of
+    /// `needle`, or 0 when it is absent. Positions count bytes when both operands are blobs and
+    /// characters otherwise, with non-text operands cast to text. NULL operands yield NULL.
+    pub fn instr(&self, needle: &Value) -> Self {
+        match (self, needle) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            (Value::Blob(haystack), Value::Blob(needle)) => {
+                let position = if needle.is_empty() {
+                    Some(0)
                 } else {
-                    splitted[0]
+                    haystack
+                        .windows(needle.len())
+                        .position(|window| window == needle.as_slice())
                 };
+                Value::Integer(position.map_or(0, |i| i as i64 + 1))
+            }
+            _ => {
+                let haystack = self.to_string();
+                let needle = needle.to_string();
+                match haystack.find(needle.as_str()) {
+                    Some(offset) => Value::Integer(haystack[..offset].chars().count() as i64 + 1),
+                    None => Value::Integer(0),
+                }
+            }
+        }
+    }
 
-                let second = splitted[1];
+    /// Implements SQLite's `abs(X)`. Unlike the SQL function, which raises an integer overflow
+    /// error, `abs(i64::MIN)` is promoted to a float. Text and blobs are cast to REAL first.
+    pub fn abs(&self) -> Self {
+        match self {
+            Value::Null => Value::Null,
+            Value::Integer(i) => match i.checked_abs() {
+                Some(abs) => Value::Integer(abs),
+                None => Value::Float(-(*i as f64)),
+            },
+            Value::Float(f) => Value::Float(f.abs()),
+            v => Value::Float(v.cast_to_real().unwrap_or(0.0).abs()),
+        }
+    }
 
-                // We want more precision for smaller numbers. in SQLite case we want 15 non zero digits in 0 < number < 1
-                // leading zeroes added to max real size. But if float < 1e-4 we go to scientific notation
-                let leading_zeros = second.chars().take_while(|c| c == &'0').count();
-                let reminder = if first_part != "0" {
-                    MAX_REAL_SIZE as isize - first_part.len() as isize
-                } else {
-                    MAX_REAL_SIZE as isize + leading_zeros as isize
-                };
-                // float that have integer part > 15 converted to sci notation
-                if reminder < 0 {
-                    return write!(f, "{:.14e}", fl);
-                }
-                // trim decimal part to reminder or self len so total digits is 15;
-                let mut fl = format!("{:.*}", second.len().min(reminder as usize), fl);
-                // if decimal part ends with 0 we trim it
-                while fl.ends_with('0') {
-                    fl.pop();
-                }
-                write!(f, "{}", fl)
-            }
-            Self::Text(s) => {
-                write!(f, "{}", s.as_str())
-            }
-            Self::Blob(b) => write!(f, "{}", String::from_utf8_lossy(b)),
+    /// Implements SQLite's `round(X, Y)`, rounding half away from zero to `digits` decimal
+    /// places. Negative `digits` are treated as 0. The result is always REAL.
+    pub fn round(&self, digits: i64) -> Self {
+        match self.cast_to_real() {
+            Some(f) => Value::Float(f.round_to_precision(digits.clamp(0, 30) as i32)),
+            None => Value::Null,
         }
     }
-}
 
-impl Value {
-    pub fn to_ffi(&self) -> ExtValue {
-        match self {
-            Self::Null => ExtValue::null(),
-            Self::Integer(i) => ExtValue::from_integer(*i),
-            Self::Float(fl) => ExtValue::from_float(*fl),
-            Self::Text(text) => ExtValue::from_text(text.as_str().to_string()),
-            Self::Blob(blob) => ExtValue::from_blob(blob.to_vec()),
+    /// Implements SQLite's `ceil(X)`. Integers, including integer text, are returned unchanged
+    /// as in SQLite; other numeric values yield the REAL ceiling. Non-numeric input yields NULL.
+    pub fn ceil(&self) -> Self {
+        match self.numeric_for_math() {
+            Value::Float(f) => Value::Float(f.ceil()),
+            v => v,
         }
     }
 
-    pub fn from_ffi(v: ExtValue) -> Result<Self> {
-        let res = match v.value_type() {
-            ExtValueType::Null => Ok(Value::Null),
-            ExtValueType::Integer => {
-                let Some(int) = v.to_integer() else {
-                    return Ok(Value::Null);
-                };
-                Ok(Value::Integer(int))
-            }
-            ExtValueType::Float => {
-                let Some(float) = v.to_float() else {
-                    return Ok(Value::Null);
-                };
-                Ok(Value::Float(float))
-            }
-            ExtValueType::Text => {
-                let Some(text) = v.to_text() else {
-                    return Ok(Value::Null);
-                };
-                #[cfg(feature = "json")]
-                if v.is_json() {
-                    return Ok(Value::Text(Text::json(text.to_string())));
-                }
-                Ok(Value::build_text(text))
-            }
-            ExtValueType::Blob => {
-                let Some(blob) = v.to_blob() else {
-                    return Ok(Value::Null);
-                };
-                Ok(Value::Blob(blob))
-            }
-            ExtValueType::Error => {
-                let Some(err) = v.to_error_details() else {
-                    return Ok(Value::Null);
-                };
-                match err {
-                    (_, Some(msg)) => Err(LimboError::ExtensionError(msg)),
-                    (code, None) => Err(LimboError::ExtensionError(code.to_string())),
-                }
-            }
+    /// Implements SQLite's `floor(X)`. Integers, including integer text, are returned unchanged
+    /// as in SQLite; other numeric values yield the REAL floor. Non-numeric input yields NULL.
+    pub fn floor(&self) -> Self {
+        match self.numeric_for_math() {
+            Value::Float(f) => Value::Float(f.floor()),
+            v => v,
+        }
+    }
+
+    /// Approximate number of bytes this value occupies in memory: the inline enum itself plus
+    /// the allocated capacity of any text or blob buffer it owns. Used to bound caches.
+    pub fn heap_size(&self) -> usize {
+        let owned = match self {
+            Value::Null | Value::Integer(_) | Value::Float(_) => 0,
+            Value::Text(t) => t.value.capacity(),
+            Value::Blob(b) => b.capacity(),
         };
-        unsafe { v.__free_internal_type() };
-        res
+        std::mem::size_of::<Self>() + owned
     }
-}
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum AggContext {
-    Avg(Value, Value), // acc and count
-    Sum(Value),
-    Count(Value),
-    Max(Option<Value>),
-    Min(Option<Value>),
-    GroupConcat(Value),
-    External(ExternalAggState),
-}
+    /// Returns a copy of the first non-NULL value, or NULL if there is none, like SQLite's
+    /// `coalesce()`.
+    pub fn coalesce(values: &[Value]) -> Value {
+        values
+            .iter()
+            .find(|v| !matches!(v, Value::Null))
+            .cloned()
+            .unwrap_or(Value::Null)
+    }
 
-const NULL: Value = Value::Null;
+    /// Two-argument form of [Value::coalesce], like SQLite's `ifnull()`.
+    pub fn ifnull(&self, other: &Value) -> Value {
+        match self {
+            Value::Null => other.clone(),
+            _ => self.clone(),
+        }
+    }
 
-impl AggContext {
-    pub fn compute_external(&mut self) -> Result<()> {
-        if let Self::External(ext_state) = self {
-            if ext_state.finalized_value.is_none() {
-                let final_value = unsafe { (ext_state.finalize_fn)(ext_state.state) };
-                ext_state.cache_final_value(Value::from_ffi(final_value)?);
+    /// Multi-argument scalar `min()`: the smallest value, or NULL if any argument is NULL.
+    /// Among equal values the last one wins, as in SQLite.
+    pub fn scalar_min<'a>(values: impl IntoIterator<Item = &'a Value>) -> Value {
+        Self::scalar_min_max(values, |best, v| best >= v)
+    }
+
+    /// Multi-argument scalar `max()`: the largest value, or NULL if any argument is NULL.
+    /// Among equal values the first one wins, as in SQLite.
+    pub fn scalar_max<'a>(values: impl IntoIterator<Item = &'a Value>) -> Value {
+        Self::scalar_min_max(values, |best, v| best < v)
+    }
+
+    fn scalar_min_max<'a>(
+        values: impl IntoIterator<Item = &'a Value>,
+        replace: impl Fn(&Value, &Value) -> bool,
+    ) -> Value {
+        let mut best: Option<&Value> = None;
+        for v in values {
+            if let Value::Null = v {
+                return Value::Null;
+            }
+            match best {
+                Some(b) if !replace(b, v) => {}
+                _ => best = Some(v),
             }
         }
-        Ok(())
+        best.cloned().unwrap_or(Value::Null)
     }
 
-    pub fn final_value(&self) -> &Value {
+    /// Renders the value's bytes as upper-case hex text. Blobs are encoded as-is, everything
+    /// else through its UTF-8 text form, and NULL becomes an empty string.
+    pub fn hex(&self) -> Value {
         match self {
-            Self::Avg(acc, _count) => acc,
-            Self::Sum(acc) => acc,
-            Self::Count(count) => count,
-            Self::Max(max) => max.as_ref().unwrap_or(&NULL),
-            Self::Min(min) => min.as_ref().unwrap_or(&NULL),
-            Self::GroupConcat(s) => s,
-            Self::External(ext_state) => ext_state.finalized_value.as_ref().unwrap_or(&NULL),
+            Value::Null => Value::build_text(""),
+            Value::Blob(b) => Value::build_text(hex::encode_upper(b)),
+            _ => Value::build_text(hex::encode_upper(self.to_string())),
         }
     }
-}
 
-impl PartialEq<Value> for Value {
-    fn eq(&self, other: &Value) -> bool {
-        match (self, other) {
-            (Self::Integer(int_left), Self::Integer(int_right)) => int_left == int_right,
-            (Self::Integer(int_left), Self::Float(float_right)) => {
-                (*int_left as f64) == (*float_right)
-            }
-            (Self::Float(float_left), Self::Integer(int_right)) => {
-                float_left == (&(*int_right as f64))
+    /// Parses hex text back into a blob. Returns NULL for NULL and for text that is not a
+    /// valid, even-length hex string.
+    pub fn unhex(&self) -> Value {
+        match self {
+            Value::Null => Value::Null,
+            _ => match hex::decode(self.to_string()) {
+                Ok(bytes) => Value::Blob(bytes),
+                Err(_) => Value::Null,
+            },
+        }
+    }
+
+    /// Renders the value as a SQL literal, like SQLite's `quote()`: NULL as `NULL`, text
+    /// single-quoted with embedded quotes doubled (stopping at the first NUL), and blobs as
+    /// `X'..'`.
+    pub fn quote(&self) -> Value {
+        match self {
+            Value::Null => Value::build_text("NULL"),
+            Value::Integer(i) => Value::build_text(i.to_string()),
+            Value::Float(f) if f.is_infinite() => {
+                Value::build_text(if *f > 0.0 { "9.0e+999" } else { "-9.0e+999" })
             }
-            (Self::Float(float_left), Self::Float(float_right)) => float_left == float_right,
-            (Self::Integer(_) | Self::Float(_), Self::Text(_) | Self::Blob(_)) => false,
-            (Self::Text(_) | Self::Blob(_), Self::Integer(_) | Self::Float(_)) => false,
-            (Self::Text(text_left), Self::Text(text_right)) => {
-                text_left.value.eq(&text_right.value)
+            Value::Float(_) => Value::build_text(self.to_string()),
+            Value::Text(s) => {
+                let s = s.as_str();
+                let s = s.split('\0').next().unwrap_or_default();
+                let mut quoted = String::with_capacity(s.len() + 2);
+                quoted.push('\'');
+                for c in s.chars() {
+                    if c == '\'' {
+                        quoted.push('\'');
+                    }
+                    quoted.push(c);
+                }
+                quoted.push('\'');
+                Value::build_text(quoted)
             }
-            (Self::Blob(blob_left), Self::Blob(blob_right)) => blob_left.eq(blob_right),
-            (Self::Null, Self::Null) => true,
-            _ => false,
+            Value::Blob(b) => Value::build_text(format!("X'{}'", hex::encode_upper(b))),
         }
     }
 
-    fn ne(&self, other: &Value) -> bool {
-        !self.eq(other)
+    /// A random integer, like SQLite's `random()`.
+    pub fn random() -> Value {
+        Self::random_with(&mut rand::rngs::OsRng)
     }
-}
 
-#[allow(clippy::non_canonical_partial_ord_impl)]
-impl PartialOrd<Value> for Value {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (Self::Integer(int_left), Self::Integer(int_right)) => int_left.partial_cmp(int_right),
-            (Self::Integer(int_left), Self::Float(float_right)) => {
-                (*int_left as f64).partial_cmp(float_right)
-            }
-            (Self::Float(float_left), Self::Integer(int_right)) => {
-                float_left.partial_cmp(&(*int_right as f64))
-            }
-            (Self::Float(float_left), Self::Float(float_right)) => {
-                float_left.partial_cmp(float_right)
-            }
-            // Numeric vs Text/Blob
-            (Self::Integer(_) | Self::Float(_), Self::Text(_) | Self::Blob(_)) => {
-                Some(std::cmp::Ordering::Less)
-            }
-            (Self::Text(_) | Self::Blob(_), Self::Integer(_) | Self::Float(_)) => {
-                Some(std::cmp::Ordering::Greater)
-            }
+    /// Like [Value::random] but drawing from `rng`, so callers can use a seeded generator.
+    pub fn random_with(rng: &mut impl rand::RngCore) -> Value {
+        Value::Integer(rng.next_u64() as i64)
+    }
 
-            (Self::Text(text_left), Self::Text(text_right)) => {
-                text_left.value.partial_cmp(&text_right.value)
-            }
-            // Text vs Blob
-            (Self::Text(_), Self::Blob(_)) => Some(std::cmp::Ordering::Less),
-            (Self::Blob(_), Self::Text(_)) => Some(std::cmp::Ordering::Greater),
+    /// A blob of `n` random bytes, like SQLite's `randomblob()`. Lengths below 1 produce a
+    /// single byte.
+    pub fn randomblob(n: usize) -> Value {
+        Self::randomblob_with(n, &mut rand::rngs::OsRng)
+    }
 
-            (Self::Blob(blob_left), Self::Blob(blob_right)) => blob_left.partial_cmp(blob_right),
-            (Self::Null, Self::Null) => Some(std::cmp::Ordering::Equal),
-            (Self::Null, _) => Some(std::cmp::Ordering::Less),
-            (_, Self::Null) => Some(std::cmp::Ordering::Greater),
-        }
+    /// Like [Value::randomblob] but drawing from `rng`.
+    pub fn randomblob_with(n: usize, rng: &mut impl rand::RngCore) -> Value {
+        let mut blob = vec![0; n.max(1)];
+        rng.fill_bytes(&mut blob);
+        Value::Blob(blob)
     }
-}
 
-impl PartialOrd<AggContext> for AggContext {
-    fn partial_cmp(&self, other: &AggContext) -> Option<std::cmp::Ordering> {
+    /// Divides two numeric values, failing with [LimboError::DivisionByZero] instead of
+    /// panicking or returning a placeholder. NULL operands give NULL, and text or blob operands
+    /// are a [LimboError::ConversionError]. `i64::MIN / -1` falls back to a float.
+    pub fn checked_div(&self, other: &Value) -> Result<Value> {
         match (self, other) {
-            (Self::Avg(a, _), Self::Avg(b, _)) => a.partial_cmp(b),
-            (Self::Sum(a), Self::Sum(b)) => a.partial_cmp(b),
-            (Self::Count(a), Self::Count(b)) => a.partial_cmp(b),
-            (Self::Max(a), Self::Max(b)) => a.partial_cmp(b),
-            (Self::Min(a), Self::Min(b)) => a.partial_cmp(b),
-            (Self::GroupConcat(a), Self::GroupConcat(b)) => a.partial_cmp(b),
-            _ => None,
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
+            (Value::Integer(_), Value::Integer(0)) => Err(LimboError::DivisionByZero),
+            (Value::Integer(_) | Value::Float(_), Value::Float(f)) if *f == 0.0 => {
+                Err(LimboError::DivisionByZero)
+            }
+            (Value::Float(_), Value::Integer(0)) => Err(LimboError::DivisionByZero),
+            (Value::Integer(l), Value::Integer(r)) => Ok(l
+                .checked_div(*r)
+                .map_or(Value::Float(*l as f64 / *r as f64), Value::Integer)),
+            (Value::Integer(l), Value::Float(r)) => Ok(Value::Float(*l as f64 / r)),
+            (Value::Float(l), Value::Integer(r)) => Ok(Value::Float(l / *r as f64)),
+            (Value::Float(l), Value::Float(r)) => Ok(Value::Float(l / r)),
+            _ => Err(LimboError::ConversionError(
+                "Expected numeric operands".into(),
+            )),
         }
     }
-}
 
-impl Eq for Value {}
-
-impl Ord for Value {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+    /// Compares two values after coercing both to `affinity`, as SQLite does for the operands
+    /// of comparisons such as BETWEEN and IN. Under TEXT affinity numbers compare as their text
+    /// form; under NUMERIC, INTEGER and REAL affinity well-formed numeric text compares as a
+    /// number. Blobs and NULLs are never coerced.
+    pub fn compare_with_affinity(&self, other: &Value, affinity: Affinity) -> std::cmp::Ordering {
+        let lhs = self.with_affinity(affinity);
+        let rhs = other.with_affinity(affinity);
+        lhs.partial_cmp(&rhs).unwrap_or(std::cmp::Ordering::Equal)
     }
-}
-
-impl std::ops::Add<Value> for Value {
-    type Output = Value;
 
-    fn add(self, rhs: Self) -> Self::Output {
-        match (self, rhs) {
-            (Self::Integer(int_left), Self::Integer(int_right)) => {
-                Self::Integer(int_left + int_right)
-            }
-            (Self::Integer(int_left), Self::Float(float_right)) => {
-                Self::Float(int_left as f64 + float_right)
-            }
-            (Self::Float(float_left), Self::Integer(int_right)) => {
-                Self::Float(float_left + int_right as f64)
-            }
-            (Self::Float(float_left), Self::Float(float_right)) => {
-                Self::Float(float_left + float_right)
-            }
-            (Self::Text(string_left), Self::Text(string_right)) => {
-                Self::build_text(&(string_left.as_str().to_string() + string_right.as_str()))
-            }
-            (Self::Text(string_left), Self::Integer(int_right)) => {
-                Self::build_text(&(string_left.as_str().to_string() + &int_right.to_string()))
-            }
-            (Self::Integer(int_left), Self::Text(string_right)) => {
-                Self::build_text(&(int_left.to_string() + string_right.as_str()))
+    fn with_affinity(&self, affinity: Affinity) -> Cow<'_, Value> {
+        match (affinity, self) {
+            (Affinity::Text, Value::Integer(_) | Value::Float(_)) => {
+                Cow::Owned(Value::build_text(self.to_string()))
             }
-            (Self::Text(string_left), Self::Float(float_right)) => {
-                let string_right = Self::Float(float_right).to_string();
-                Self::build_text(&(string_left.as_str().to_string() + &string_right))
-            }
-            (Self::Float(float_left), Self::Text(string_right)) => {
-                let string_left = Self::Float(float_left).to_string();
-                Self::build_text(&(string_left + string_right.as_str()))
+            (Affinity::Integer | Affinity::Numeric | Affinity::Real, Value::Text(t)) => {
+                match checked_cast_text_to_numeric_exact(t.as_str()) {
+                    Ok(num) => Cow::Owned(num),
+                    Err(()) => Cow::Borrowed(self),
+                }
             }
-            (lhs, Self::Null) => lhs,
-            (Self::Null, rhs) => rhs,
-            _ => Self::Float(0.0),
+            _ => Cow::Borrowed(self),
         }
     }
-}
 
-impl std::ops::Add<f64> for Value {
-    type Output = Value;
+    /// Matches the value against a GLOB `pattern` (case-sensitive `*`, `?` and `[...]`
+    /// wildcards), returning 1 or 0, or NULL if either side is NULL. Numbers and blobs are
+    /// matched through their text form.
+    pub fn glob_match(&self, pattern: &Value) -> Value {
+        match (self, pattern) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            _ => Value::Integer(exec_glob(None, &pattern.to_string(), &self.to_string()) as i64),
+        }
+    }
 
-    fn add(self, rhs: f64) -> Self::Output {
-        match self {
-            Self::Integer(int_left) => Self::Float(int_left as f64 + rhs),
-            Self::Float(float_left) => Self::Float(float_left + rhs),
-            _ => unreachable!(),
+    /// Matches the value against a LIKE `pattern`, where `%` matches any sequence and `_` any
+    /// single character, ignoring ASCII case. An `escape` character makes the character after
+    /// it match literally. Returns 1 or 0, or NULL if either side is NULL.
+    pub fn like_match(&self, pattern: &Value, escape: Option<char>) -> Value {
+        match (self, pattern) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            _ => Value::Integer(crate::util::like_match(
+                &pattern.to_string(),
+                &self.to_string(),
+                escape,
+            ) as i64),
         }
     }
-}
 
-impl std::ops::Add<i64> for Value {
-    type Output = Value;
+    /// Builds text from Unicode code points. Each argument is read as an integer the way
+    /// `sqlite3_value_int64` does, so NULL and non-numeric text give U+0000, 65.7 gives 'A' and
+    /// '66' gives 'B'. Code points that are out of range or are surrogates become U+FFFD.
+    pub fn char_from_codepoints<'a>(args: impl IntoIterator<Item = &'a Value>) -> Value {
+        let text: String = args
+            .into_iter()
+            .map(|arg| {
+                let code_point = match arg {
+                    Value::Blob(b) => Value::build_text(String::from_utf8_lossy(b)).try_as_i64(),
+                    v => v.try_as_i64(),
+                };
+                u32::try_from(code_point.unwrap_or(0))
+                    .ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER)
+            })
+            .collect();
+        Value::build_text(text)
+    }
 
-    fn add(self, rhs: i64) -> Self::Output {
+    /// Returns the code point of the first character, like SQLite's `unicode()`, or NULL for
+    /// NULL and empty values.
+    pub fn unicode(&self) -> Value {
         match self {
-            Self::Integer(int_left) => Self::Integer(int_left + rhs),
-            Self::Float(float_left) => Self::Float(float_left + rhs as f64),
-            _ => unreachable!(),
+            Value::Null => Value::Null,
+            _ => self
+                .to_string()
+                .chars()
+                .next()
+                .map_or(Value::Null, |c| Value::Integer(c as i64)),
         }
     }
-}
 
-impl std::ops::AddAssign for Value {
-    fn add_assign(&mut self, rhs: Self) {
-        *self = self.clone() + rhs;
+    /// Converts an integral float within i64 range to an integer so that values which compare
+    /// equal, like `1.0` and `1`, also hash and group together. Other values are unchanged.
+    pub fn normalize_numeric(&self) -> Value {
+        match self {
+            Value::Float(f)
+                if f.fract() == 0.0 && *f >= i64::MIN as f64 && *f < -(i64::MIN as f64) =>
+            {
+                Value::Integer(*f as i64)
+            }
+            _ => self.clone(),
+        }
     }
-}
 
-impl std::ops::AddAssign<i64> for Value {
-    fn add_assign(&mut self, rhs: i64) {
-        *self = self.clone() + rhs;
+    /// Adds two values the way SQL's `+` operator does: NULL if either side is NULL, text and
+    /// blobs converted to numbers, and integer overflow promoted to REAL.
+    ///
+    /// This differs from the `Add` impl, which skips NULLs so that aggregates such as `sum()`
+    /// can accumulate over them.
+    pub fn arith_add(&self, other: &Value) -> Value {
+        (Numeric::from(self) + Numeric::from(other)).into()
     }
-}
 
-impl std::ops::AddAssign<f64> for Value {
-    fn add_assign(&mut self, rhs: f64) {
-        *self = self.clone() + rhs;
+    /// Like [Value::arith_add], but for strict typing: a text or blob operand that is not
+    /// entirely a number is a [LimboError::ConversionError] instead of being read as 0.
+    pub fn arith_add_strict(&self, other: &Value) -> Result<Value> {
+        for operand in [self, other] {
+            if let Value::Text(_) | Value::Blob(_) = operand {
+                let text = std::str::from_utf8(operand.as_bytes().unwrap()).unwrap_or_default();
+                if checked_cast_text_to_numeric_exact(text).is_err() {
+                    return Err(type_mismatch("numeric", operand.type_name()));
+                }
+            }
+        }
+        Ok(self.arith_add(other))
     }
-}
 
-impl std::ops::Div<Value> for Value {
-    type Output = Value;
+    /// Subtracts like SQL's `-`, with the same coercions as [Value::arith_add].
+    pub fn arith_sub(&self, other: &Value) -> Value {
+        (Numeric::from(self) - Numeric::from(other)).into()
+    }
 
-    fn div(self, rhs: Value) -> Self::Output {
-        match (self, rhs) {
-            (Self::Integer(int_left), Self::Integer(int_right)) => {
-                Self::Integer(int_left / int_right)
-            }
-            (Self::Integer(int_left), Self::Float(float_right)) => {
-                Self::Float(int_left as f64 / float_right)
+    /// Multiplies like SQL's `*`, with the same coercions as [Value::arith_add].
+    pub fn arith_mul(&self, other: &Value) -> Value {
+        (Numeric::from(self) * Numeric::from(other)).into()
+    }
+
+    /// Divides like SQL's `/`: integer division for two integers and NULL when dividing by
+    /// zero, with the same coercions as [Value::arith_add].
+    pub fn arith_div(&self, other: &Value) -> Value {
+        (Numeric::from(self) / Numeric::from(other)).into()
+    }
+
+    /// Concatenates the bytes of two values into a blob, or NULL if either is NULL. Text
+    /// contributes its UTF-8 bytes and numbers their text form.
+    pub fn blob_concat(&self, other: &Value) -> Value {
+        fn bytes(value: &Value) -> Cow<'_, [u8]> {
+            match value {
+                Value::Blob(b) => Cow::Borrowed(b),
+                Value::Text(t) => Cow::Borrowed(&t.value),
+                _ => Cow::Owned(value.to_string().into_bytes()),
             }
-            (Self::Float(float_left), Self::Integer(int_right)) => {
-                Self::Float(float_left / int_right as f64)
-            }
-            (Self::Float(float_left), Self::Float(float_right)) => {
-                Self::Float(float_left / float_right)
-            }
-            _ => Self::Float(0.0),
         }
-    }
-}
-
-impl std::ops::DivAssign<Value> for Value {
-    fn div_assign(&mut self, rhs: Value) {
-        *self = self.clone() / rhs;
-    }
-}
-
-pub trait FromValue<'a> {
-    fn from_value(value: &'a RefValue) -> Result<Self>
-    where
-        Self: Sized + 'a;
-}
-
-impl<'a> FromValue<'a> for i64 {
-    fn from_value(value: &'a RefValue) -> Result<Self> {
-        match value {
-            RefValue::Integer(i) => Ok(*i),
-            _ => Err(LimboError::ConversionError("Expected integer value".into())),
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            _ => Value::Blob([bytes(self), bytes(other)].concat()),
         }
     }
-}
 
-impl<'a> FromValue<'a> for String {
-    fn from_value(value: &'a RefValue) -> Result<Self> {
-        match value {
-            RefValue::Text(s) => Ok(s.as_str().to_string()),
-            _ => Err(LimboError::ConversionError("Expected text value".into())),
+    /// Like [Ord::cmp], but compares two text values by the bytes they would have in
+    /// `encoding`, as SQLite's BINARY collation compares stored text. UTF-8 and UTF-16BE give
+    /// code point and UTF-16 code unit order; the two differ for characters above U+FFFF,
+    /// which UTF-16 stores as surrogates that sort below U+E000..U+FFFF.
+    pub fn compare_text_with_encoding(
+        &self,
+        other: &Value,
+        encoding: Encoding,
+    ) -> std::cmp::Ordering {
+        let (Value::Text(left), Value::Text(right)) = (self, other) else {
+            return self.cmp(other);
+        };
+        let (left, right) = (left.as_str(), right.as_str());
+        match encoding {
+            Encoding::Utf8 => left.as_bytes().cmp(right.as_bytes()),
+            Encoding::Utf16be => left.encode_utf16().cmp(right.encode_utf16()),
+            Encoding::Utf16le => left
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .cmp(right.encode_utf16().flat_map(u16::to_le_bytes)),
         }
     }
-}
 
-impl<'a> FromValue<'a> for &'a str {
-    fn from_value(value: &'a RefValue) -> Result<Self> {
-        match value {
-            RefValue::Text(s) => Ok(s.as_str()),
-            _ => Err(LimboError::ConversionError("Expected text value".into())),
+    /// Like [Ord::cmp], but places NULL after every other value when `nulls_last` is set,
+    /// as `ORDER BY ... NULLS LAST` requires.
+    pub fn cmp_with_nulls(&self, other: &Value, nulls_last: bool) -> std::cmp::Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => std::cmp::Ordering::Equal,
+            (Value::Null, _) if nulls_last => std::cmp::Ordering::Greater,
+            (_, Value::Null) if nulls_last => std::cmp::Ordering::Less,
+            _ => self.cmp(other),
         }
     }
-}
-
-/// This struct serves the purpose of not allocating multiple vectors of bytes if not needed.
-/// A value in a record that has already been serialized can stay serialized and what this struct offsers
-/// is easy acces to each value which point to the payload.
-/// The name might be contradictory as it is immutable in the sense that you cannot modify the values without modifying the payload.
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
-pub struct ImmutableRecord {
-    // We have to be super careful with this buffer since we make values point to the payload we need to take care reallocations
-    // happen in a controlled manner. If we realocate with values that should be correct, they will now point to undefined data.
-    // We don't use pin here because it would make it imposible to reuse the buffer if we need to push a new record in the same struct.
-    payload: Vec<u8>,
-    pub values: Vec<RefValue>,
-    recreating: bool,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Record {
-    values: Vec<Value>,
-}
 
-impl Record {
-    // pub fn get<'a, T: FromValue<'a> + 'a>(&'a self, idx: usize) -> Result<T> {
-    //     let value = &self.values[idx];
-    //     T::from_value(value)
-    // }
+    /// Appends an encoding of this value to `buf` whose bytes compare in the order [Ord::cmp]
+    /// gives, or the reverse when `desc` is set, so sort keys can be compared with `memcmp`.
+    /// Keys for several columns can be appended one after another.
+    ///
+    /// A type tag byte orders NULL before numbers, text and blobs. Numbers are encoded as
+    /// their float value followed by their integer part, so integers too large for a float
+    /// still order exactly, and a final byte recording whether the number is an integer, a
+    /// float or `-0.0` so [Value::decode_sort_key] can restore it. That byte puts an integer
+    /// before an equal float. Text and blobs escape 0x00 as 0x00 0xFF and end with 0x00 0x00, so a
+    /// value sorts before any longer value it is a prefix of.
+    pub fn encode_sort_key(&self, desc: bool, buf: &mut Vec<u8>) {
+        fn ordered_f64(f: f64) -> [u8; 8] {
+            // -0.0 compares equal to 0.0, so both share the encoding of 0.0.
+            let bits = if f == 0.0 { 0 } else { f.to_bits() };
+            let bits = if bits >> 63 == 1 {
+                !bits
+            } else {
+                bits | 1 << 63
+            };
+            bits.to_be_bytes()
+        }
+        fn ordered_i64(i: i64) -> [u8; 8] {
+            ((i as u64) ^ 1 << 63).to_be_bytes()
+        }
 
-    pub fn count(&self) -> usize {
-        self.values.len()
+        let start = buf.len();
+        match self {
+            Value::Null => buf.push(0x01),
+            Value::Integer(i) => {
+                buf.push(0x02);
+                buf.extend_from_slice(&ordered_f64(*i as f64));
+                buf.extend_from_slice(&ordered_i64(*i));
+                buf.push(0x00);
+            }
+            Value::Float(f) => {
+                buf.push(0x02);
+                buf.extend_from_slice(&ordered_f64(*f));
+                buf.extend_from_slice(&ordered_i64(*f as i64));
+                buf.push(if *f == 0.0 && f.is_sign_negative() {
+                    0x02
+                } else {
+                    0x01
+                });
+            }
+            Value::Text(_) | Value::Blob(_) => {
+                buf.push(if matches!(self, Value::Text(_)) {
+                    0x03
+                } else {
+                    0x04
+                });
+                for &byte in self.as_bytes().unwrap() {
+                    buf.push(byte);
+                    if byte == 0x00 {
+                        buf.push(0xff);
+                    }
+                }
+                buf.extend_from_slice(&[0x00, 0x00]);
+            }
+        }
+        if desc {
+            buf[start..].iter_mut().for_each(|byte| *byte = !*byte);
+        }
     }
 
-    pub fn last_value(&self) -> Option<&Value> {
-        self.values.last()
+    /// Decodes the first key [Value::encode_sort_key] wrote to `buf`, returning the value and
+    /// the number of bytes it took. Descending keys are recognised by their inverted tag byte.
+    pub fn decode_sort_key(buf: &[u8]) -> Result<(Value, usize)> {
+        let malformed = || LimboError::InternalError("malformed sort key".to_string());
+        let desc = *buf.first().ok_or_else(malformed)? >= 0x80;
+        let byte = |i: usize| {
+            buf.get(i)
+                .map(|&b| if desc { !b } else { b })
+                .ok_or_else(malformed)
+        };
+        match byte(0)? {
+            0x01 => Ok((Value::Null, 1)),
+            0x02 => {
+                let mut number = [0u8; 17];
+                for (i, b) in number.iter_mut().enumerate() {
+                    *b = byte(i + 1)?;
+                }
+                let bits = u64::from_be_bytes(number[..8].try_into().unwrap());
+                let bits = if bits >> 63 == 1 {
+                    bits ^ 1 << 63
+                } else {
+                    !bits
+                };
+                let int = u64::from_be_bytes(number[8..16].try_into().unwrap()) ^ 1 << 63;
+                let value = match number[16] {
+                    0x00 => Value::Integer(int as i64),
+                    0x01 => Value::Float(f64::from_bits(bits)),
+                    0x02 => Value::Float(-0.0),
+                    _ => return Err(malformed()),
+                };
+                Ok((value, 18))
+            }
+            tag @ (0x03 | 0x04) => {
+                let mut bytes = Vec::new();
+                let mut i = 1;
+                loop {
+                    match byte(i)? {
+                        0x00 => match byte(i + 1)? {
+                            0x00 => break,
+                            0xff => bytes.push(0x00),
+                            _ => return Err(malformed()),
+                        },
+                        b => {
+                            bytes.push(b);
+                            i += 1;
+                            continue;
+                        }
+                    }
+                    i += 2;
+                }
+                let value = if tag == 0x03 {
+                    Value::Text(Text {
+                        value: bytes,
+                        subtype: TextSubtype::Text,
+                    })
+                } else {
+                    Value::Blob(bytes)
+                };
+                Ok((value, i + 2))
+            }
+            _ => Err(malformed()),
+        }
     }
 
-    pub fn get_values(&self) -> &Vec<Value> {
-        &self.values
+    /// SQLite's `sign()`: Integer -1, 0 or 1 for negative, zero and positive numbers. Text,
+    /// and blobs read as text, count only if the whole value is a number; anything else,
+    /// including NULL, gives NULL.
+    pub fn signum(&self) -> Value {
+        match self {
+            Value::Integer(i) => Value::Integer(i.signum()),
+            Value::Float(f) if *f > 0.0 => Value::Integer(1),
+            Value::Float(f) if *f < 0.0 => Value::Integer(-1),
+            Value::Float(_) => Value::Integer(0),
+            Value::Text(_) | Value::Blob(_) => std::str::from_utf8(self.as_bytes().unwrap())
+                .ok()
+                .and_then(|text| checked_cast_text_to_numeric_exact(text).ok())
+                .map_or(Value::Null, |number| number.signum()),
+            Value::Null => Value::Null,
+        }
     }
 
-    pub fn get_value(&self, idx: usize) -> &Value {
-        &self.values[idx]
+    /// SQLite's `concat()`: the non-NULL `args` cast to text and joined. Unlike `||`, a NULL
+    /// argument is skipped rather than making the result NULL.
+    pub fn concat(args: &[Value]) -> Value {
+        let mut result = String::new();
+        for arg in args {
+            result.push_str(&arg.to_text_lossy());
+        }
+        Value::build_text(&result)
     }
 
-    pub fn len(&self) -> usize {
-        self.values.len()
+    /// SQLite's `concat_ws()`: joins the non-NULL `args`, each cast to text, with `sep` between
+    /// them. A NULL separator gives NULL.
+    pub fn concat_ws(sep: &Value, args: &[Value]) -> Value {
+        if matches!(sep, Value::Null) {
+            return Value::Null;
+        }
+        let sep = sep.to_text_lossy();
+        let mut result = String::new();
+        for (i, arg) in args
+            .iter()
+            .filter(|arg| !matches!(arg, Value::Null))
+            .enumerate()
+        {
+            if i > 0 {
+                result.push_str(&sep);
+            }
+            result.push_str(&arg.to_text_lossy());
+        }
+        Value::build_text(&result)
     }
-}
-struct AppendWriter<'a> {
-    buf: &'a mut Vec<u8>,
-    pos: usize,
-    buf_capacity_start: usize,
-    buf_ptr_start: *const u8,
-}
 
-impl<'a> AppendWriter<'a> {
-    pub fn new(buf: &'a mut Vec<u8>, pos: usize) -> Self {
-        let buf_ptr_start = buf.as_ptr();
-        let buf_capacity_start = buf.capacity();
-        Self {
-            buf,
-            pos,
-            buf_capacity_start,
-            buf_ptr_start,
+    /// The value cast to text, with blob bytes read as UTF-8.
+    fn to_text_lossy(&self) -> std::borrow::Cow<'_, str> {
+        match self {
+            Value::Text(t) => std::borrow::Cow::Borrowed(t.as_str()),
+            Value::Blob(b) => String::from_utf8_lossy(b),
+            Value::Null => std::borrow::Cow::Borrowed(""),
+            v => std::borrow::Cow::Owned(v.to_string()),
         }
     }
 
-    #[inline]
-    pub fn extend_from_slice(&mut self, slice: &[u8]) {
-        self.buf[self.pos..self.pos + slice.len()].copy_from_slice(slice);
-        self.pos += slice.len();
+    /// Whether a WHERE or CHECK condition with this value holds. Numbers are true unless zero,
+    /// and text and blobs are read as their leading number, so "0", "abc" and "" are false
+    /// while "0.5" and "2x" are true. NULL is never true.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Integer(i) => *i != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::Text(t) => cast_text_to_numeric(t.as_str()).is_truthy(),
+            Value::Blob(b) => cast_text_to_numeric(&String::from_utf8_lossy(b)).is_truthy(),
+        }
     }
 
-    fn assert_finish_capacity(&self) {
-        // let's make sure we didn't reallocate anywhere else
-        assert_eq!(self.buf_capacity_start, self.buf.capacity());
-        assert_eq!(self.buf_ptr_start, self.buf.as_ptr());
+    /// Returns NULL if both values compare equal and `self` otherwise, like SQLite's `nullif()`.
+    /// Integers and floats compare numerically, so `nullif(1, 1.0)` is NULL.
+    pub fn nullif(&self, other: &Value) -> Value {
+        if self.partial_cmp(other) == Some(std::cmp::Ordering::Equal) {
+            Value::Null
+        } else {
+            self.clone()
+        }
     }
-}
 
-impl ImmutableRecord {
-    pub fn new(payload_capacity: usize, value_capacity: usize) -> Self {
-        Self {
-            payload: Vec::with_capacity(payload_capacity),
-            values: Vec::with_capacity(value_capacity),
-            recreating: false,
+    // Casts a value to REAL the way SQLite's `sqlite3_value_double` does, using the longest
+    // numeric prefix of text and blobs. Returns None only for NULL.
+    fn cast_to_real(&self) -> Option<f64> {
+        match self {
+            Value::Null => None,
+            Value::Integer(i) => Some(*i as f64),
+            Value::Float(f) => Some(*f),
+            Value::Text(t) => Some(cast_text_to_numeric_f64(t.as_str())),
+            Value::Blob(b) => Some(cast_text_to_numeric_f64(&String::from_utf8_lossy(b))),
         }
     }
 
-    pub fn get<'a, T: FromValue<'a> + 'a>(&'a self, idx: usize) -> Result<T> {
-        let value = self
-            .values
-            .get(idx)
-            .ok_or(LimboError::InternalError("Index out of bounds".into()))?;
-        T::from_value(value)
+    // Converts a value to Integer or Float for the math functions, which yield NULL for
+    // anything that does not look like a number.
+    fn numeric_for_math(&self) -> Value {
+        match self {
+            Value::Integer(_) | Value::Float(_) => self.clone(),
+            Value::Text(t) => checked_cast_text_to_numeric(t.as_str()).unwrap_or(Value::Null),
+            _ => Value::Null,
+        }
     }
+}
 
-    pub fn count(&self) -> usize {
-        self.values.len()
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalAggState {
+    pub state: *mut AggCtx,
+    pub argc: usize,
+    pub step_fn: StepFunction,
+    pub finalize_fn: FinalizeFunction,
+    pub finalized_value: Option<Value>,
+}
 
-    pub fn last_value(&self) -> Option<&RefValue> {
-        self.values.last()
+impl ExternalAggState {
+    pub fn cache_final_value(&mut self, value: Value) -> &Value {
+        self.finalized_value = Some(value);
+        self.finalized_value.as_ref().unwrap()
     }
+}
 
-    pub fn get_values(&self) -> &Vec<RefValue> {
-        &self.values
-    }
+/// Please use Display trait for all limbo output so we have single origin of truth
+/// When you need value as string:
+/// ---GOOD---
+/// format!("{}", value);
+/// ---BAD---
+/// match value {
+///   Value::Integer(i) => *i.as_str(),
+///   Value::Float(f) => *f.as_str(),
+///   ....
+/// }
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, ""),
+            Self::Integer(i) => {
+                write!(f, "{}", i)
+            }
+            Self::Float(fl) => {
+                let fl = *fl;
+                if fl == f64::INFINITY {
+                    return write!(f, "Inf");
+                }
+                if fl == f64::NEG_INFINITY {
+                    return write!(f, "-Inf");
+                }
+                if fl.is_nan() {
+                    return write!(f, "");
+                }
+                // handle negative 0
+                if fl == -0.0 {
+                    return write!(f, "{:.1}", fl.abs());
+                }
 
-    pub fn get_value(&self, idx: usize) -> &RefValue {
-        &self.values[idx]
-    }
+                // handle scientific notation without trailing zeros
+                if (fl.abs() < 1e-4 || fl.abs() >= 1e15) && fl != 0.0 {
+                    let sci_notation = format!("{:.14e}", fl);
+                    let parts: Vec<&str> = sci_notation.split('e').collect();
 
-    pub fn get_value_opt(&self, idx: usize) -> Option<&RefValue> {
-        self.values.get(idx)
-    }
+                    if parts.len() == 2 {
+                        let mantissa = parts[0];
+                        let exponent = parts[1];
 
-    pub fn len(&self) -> usize {
-        self.values.len()
-    }
+                        let decimal_parts: Vec<&str> = mantissa.split('.').collect();
+                        if decimal_parts.len() == 2 {
+                            let whole = decimal_parts[0];
+                            // 1.{this part}
+                            let mut fraction = String::from(decimal_parts[1]);
 
-    pub fn from_registers(registers: &[Register]) -> Self {
-        let mut values = Vec::with_capacity(registers.len());
-        let mut serials = Vec::with_capacity(registers.len());
-        let mut size_header = 0;
-        let mut size_values = 0;
+                            //removing trailing 0 from fraction
+                            while fraction.ends_with('0') {
+                                fraction.pop();
+                            }
 
-        let mut serial_type_buf = [0; 9];
-        // write serial types
-        for value in registers {
-            let value = value.get_owned_value();
-            let serial_type = SerialType::from(value);
-            let n = write_varint(&mut serial_type_buf[0..], serial_type.into());
-            serials.push((serial_type_buf, n));
+                            let trimmed_mantissa = if fraction.is_empty() {
+                                whole.to_string()
+                            } else {
+                                format!("{}.{}", whole, fraction)
+                            };
+                            let (prefix, exponent) = if exponent.starts_with('-') {
+                                ("-0", &exponent[1..])
+                            } else {
+                                ("+", exponent)
+                            };
+                            return write!(f, "{}e{}{}", trimmed_mantissa, prefix, exponent);
+                        }
+                    }
 
-            let value_size = serial_type.size();
+                    // fallback
+                    return write!(f, "{}", sci_notation);
+                }
 
-            size_header += n;
-            size_values += value_size;
-        }
-        let mut header_size = size_header;
-        const MIN_HEADER_SIZE: usize = 126;
-        if header_size <= MIN_HEADER_SIZE {
-            // common case
-            // This case means the header size can be contained by a single byte, therefore
-            // header_size == size of serial types + 1 byte from the header size
-            // Since header_size is a varint, and a varint the first bit is used to represent we have more bytes to read,
-            // header size here will be 126 == (2^7 - 1)
-            header_size += 1;
-        } else {
-            todo!("calculate big header size extra bytes");
-            // get header varint len
-            // header_size += n;
-            // if( nVarint<sqlite3VarintLen(nHdr) ) nHdr++;
-        }
-        // 1. write header size
-        let mut buf = Vec::new();
-        buf.reserve_exact(header_size + size_values);
-        assert_eq!(buf.capacity(), header_size + size_values);
-        assert!(header_size <= 126);
-        let n = write_varint(&mut serial_type_buf, header_size as u64);
+                // handle floating point max size is 15.
+                // If left > right && right + left > 15 go to sci notation
+                // If right > left && right + left > 15 truncate left so right + left == 15
+                let rounded = fl.round();
+                if (fl - rounded).abs() < 1e-14 {
+                    // if we very close to integer trim decimal part to 1 digit
+                    if rounded == rounded as i64 as f64 {
+                        return write!(f, "{:.1}", fl);
+                    }
+                }
 
-        buf.resize(buf.capacity(), 0);
-        let mut writer = AppendWriter::new(&mut buf, 0);
-        writer.extend_from_slice(&serial_type_buf[..n]);
+                let fl_str = format!("{}", fl);
+                let splitted = fl_str.split('.').collect::<Vec<&str>>();
+                // fallback
+                if splitted.len() != 2 {
+                    return write!(f, "{:.14e}", fl);
+                }
 
-        // 2. Write serial
-        for (value, n) in serials {
-            writer.extend_from_slice(&value[..n]);
-        }
+                let first_part = if fl < 0.0 {
+                    // remove -
+                    &splitted[0][1..]
+                } else {
+                    splitted[0]
+                };
 
-        // write content
-        for value in registers {
-            let value = value.get_owned_value();
-            let start_offset = writer.pos;
-            match value {
-                Value::Null => {
-                    values.push(RefValue::Null);
+                let second = splitted[1];
+
+                // We want more precision for smaller numbers. in SQLite case we want 15 non zero digits in 0 < number < 1
+                // leading zeroes added to max real size. But if float < 1e-4 we go to scientific notation
+                let leading_zeros = second.chars().take_while(|c| c == &'0').count();
+                let reminder = if first_part != "0" {
+                    MAX_REAL_SIZE as isize - first_part.len() as isize
+                } else {
+                    MAX_REAL_SIZE as isize + leading_zeros as isize
+                };
+                // float that have integer part > 15 converted to sci notation
+                if reminder < 0 {
+                    return write!(f, "{:.14e}", fl);
                 }
-                Value::Integer(i) => {
-                    values.push(RefValue::Integer(*i));
-                    let serial_type = SerialType::from(value);
-                    match serial_type.kind() {
-                        SerialTypeKind::ConstInt0 | SerialTypeKind::ConstInt1 => {}
-                        SerialTypeKind::I8 => writer.extend_from_slice(&(*i as i8).to_be_bytes()),
-                        SerialTypeKind::I16 => writer.extend_from_slice(&(*i as i16).to_be_bytes()),
-                        SerialTypeKind::I24 => {
-                            writer.extend_from_slice(&(*i as i32).to_be_bytes()[1..])
-                        } // remove most significant byte
-                        SerialTypeKind::I32 => writer.extend_from_slice(&(*i as i32).to_be_bytes()),
-                        SerialTypeKind::I48 => writer.extend_from_slice(&i.to_be_bytes()[2..]), // remove 2 most significant bytes
-                        SerialTypeKind::I64 => writer.extend_from_slice(&i.to_be_bytes()),
-                        other => panic!("Serial type is not an integer: {:?}", other),
-                    }
+                // trim decimal part to reminder or self len so total digits is 15;
+                let mut fl = format!("{:.*}", second.len().min(reminder as usize), fl);
+                // if decimal part ends with 0 we trim it
+                while fl.ends_with('0') {
+                    fl.pop();
                 }
-                Value::Float(f) => {
-                    values.push(RefValue::Float(*f));
-                    writer.extend_from_slice(&f.to_be_bytes())
+                write!(f, "{}", fl)
+            }
+            Self::Text(s) => {
+                write!(f, "{}", s.as_str())
+            }
+            Self::Blob(b) => write!(f, "{}", String::from_utf8_lossy(b)),
+        }
+    }
+}
+
+impl Value {
+    /// Renders the value like [Display] does, adjusted by `options`.
+    pub fn format_with(&self, options: &DisplayOptions) -> String {
+        match self {
+            Self::Null => options.null_text.clone(),
+            Self::Float(fl) if fl.is_finite() => match options.float_precision {
+                Some(precision) => format!("{fl:.precision$}"),
+                None => self.to_string(),
+            },
+            Self::Blob(b) => match options.max_blob_bytes {
+                Some(max) if b.len() > max => {
+                    format!("{}...", String::from_utf8_lossy(&b[..max]))
                 }
-                Value::Text(t) => {
-                    writer.extend_from_slice(&t.value);
-                    let end_offset = writer.pos;
-                    let len = end_offset - start_offset;
-                    let ptr = unsafe { writer.buf.as_ptr().add(start_offset) };
-                    let value = RefValue::Text(TextRef {
-                        value: RawSlice::new(ptr, len),
-                        subtype: t.subtype.clone(),
-                    });
-                    values.push(value);
+                _ => self.to_string(),
+            },
+            _ => self.to_string(),
+        }
+    }
+
+    pub fn to_ffi(&self) -> ExtValue {
+        match self {
+            Self::Null => ExtValue::null(),
+            Self::Integer(i) => ExtValue::from_integer(*i),
+            Self::Float(fl) => ExtValue::from_float(*fl),
+            Self::Text(text) => ExtValue::from_text(text.as_str().to_string()),
+            Self::Blob(blob) => ExtValue::from_blob(blob.to_vec()),
+        }
+    }
+
+    pub fn from_ffi(v: ExtValue) -> Result<Self> {
+        let res = match v.value_type() {
+            ExtValueType::Null => Ok(Value::Null),
+            ExtValueType::Integer => {
+                let Some(int) = v.to_integer() else {
+                    return Ok(Value::Null);
+                };
+                Ok(Value::Integer(int))
+            }
+            ExtValueType::Float => {
+                let Some(float) = v.to_float() else {
+                    return Ok(Value::Null);
+                };
+                Ok(Value::Float(float))
+            }
+            ExtValueType::Text => {
+                let Some(text) = v.to_text() else {
+                    return Ok(Value::Null);
+                };
+                #[cfg(feature = "json")]
+                if v.is_json() {
+                    return Ok(Value::Text(Text::json(text.to_string())));
                 }
-                Value::Blob(b) => {
-                    writer.extend_from_slice(b);
-                    let end_offset = writer.pos;
-                    let len = end_offset - start_offset;
-                    let ptr = unsafe { writer.buf.as_ptr().add(start_offset) };
-                    values.push(RefValue::Blob(RawSlice::new(ptr, len)));
+                Ok(Value::build_text(text))
+            }
+            ExtValueType::Blob => {
+                let Some(blob) = v.to_blob() else {
+                    return Ok(Value::Null);
+                };
+                Ok(Value::Blob(blob))
+            }
+            ExtValueType::Error => {
+                let Some(err) = v.to_error_details() else {
+                    return Ok(Value::Null);
+                };
+                match err {
+                    (_, Some(msg)) => Err(LimboError::ExtensionError(msg)),
+                    (code, None) => Err(LimboError::ExtensionError(code.to_string())),
                 }
-            };
+            }
+        };
+        unsafe { v.__free_internal_type() };
+        res
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AggContext {
+    Avg(Value, Value), // sum and count of non-NULL values
+    Sum(Value),
+    Count(Value, bool), // count, and whether NULLs are counted as for count(*)
+    Max(Option<Value>),
+    Min(Option<Value>),
+    GroupConcat(Value),
+    OrderedGroupConcat(Vec<(Value, Value, Value)>), // buffered (order key, value, separator)
+    Median(Vec<f64>),
+    Percentile(Vec<f64>, f64), // buffered values and the percentile, from 0 to 100
+    BitAnd(i64),
+    BitOr(i64),
+    External(ExternalAggState),
+}
+
+const NULL: Value = Value::Null;
+
+impl AggContext {
+    /// `sum()` starts as NULL so that a group with no non-NULL input sums to NULL.
+    pub fn new_sum() -> Self {
+        Self::Sum(Value::Null)
+    }
+
+    /// `total()` starts at 0.0: it is always REAL and never NULL.
+    pub fn new_total() -> Self {
+        Self::Sum(Value::Float(0.0))
+    }
+
+    pub fn new_avg() -> Self {
+        Self::Avg(Value::Float(0.0), Value::Integer(0))
+    }
+
+    /// `count(col)`, which skips NULL values.
+    pub fn new_count() -> Self {
+        Self::Count(Value::Integer(0), false)
+    }
+
+    /// `count(*)`, which counts every row.
+    pub fn new_count_all() -> Self {
+        Self::Count(Value::Integer(0), true)
+    }
+
+    pub fn new_min() -> Self {
+        Self::Min(None)
+    }
+
+    pub fn new_max() -> Self {
+        Self::Max(None)
+    }
+
+    /// `group_concat()` starts as NULL, its result when every input is NULL. The separator is
+    /// not stored because SQLite evaluates it per row.
+    pub fn new_group_concat() -> Self {
+        Self::GroupConcat(Value::Null)
+    }
+
+    /// `group_concat(... ORDER BY ...)`. Inputs are buffered with their order keys and only
+    /// joined, in key order, by [AggContext::final_value].
+    pub fn new_ordered_group_concat() -> Self {
+        Self::OrderedGroupConcat(Vec::new())
+    }
+
+    pub fn new_median() -> Self {
+        Self::Median(Vec::new())
+    }
+
+    /// `percentile(x, p)` for `p` between 0 and 100; `new_percentile(50.0)` is the median.
+    /// Any other `p`, including NaN, is an error.
+    pub fn new_percentile(p: f64) -> Result<Self> {
+        if !(0.0..=100.0).contains(&p) {
+            return Err(LimboError::InvalidArgument(format!(
+                "the percentile {p} is not between 0.0 and 100.0"
+            )));
         }
+        Ok(Self::Percentile(Vec::new(), p))
+    }
 
-        writer.assert_finish_capacity();
-        Self {
-            payload: buf,
-            values,
-            recreating: false,
+    /// `bit_and()` starts from -1, all bits set, so an empty group gives -1.
+    pub fn new_bit_and() -> Self {
+        Self::BitAnd(-1)
+    }
+
+    /// `bit_or()` starts from 0, no bits set.
+    pub fn new_bit_or() -> Self {
+        Self::BitOr(0)
+    }
+
+    /// Folds one input value into the aggregate. NULL inputs are skipped, except by the
+    /// `count(*)` context from [AggContext::new_count_all]. `min()`/`max()` compare with
+    /// [Value]'s ordering and keep the first of equal values. `group_concat()` uses a `,`
+    /// separator; see [AggContext::step_group_concat] to supply another. `median()` and
+    /// `percentile()` skip text and blobs, which SQLite rejects as not numeric, rather than
+    /// reading them as 0.
+    pub fn step(&mut self, value: &Value) {
+        if matches!(value, Value::Null) {
+            if let Self::Count(count, true) = self {
+                *count += 1;
+            }
+            return;
+        }
+        match self {
+            Self::Avg(sum, count) => {
+                *sum = sum.arith_add(value);
+                *count += 1;
+            }
+            Self::Sum(acc) => *acc += value.clone(),
+            Self::Count(count, _) => *count += 1,
+            Self::Max(max) => match max {
+                Some(current) if value <= current => {}
+                _ => *max = Some(value.clone()),
+            },
+            Self::Min(min) => match min {
+                Some(current) if value >= current => {}
+                _ => *min = Some(value.clone()),
+            },
+            Self::GroupConcat(_) => self.step_group_concat(value, &Value::build_text(",")),
+            Self::OrderedGroupConcat(_) => {
+                self.step_ordered_group_concat(value, &Value::build_text(","), value)
+            }
+            Self::Median(values) | Self::Percentile(values, _) => match value {
+                Value::Integer(i) => values.push(*i as f64),
+                Value::Float(f) => values.push(*f),
+                _ => {}
+            },
+            Self::BitAnd(acc) => *acc &= bit_operand(value),
+            Self::BitOr(acc) => *acc |= bit_operand(value),
+            Self::External(_) => {
+                unreachable!("external aggregates are stepped through their step function")
+            }
         }
     }
 
-    pub fn start_serialization(&mut self, payload: &[u8]) {
-        self.recreating = true;
-        self.payload.extend_from_slice(payload);
-    }
-    pub fn end_serialization(&mut self) {
-        assert!(self.recreating);
-        self.recreating = false;
+    /// Appends `value` to a `group_concat()`, preceded by `separator` unless it is the first
+    /// non-NULL value. NULL values are skipped and add no separator.
+    pub fn step_group_concat(&mut self, value: &Value, separator: &Value) {
+        let Self::GroupConcat(acc) = self else {
+            unreachable!("step_group_concat called on {:?}", self);
+        };
+        match (&*acc, value) {
+            (_, Value::Null) => {}
+            (Value::Null, _) => *acc = value.clone(),
+            _ => *acc = Value::build_text(format!("{}{}{}", acc, separator, value)),
+        }
+    }
+
+    /// Buffers `value` for an ordered `group_concat()`, to be joined after the values with
+    /// lower `order_key`s. NULL values are skipped.
+    pub fn step_ordered_group_concat(
+        &mut self,
+        value: &Value,
+        separator: &Value,
+        order_key: &Value,
+    ) {
+        let Self::OrderedGroupConcat(rows) = self else {
+            unreachable!("step_ordered_group_concat called on {:?}", self);
+        };
+        if !matches!(value, Value::Null) {
+            rows.push((order_key.clone(), value.clone(), separator.clone()));
+        }
+    }
+
+    pub fn compute_external(&mut self) -> Result<()> {
+        if let Self::External(ext_state) = self {
+            if ext_state.finalized_value.is_none() {
+                let final_value = unsafe { (ext_state.finalize_fn)(ext_state.state) };
+                ext_state.cache_final_value(Value::from_ffi(final_value)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// The aggregate's result. `Avg` keeps the running sum and the number of non-NULL inputs,
+    /// and divides only here, giving NULL for an empty group.
+    pub fn final_value(&self) -> Value {
+        match self {
+            Self::Avg(sum, count) => match count {
+                Value::Integer(0) => Value::Null,
+                _ => sum.clone() / count.clone(),
+            },
+            Self::Sum(acc) => acc.clone(),
+            Self::Count(count, _) => count.clone(),
+            Self::Max(max) => max.clone().unwrap_or(NULL),
+            Self::Min(min) => min.clone().unwrap_or(NULL),
+            Self::GroupConcat(s) => s.clone(),
+            Self::OrderedGroupConcat(rows) => {
+                let mut rows: Vec<_> = rows.iter().collect();
+                // A stable sort keeps rows with equal keys in input order.
+                rows.sort_by(|a, b| a.0.cmp(&b.0));
+                let mut concat = Self::new_group_concat();
+                for (_, value, separator) in rows {
+                    concat.step_group_concat(value, separator);
+                }
+                concat.final_value()
+            }
+            Self::Median(values) => percentile(values, 50.0),
+            Self::Percentile(values, p) => percentile(values, *p),
+            Self::BitAnd(acc) | Self::BitOr(acc) => Value::Integer(*acc),
+            Self::External(ext_state) => ext_state.finalized_value.clone().unwrap_or(NULL),
+        }
+    }
+}
+
+/// Reads a `bit_and()`/`bit_or()` input as an integer, truncating floats and reading text as a
+/// number.
+fn bit_operand(value: &Value) -> i64 {
+    match Numeric::from(value) {
+        Numeric::Null => 0,
+        Numeric::Integer(i) => i,
+        Numeric::Float(f) => f64::from(f) as i64,
+    }
+}
+
+/// The `p`-th percentile of `values`, interpolating linearly between the two nearest values
+/// when it falls between them, or NULL when there are none.
+fn percentile(values: &[f64], p: f64) -> Value {
+    if values.is_empty() {
+        return Value::Null;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    Value::Float(sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64))
+}
+
+impl PartialEq<Value> for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Self::Integer(int_left), Self::Integer(int_right)) => int_left == int_right,
+            (Self::Integer(int_left), Self::Float(float_right)) => {
+                (*int_left as f64) == (*float_right)
+            }
+            (Self::Float(float_left), Self::Integer(int_right)) => {
+                float_left == (&(*int_right as f64))
+            }
+            (Self::Float(float_left), Self::Float(float_right)) => float_left == float_right,
+            (Self::Integer(_) | Self::Float(_), Self::Text(_) | Self::Blob(_)) => false,
+            (Self::Text(_) | Self::Blob(_), Self::Integer(_) | Self::Float(_)) => false,
+            (Self::Text(text_left), Self::Text(text_right)) => {
+                text_left.value.eq(&text_right.value)
+            }
+            (Self::Blob(blob_left), Self::Blob(blob_right)) => blob_left.eq(blob_right),
+            (Self::Null, Self::Null) => true,
+            _ => false,
+        }
+    }
+
+    fn ne(&self, other: &Value) -> bool {
+        !self.eq(other)
+    }
+}
+
+#[allow(clippy::non_canonical_partial_ord_impl)]
+impl PartialOrd<Value> for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Integer(int_left), Self::Integer(int_right)) => int_left.partial_cmp(int_right),
+            (Self::Integer(int_left), Self::Float(float_right)) => {
+                (*int_left as f64).partial_cmp(float_right)
+            }
+            (Self::Float(float_left), Self::Integer(int_right)) => {
+                float_left.partial_cmp(&(*int_right as f64))
+            }
+            (Self::Float(float_left), Self::Float(float_right)) => {
+                float_left.partial_cmp(float_right)
+            }
+            // Numeric vs Text/Blob
+            (Self::Integer(_) | Self::Float(_), Self::Text(_) | Self::Blob(_)) => {
+                Some(std::cmp::Ordering::Less)
+            }
+            (Self::Text(_) | Self::Blob(_), Self::Integer(_) | Self::Float(_)) => {
+                Some(std::cmp::Ordering::Greater)
+            }
+
+            (Self::Text(text_left), Self::Text(text_right)) => {
+                text_left.value.partial_cmp(&text_right.value)
+            }
+            // Text vs Blob
+            (Self::Text(_), Self::Blob(_)) => Some(std::cmp::Ordering::Less),
+            (Self::Blob(_), Self::Text(_)) => Some(std::cmp::Ordering::Greater),
+
+            (Self::Blob(blob_left), Self::Blob(blob_right)) => blob_left.partial_cmp(blob_right),
+            (Self::Null, Self::Null) => Some(std::cmp::Ordering::Equal),
+            (Self::Null, _) => Some(std::cmp::Ordering::Less),
+            (_, Self::Null) => Some(std::cmp::Ordering::Greater),
+        }
+    }
+}
+
+impl PartialOrd<AggContext> for AggContext {
+    fn partial_cmp(&self, other: &AggContext) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Avg(a, _), Self::Avg(b, _)) => a.partial_cmp(b),
+            (Self::Sum(a), Self::Sum(b)) => a.partial_cmp(b),
+            (Self::Count(a, _), Self::Count(b, _)) => a.partial_cmp(b),
+            (Self::Max(a), Self::Max(b)) => a.partial_cmp(b),
+            (Self::Min(a), Self::Min(b)) => a.partial_cmp(b),
+            (Self::GroupConcat(a), Self::GroupConcat(b)) => a.partial_cmp(b),
+            _ => None,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+/// Accumulating addition used by aggregates: a NULL operand is skipped rather than making the
+/// result NULL, and text or blob operands are read as numbers and make the result REAL, as in
+/// `sum()`. Use [Value::arith_add] for the SQL `+` operator and `||` to join text.
+impl std::ops::Add<Value> for Value {
+    type Output = Value;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (Self::Integer(int_left), Self::Integer(int_right)) => {
+                int_left.checked_add(int_right).map_or(
+                    Self::Float(int_left as f64 + int_right as f64),
+                    Self::Integer,
+                )
+            }
+            (Self::Integer(int_left), Self::Float(float_right)) => {
+                Self::Float(int_left as f64 + float_right)
+            }
+            (Self::Float(float_left), Self::Integer(int_right)) => {
+                Self::Float(float_left + int_right as f64)
+            }
+            (Self::Float(float_left), Self::Float(float_right)) => {
+                Self::Float(float_left + float_right)
+            }
+            (lhs @ (Self::Integer(_) | Self::Float(_)), Self::Null) => lhs,
+            (Self::Null, rhs @ (Self::Integer(_) | Self::Float(_))) => rhs,
+            (Self::Null, Self::Null) => Self::Null,
+            (lhs, rhs) => {
+                // Text that is wholly a number counts as that number; anything else is read
+                // as a float prefix, which also makes the result REAL.
+                let coerce = |value: Value| match &value {
+                    Value::Text(t) => checked_cast_text_to_numeric_exact(t.as_str())
+                        .unwrap_or_else(|_| Value::Float(Numeric::from(&value).into_f64())),
+                    Value::Blob(_) => Value::Float(Numeric::from(&value).into_f64()),
+                    _ => value,
+                };
+                coerce(lhs) + coerce(rhs)
+            }
+        }
+    }
+}
+
+impl std::ops::Add<f64> for Value {
+    type Output = Value;
+
+    fn add(self, rhs: f64) -> Self::Output {
+        match self {
+            Self::Integer(int_left) => Self::Float(int_left as f64 + rhs),
+            Self::Float(float_left) => Self::Float(float_left + rhs),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Counter-style addition: NULL counts as zero, text and blobs are returned unchanged and an
+/// integer overflow spills into a float, so accumulators never panic.
+impl std::ops::Add<i64> for Value {
+    type Output = Value;
+
+    fn add(self, rhs: i64) -> Self::Output {
+        match self {
+            Self::Integer(int_left) => int_left
+                .checked_add(rhs)
+                .map_or(Self::Float(int_left as f64 + rhs as f64), Self::Integer),
+            Self::Float(float_left) => Self::Float(float_left + rhs as f64),
+            Self::Null => Self::Integer(rhs),
+            other => other,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Value {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = self.clone() + rhs;
+    }
+}
+
+impl std::ops::AddAssign<i64> for Value {
+    fn add_assign(&mut self, rhs: i64) {
+        *self = self.clone() + rhs;
+    }
+}
+
+impl std::ops::AddAssign<f64> for Value {
+    fn add_assign(&mut self, rhs: f64) {
+        *self = self.clone() + rhs;
+    }
+}
+
+impl std::ops::Div<Value> for Value {
+    type Output = Value;
+
+    fn div(self, rhs: Value) -> Self::Output {
+        match (self, rhs) {
+            (Self::Integer(int_left), Self::Integer(int_right)) => {
+                Self::Integer(int_left / int_right)
+            }
+            (Self::Integer(int_left), Self::Float(float_right)) => {
+                Self::Float(int_left as f64 / float_right)
+            }
+            (Self::Float(float_left), Self::Integer(int_right)) => {
+                Self::Float(float_left / int_right as f64)
+            }
+            (Self::Float(float_left), Self::Float(float_right)) => {
+                Self::Float(float_left / float_right)
+            }
+            _ => Self::Float(0.0),
+        }
+    }
+}
+
+impl std::ops::DivAssign<Value> for Value {
+    fn div_assign(&mut self, rhs: Value) {
+        *self = self.clone() / rhs;
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Integer(value as i64)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::Text(Text::from(value))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::build_text(value)
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Blob(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl TryFrom<&Value> for i64 {
+    type Error = LimboError;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        match value {
+            Value::Integer(i) => Ok(*i),
+            _ => Err(type_mismatch("integer", value.type_name())),
+        }
+    }
+}
+
+impl TryFrom<&Value> for f64 {
+    type Error = LimboError;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        match value {
+            Value::Float(f) => Ok(*f),
+            _ => Err(type_mismatch("real", value.type_name())),
+        }
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = LimboError;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        match value {
+            Value::Text(t) => Ok(t.as_str().to_string()),
+            _ => Err(type_mismatch("text", value.type_name())),
+        }
+    }
+}
+
+impl TryFrom<&Value> for Vec<u8> {
+    type Error = LimboError;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        match value {
+            Value::Blob(b) => Ok(b.clone()),
+            _ => Err(type_mismatch("blob", value.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = LimboError;
+
+    fn try_from(value: Value) -> Result<Self> {
+        Self::try_from(&value)
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = LimboError;
+
+    fn try_from(value: Value) -> Result<Self> {
+        Self::try_from(&value)
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = LimboError;
+
+    fn try_from(value: Value) -> Result<Self> {
+        match value {
+            Value::Text(t) => String::from_utf8(t.value)
+                .map_err(|_| LimboError::ConversionError("Expected text value".into())),
+            _ => Err(type_mismatch("text", value.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Value> for Vec<u8> {
+    type Error = LimboError;
+
+    fn try_from(value: Value) -> Result<Self> {
+        match value {
+            Value::Blob(b) => Ok(b),
+            _ => Err(type_mismatch("blob", value.type_name())),
+        }
+    }
+}
+
+/// The error returned when a value is read back as a type it does not hold, naming both the
+/// expected and the actual type.
+pub(crate) fn type_mismatch(expected: &str, found: &str) -> LimboError {
+    LimboError::ConversionError(format!("Expected {expected} value, found {found}"))
+}
+
+pub trait FromValue<'a> {
+    fn from_value(value: &'a RefValue) -> Result<Self>
+    where
+        Self: Sized + 'a;
+}
+
+impl<'a> FromValue<'a> for i64 {
+    fn from_value(value: &'a RefValue) -> Result<Self> {
+        match value {
+            RefValue::Integer(i) => Ok(*i),
+            _ => Err(type_mismatch("integer", value.type_name())),
+        }
+    }
+}
+
+impl<'a> FromValue<'a> for String {
+    fn from_value(value: &'a RefValue) -> Result<Self> {
+        match value {
+            RefValue::Text(s) => Ok(s.as_str().to_string()),
+            _ => Err(type_mismatch("text", value.type_name())),
+        }
+    }
+}
+
+impl<'a> FromValue<'a> for &'a str {
+    fn from_value(value: &'a RefValue) -> Result<Self> {
+        match value {
+            RefValue::Text(s) => Ok(s.as_str()),
+            _ => Err(type_mismatch("text", value.type_name())),
+        }
+    }
+}
+
+impl<'a> FromValue<'a> for &'a [u8] {
+    fn from_value(value: &'a RefValue) -> Result<Self> {
+        match value {
+            RefValue::Blob(b) => Ok(b.to_slice()),
+            _ => Err(type_mismatch("blob", value.type_name())),
+        }
+    }
+}
+
+impl<'a> FromValue<'a> for Vec<u8> {
+    fn from_value(value: &'a RefValue) -> Result<Self> {
+        match value {
+            RefValue::Blob(b) => Ok(b.to_slice().to_vec()),
+            _ => Err(type_mismatch("blob", value.type_name())),
+        }
+    }
+}
+
+/// This struct serves the purpose of not allocating multiple vectors of bytes if not needed.
+/// A value in a record that has already been serialized can stay serialized and what this struct offsers
+/// is easy acces to each value which point to the payload.
+/// The name might be contradictory as it is immutable in the sense that you cannot modify the values without modifying the payload.
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ImmutableRecord {
+    // We have to be super careful with this buffer since we make values point to the payload we need to take care reallocations
+    // happen in a controlled manner. If we realocate with values that should be correct, they will now point to undefined data.
+    // We don't use pin here because it would make it imposible to reuse the buffer if we need to push a new record in the same struct.
+    payload: Vec<u8>,
+    pub values: Vec<RefValue>,
+    recreating: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Record {
+    values: RecordValues,
+}
+
+/// Storage for a [Record]'s values. With the `smallvec` feature records of up to
+/// [RECORD_INLINE_VALUES] values are kept inline instead of on the heap.
+#[cfg(feature = "smallvec")]
+type RecordValues = smallvec::SmallVec<[Value; RECORD_INLINE_VALUES]>;
+#[cfg(not(feature = "smallvec"))]
+type RecordValues = Vec<Value>;
+
+#[cfg(feature = "smallvec")]
+pub const RECORD_INLINE_VALUES: usize = 8;
+
+impl Record {
+    // pub fn get<'a, T: FromValue<'a> + 'a>(&'a self, idx: usize) -> Result<T> {
+    //     let value = &self.values[idx];
+    //     T::from_value(value)
+    // }
+
+    pub fn count(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn last_value(&self) -> Option<&Value> {
+        self.values.last()
+    }
+
+    #[cfg(not(feature = "smallvec"))]
+    pub fn get_values(&self) -> &Vec<Value> {
+        &self.values
+    }
+
+    /// With the `smallvec` feature the values are not stored in a `Vec`, so they are returned
+    /// as a slice.
+    #[cfg(feature = "smallvec")]
+    pub fn get_values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Value> {
+        self.values.iter()
+    }
+
+    pub fn get_value(&self, idx: usize) -> &Value {
+        &self.values[idx]
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Builds a new record by applying `f` to every column in order.
+    pub fn map_values<F: FnMut(&Value) -> Value>(&self, f: F) -> Record {
+        self.values.iter().map(f).collect()
+    }
+
+    /// Builds a copy of this record with each column converted by the matching affinity, as
+    /// the `Affinity` instruction converts values before they are stored or compared. Columns
+    /// past the end of `affinities` are copied unchanged.
+    pub fn apply_affinities(&self, affinities: &[Affinity]) -> Record {
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let mut value = value.clone();
+                if let Some(&affinity) = affinities.get(i) {
+                    value.apply_affinity(affinity);
+                }
+                value
+            })
+            .collect()
+    }
+
+    /// Builds the record a STRICT table stores for this row, converting each column to its
+    /// declared type with [StrictType::convert], as the `TypeCheck` instruction does. Columns
+    /// past the end of `column_types` are copied unchanged. A value that cannot be stored in
+    /// its column is a constraint error naming the column by its index.
+    pub fn enforce_strict_types(&self, column_types: &[StrictType]) -> Result<Record> {
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let Some(&column_type) = column_types.get(i) else {
+                    return Ok(value.clone());
+                };
+                column_type.convert(value).ok_or_else(|| {
+                    LimboError::Constraint(format!(
+                        "cannot store {} value in {column_type} column {i}",
+                        value.type_name().to_uppercase()
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Record::new)
+    }
+
+    /// Builds a record of the first `n` columns, or of all of them if there are fewer, as an
+    /// index seek on a prefix of the key columns needs.
+    pub fn prefix(&self, n: usize) -> Record {
+        self.values.iter().take(n).cloned().collect()
+    }
+
+    /// Builds a copy of this record extended with NULL columns up to `n` columns. A record
+    /// that already has `n` or more columns is copied unchanged.
+    pub fn pad_to(&self, n: usize) -> Record {
+        self.values
+            .iter()
+            .cloned()
+            .chain(std::iter::repeat(Value::Null).take(n.saturating_sub(self.len())))
+            .collect()
+    }
+
+    /// Builds a new record holding the columns of `self` followed by those of `other`, as a
+    /// join produces from an outer and an inner row.
+    pub fn concat(&self, other: &Record) -> Record {
+        self.values
+            .iter()
+            .chain(other.values.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Builds a new record from the columns at `indices`, in that order. Columns may be
+    /// repeated or dropped.
+    pub fn project(&self, indices: &[usize]) -> Result<Record> {
+        indices
+            .iter()
+            .map(|&idx| {
+                self.values.get(idx).cloned().ok_or_else(|| {
+                    LimboError::InvalidArgument(format!(
+                        "column index {} out of range for record with {} columns",
+                        idx,
+                        self.values.len()
+                    ))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Builds a record without going through an intermediate `Vec`, so that small records stay
+/// inline when the `smallvec` feature is enabled.
+impl FromIterator<Value> for Record {
+    fn from_iter<I: IntoIterator<Item = Value>>(iter: I) -> Self {
+        Self {
+            values: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for Record {
+    type Item = Value;
+    type IntoIter = <RecordValues as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Record {
+    type Item = &'a Value;
+    type IntoIter = std::slice::Iter<'a, Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.iter()
+    }
+}
+
+struct AppendWriter<'a> {
+    buf: &'a mut Vec<u8>,
+    pos: usize,
+    buf_capacity_start: usize,
+    buf_ptr_start: *const u8,
+}
+
+impl<'a> AppendWriter<'a> {
+    pub fn new(buf: &'a mut Vec<u8>, pos: usize) -> Self {
+        let buf_ptr_start = buf.as_ptr();
+        let buf_capacity_start = buf.capacity();
+        Self {
+            buf,
+            pos,
+            buf_capacity_start,
+            buf_ptr_start,
+        }
+    }
+
+    #[inline]
+    pub fn extend_from_slice(&mut self, slice: &[u8]) {
+        self.buf[self.pos..self.pos + slice.len()].copy_from_slice(slice);
+        self.pos += slice.len();
+    }
+
+    fn assert_finish_capacity(&self) {
+        // let's make sure we didn't reallocate anywhere else
+        assert_eq!(self.buf_capacity_start, self.buf.capacity());
+        assert_eq!(self.buf_ptr_start, self.buf.as_ptr());
+    }
+}
+
+impl ImmutableRecord {
+    pub fn new(payload_capacity: usize, value_capacity: usize) -> Self {
+        Self {
+            payload: Vec::with_capacity(payload_capacity),
+            values: Vec::with_capacity(value_capacity),
+            recreating: false,
+        }
+    }
+
+    pub fn get<'a, T: FromValue<'a> + 'a>(&'a self, idx: usize) -> Result<T> {
+        let value = self
+            .values
+            .get(idx)
+            .ok_or(LimboError::InternalError("Index out of bounds".into()))?;
+        T::from_value(value)
+    }
+
+    pub fn count(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn last_value(&self) -> Option<&RefValue> {
+        self.values.last()
+    }
+
+    pub fn get_values(&self) -> &Vec<RefValue> {
+        &self.values
+    }
+
+    pub fn get_value(&self, idx: usize) -> &RefValue {
+        &self.values[idx]
+    }
+
+    pub fn get_value_opt(&self, idx: usize) -> Option<&RefValue> {
+        self.values.get(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn from_registers(registers: &[Register]) -> Self {
+        let mut values = Vec::with_capacity(registers.len());
+        let mut serials = Vec::with_capacity(registers.len());
+        let mut size_header = 0;
+        let mut size_values = 0;
+
+        let mut serial_type_buf = [0; 9];
+        // write serial types
+        for value in registers {
+            let value = value.get_owned_value();
+            let serial_type = SerialType::from(value);
+            let n = write_varint(&mut serial_type_buf[0..], serial_type.into());
+            serials.push((serial_type_buf, n));
+
+            let value_size = serial_type.size();
+
+            size_header += n;
+            size_values += value_size;
+        }
+        let mut header_size = size_header;
+        const MIN_HEADER_SIZE: usize = 126;
+        if header_size <= MIN_HEADER_SIZE {
+            // common case
+            // This case means the header size can be contained by a single byte, therefore
+            // header_size == size of serial types + 1 byte from the header size
+            // Since header_size is a varint, and a varint the first bit is used to represent we have more bytes to read,
+            // header size here will be 126 == (2^7 - 1)
+            header_size += 1;
+        } else {
+            todo!("calculate big header size extra bytes");
+            // get header varint len
+            // header_size += n;
+            // if( nVarint<sqlite3VarintLen(nHdr) ) nHdr++;
+        }
+        // 1. write header size
+        let mut buf = Vec::new();
+        buf.reserve_exact(header_size + size_values);
+        assert_eq!(buf.capacity(), header_size + size_values);
+        assert!(header_size <= 126);
+        let n = write_varint(&mut serial_type_buf, header_size as u64);
+
+        buf.resize(buf.capacity(), 0);
+        let mut writer = AppendWriter::new(&mut buf, 0);
+        writer.extend_from_slice(&serial_type_buf[..n]);
+
+        // 2. Write serial
+        for (value, n) in serials {
+            writer.extend_from_slice(&value[..n]);
+        }
+
+        // write content
+        for value in registers {
+            let value = value.get_owned_value();
+            let start_offset = writer.pos;
+            match value {
+                Value::Null => {
+                    values.push(RefValue::Null);
+                }
+                Value::Integer(i) => {
+                    values.push(RefValue::Integer(*i));
+                    let serial_type = SerialType::from(value);
+                    match serial_type.kind() {
+                        SerialTypeKind::ConstInt0 | SerialTypeKind::ConstInt1 => {}
+                        SerialTypeKind::I8 => writer.extend_from_slice(&(*i as i8).to_be_bytes()),
+                        SerialTypeKind::I16 => writer.extend_from_slice(&(*i as i16).to_be_bytes()),
+                        SerialTypeKind::I24 => {
+                            writer.extend_from_slice(&(*i as i32).to_be_bytes()[1..])
+                        } // remove most significant byte
+                        SerialTypeKind::I32 => writer.extend_from_slice(&(*i as i32).to_be_bytes()),
+                        SerialTypeKind::I48 => writer.extend_from_slice(&i.to_be_bytes()[2..]), // remove 2 most significant bytes
+                        SerialTypeKind::I64 => writer.extend_from_slice(&i.to_be_bytes()),
+                        other => panic!("Serial type is not an integer: {:?}", other),
+                    }
+                }
+                Value::Float(f) => {
+                    values.push(RefValue::Float(*f));
+                    writer.extend_from_slice(&f.to_be_bytes())
+                }
+                Value::Text(t) => {
+                    writer.extend_from_slice(&t.value);
+                    let end_offset = writer.pos;
+                    let len = end_offset - start_offset;
+                    let ptr = unsafe { writer.buf.as_ptr().add(start_offset) };
+                    let value = RefValue::Text(TextRef {
+                        value: RawSlice::new(ptr, len),
+                        subtype: t.subtype.clone(),
+                    });
+                    values.push(value);
+                }
+                Value::Blob(b) => {
+                    writer.extend_from_slice(b);
+                    let end_offset = writer.pos;
+                    let len = end_offset - start_offset;
+                    let ptr = unsafe { writer.buf.as_ptr().add(start_offset) };
+                    values.push(RefValue::Blob(RawSlice::new(ptr, len)));
+                }
+            };
+        }
+
+        writer.assert_finish_capacity();
+        Self {
+            payload: buf,
+            values,
+            recreating: false,
+        }
+    }
+
+    pub fn start_serialization(&mut self, payload: &[u8]) {
+        self.recreating = true;
+        self.payload.extend_from_slice(payload);
+    }
+    pub fn end_serialization(&mut self) {
+        assert!(self.recreating);
+        self.recreating = false;
+    }
+
+    pub fn add_value(&mut self, value: RefValue) {
+        assert!(self.recreating);
+        self.values.push(value);
+    }
+
+    pub fn invalidate(&mut self) {
+        self.payload.clear();
+        self.values.clear();
+    }
+
+    pub fn get_payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+impl Display for ImmutableRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for value in &self.values {
+            match value {
+                RefValue::Null => write!(f, "NULL")?,
+                RefValue::Integer(i) => write!(f, "Integer({})", *i)?,
+                RefValue::Float(flo) => write!(f, "Float({})", *flo)?,
+                RefValue::Text(text_ref) => write!(f, "Text({})", text_ref.as_str())?,
+                RefValue::Blob(raw_slice) => {
+                    write!(f, "Blob({})", String::from_utf8_lossy(raw_slice.to_slice()))?
+                }
+            }
+            if value != self.values.last().unwrap() {
+                write!(f, ", ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Clone for ImmutableRecord {
+    fn clone(&self) -> Self {
+        let mut new_values = Vec::new();
+        let new_payload = self.payload.clone();
+        for value in &self.values {
+            let value = match value {
+                RefValue::Null => RefValue::Null,
+                RefValue::Integer(i) => RefValue::Integer(*i),
+                RefValue::Float(f) => RefValue::Float(*f),
+                RefValue::Text(text_ref) => {
+                    // let's update pointer
+                    let ptr_start = self.payload.as_ptr() as usize;
+                    let ptr_end = text_ref.value.data as usize;
+                    let len = ptr_end - ptr_start;
+                    let new_ptr = unsafe { new_payload.as_ptr().add(len) };
+                    RefValue::Text(TextRef {
+                        value: RawSlice::new(new_ptr, text_ref.value.len),
+                        subtype: text_ref.subtype.clone(),
+                    })
+                }
+                RefValue::Blob(raw_slice) => {
+                    let ptr_start = self.payload.as_ptr() as usize;
+                    let ptr_end = raw_slice.data as usize;
+                    let len = ptr_end - ptr_start;
+                    let new_ptr = unsafe { new_payload.as_ptr().add(len) };
+                    RefValue::Blob(RawSlice::new(new_ptr, raw_slice.len))
+                }
+            };
+            new_values.push(value);
+        }
+        Self {
+            payload: new_payload,
+            values: new_values,
+            recreating: self.recreating,
+        }
+    }
+}
+
+impl RefValue {
+    /// The name `typeof()` reports for this value, as [Value::type_name] does.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Integer(_) => "integer",
+            Self::Float(_) => "real",
+            Self::Text(_) => "text",
+            Self::Blob(_) => "blob",
+        }
+    }
+
+    pub fn to_ffi(&self) -> ExtValue {
+        match self {
+            Self::Null => ExtValue::null(),
+            Self::Integer(i) => ExtValue::from_integer(*i),
+            Self::Float(fl) => ExtValue::from_float(*fl),
+            Self::Text(text) => ExtValue::from_text(
+                std::str::from_utf8(text.value.to_slice())
+                    .unwrap()
+                    .to_string(),
+            ),
+            Self::Blob(blob) => ExtValue::from_blob(blob.to_slice().to_vec()),
+        }
+    }
+
+    pub fn to_owned(&self) -> Value {
+        match self {
+            RefValue::Null => Value::Null,
+            RefValue::Integer(i) => Value::Integer(*i),
+            RefValue::Float(f) => Value::Float(*f),
+            RefValue::Text(text_ref) => Value::Text(Text {
+                value: text_ref.value.to_slice().to_vec(),
+                subtype: text_ref.subtype.clone(),
+            }),
+            RefValue::Blob(b) => Value::Blob(b.to_slice().to_vec()),
+        }
+    }
+    pub fn to_blob(&self) -> Option<&[u8]> {
+        match self {
+            Self::Blob(blob) => Some(blob.to_slice()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RefValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "NULL"),
+            Self::Integer(i) => write!(f, "{}", i),
+            Self::Float(fl) => write!(f, "{:?}", fl),
+            Self::Text(s) => write!(f, "{}", s.as_str()),
+            Self::Blob(b) => write!(f, "{}", String::from_utf8_lossy(b.to_slice())),
+        }
+    }
+}
+impl Eq for RefValue {}
+
+impl Ord for RefValue {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+#[allow(clippy::non_canonical_partial_ord_impl)]
+impl PartialOrd<RefValue> for RefValue {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Self::Integer(int_left), Self::Integer(int_right)) => int_left.partial_cmp(int_right),
+            (Self::Integer(int_left), Self::Float(float_right)) => {
+                (*int_left as f64).partial_cmp(float_right)
+            }
+            (Self::Float(float_left), Self::Integer(int_right)) => {
+                float_left.partial_cmp(&(*int_right as f64))
+            }
+            (Self::Float(float_left), Self::Float(float_right)) => {
+                float_left.partial_cmp(float_right)
+            }
+            // Numeric vs Text/Blob
+            (Self::Integer(_) | Self::Float(_), Self::Text(_) | Self::Blob(_)) => {
+                Some(std::cmp::Ordering::Less)
+            }
+            (Self::Text(_) | Self::Blob(_), Self::Integer(_) | Self::Float(_)) => {
+                Some(std::cmp::Ordering::Greater)
+            }
+
+            (Self::Text(text_left), Self::Text(text_right)) => text_left
+                .value
+                .to_slice()
+                .partial_cmp(text_right.value.to_slice()),
+            // Text vs Blob
+            (Self::Text(_), Self::Blob(_)) => Some(std::cmp::Ordering::Less),
+            (Self::Blob(_), Self::Text(_)) => Some(std::cmp::Ordering::Greater),
+
+            (Self::Blob(blob_left), Self::Blob(blob_right)) => {
+                blob_left.to_slice().partial_cmp(blob_right.to_slice())
+            }
+            (Self::Null, Self::Null) => Some(std::cmp::Ordering::Equal),
+            (Self::Null, _) => Some(std::cmp::Ordering::Less),
+            (_, Self::Null) => Some(std::cmp::Ordering::Greater),
+        }
+    }
+}
+
+/// A bitfield that represents the comparison spec for index keys.
+/// Since indexed columns can individually specify ASC/DESC, each key must
+/// be compared differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct IndexKeySortOrder(u64);
+
+impl IndexKeySortOrder {
+    pub fn get_sort_order_for_col(&self, column_idx: usize) -> SortOrder {
+        assert!(column_idx < 64, "column index out of range: {}", column_idx);
+        match self.0 & (1 << column_idx) {
+            0 => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+
+    pub fn from_index(index: &Index) -> Self {
+        let mut spec = 0;
+        for (i, column) in index.columns.iter().enumerate() {
+            spec |= ((column.order == SortOrder::Desc) as u64) << i;
+        }
+        IndexKeySortOrder(spec)
+    }
+
+    pub fn from_list(order: &[SortOrder]) -> Self {
+        let mut spec = 0;
+        for (i, order) in order.iter().enumerate() {
+            spec |= ((*order == SortOrder::Desc) as u64) << i;
+        }
+        IndexKeySortOrder(spec)
+    }
+
+    pub fn default() -> Self {
+        Self(0)
+    }
+}
+
+impl Default for IndexKeySortOrder {
+    fn default() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+/// Metadata about an index, used for handling and comparing index keys.
+///
+/// This struct provides information about the sorting order of columns,
+/// whether the index includes a row ID, and the total number of columns
+/// in the index.
+pub struct IndexKeyInfo {
+    /// Specifies the sorting order (ascending or descending) for each column in the index.
+    pub sort_order: IndexKeySortOrder,
+    /// Indicates whether the index includes a row ID column.
+    pub has_rowid: bool,
+    /// The total number of columns in the index, including the row ID column if present.
+    pub num_cols: usize,
+}
+
+impl IndexKeyInfo {
+    pub fn new_from_index(index: &Index) -> Self {
+        Self {
+            sort_order: IndexKeySortOrder::from_index(index),
+            has_rowid: index.has_rowid,
+            num_cols: index.columns.len() + (index.has_rowid as usize),
+        }
+    }
+}
+
+pub fn compare_immutable(
+    l: &[RefValue],
+    r: &[RefValue],
+    index_key_sort_order: IndexKeySortOrder,
+    collations: &[CollationSeq],
+) -> std::cmp::Ordering {
+    assert_eq!(l.len(), r.len());
+    for (i, (l, r)) in l.iter().zip(r).enumerate() {
+        let column_order = index_key_sort_order.get_sort_order_for_col(i);
+        let collation = collations.get(i).copied().unwrap_or_default();
+        let cmp = match (l, r) {
+            (RefValue::Text(left), RefValue::Text(right)) => {
+                collation.compare_strings(left.as_str(), right.as_str())
+            }
+            _ => l.partial_cmp(r).unwrap(),
+        };
+        if !cmp.is_eq() {
+            return match column_order {
+                SortOrder::Asc => cmp,
+                SortOrder::Desc => cmp.reverse(),
+            };
+        }
+    }
+    std::cmp::Ordering::Equal
+}
+
+const I8_LOW: i64 = -128;
+const I8_HIGH: i64 = 127;
+const I16_LOW: i64 = -32768;
+const I16_HIGH: i64 = 32767;
+const I24_LOW: i64 = -8388608;
+const I24_HIGH: i64 = 8388607;
+const I32_LOW: i64 = -2147483648;
+const I32_HIGH: i64 = 2147483647;
+const I48_LOW: i64 = -140737488355328;
+const I48_HIGH: i64 = 140737488355327;
+
+/// Sqlite Serial Types
+/// https://www.sqlite.org/fileformat.html#record_format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct SerialType(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SerialTypeKind {
+    Null,
+    I8,
+    I16,
+    I24,
+    I32,
+    I48,
+    I64,
+    F64,
+    ConstInt0,
+    ConstInt1,
+    Text,
+    Blob,
+}
+
+impl SerialType {
+    #[inline(always)]
+    pub fn u64_is_valid_serial_type(n: u64) -> bool {
+        n != 10 && n != 11
+    }
+
+    const NULL: Self = Self(0);
+    const I8: Self = Self(1);
+    const I16: Self = Self(2);
+    const I24: Self = Self(3);
+    const I32: Self = Self(4);
+    const I48: Self = Self(5);
+    const I64: Self = Self(6);
+    const F64: Self = Self(7);
+    const CONST_INT0: Self = Self(8);
+    const CONST_INT1: Self = Self(9);
+
+    pub fn null() -> Self {
+        Self::NULL
+    }
+
+    pub fn i8() -> Self {
+        Self::I8
+    }
+
+    pub fn i16() -> Self {
+        Self::I16
+    }
+
+    pub fn i24() -> Self {
+        Self::I24
+    }
+
+    pub fn i32() -> Self {
+        Self::I32
+    }
+
+    pub fn i48() -> Self {
+        Self::I48
+    }
+
+    pub fn i64() -> Self {
+        Self::I64
+    }
+
+    pub fn f64() -> Self {
+        Self::F64
+    }
+
+    pub fn const_int0() -> Self {
+        Self::CONST_INT0
+    }
+
+    pub fn const_int1() -> Self {
+        Self::CONST_INT1
+    }
+
+    pub fn blob(size: u64) -> Self {
+        Self(12 + size * 2)
+    }
+
+    pub fn text(size: u64) -> Self {
+        Self(13 + size * 2)
+    }
+
+    pub fn kind(&self) -> SerialTypeKind {
+        match self.0 {
+            0 => SerialTypeKind::Null,
+            1 => SerialTypeKind::I8,
+            2 => SerialTypeKind::I16,
+            3 => SerialTypeKind::I24,
+            4 => SerialTypeKind::I32,
+            5 => SerialTypeKind::I48,
+            6 => SerialTypeKind::I64,
+            7 => SerialTypeKind::F64,
+            8 => SerialTypeKind::ConstInt0,
+            9 => SerialTypeKind::ConstInt1,
+            n if n >= 12 => match n % 2 {
+                0 => SerialTypeKind::Blob,
+                1 => SerialTypeKind::Text,
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self.kind() {
+            SerialTypeKind::Null => 0,
+            SerialTypeKind::I8 => 1,
+            SerialTypeKind::I16 => 2,
+            SerialTypeKind::I24 => 3,
+            SerialTypeKind::I32 => 4,
+            SerialTypeKind::I48 => 6,
+            SerialTypeKind::I64 => 8,
+            SerialTypeKind::F64 => 8,
+            SerialTypeKind::ConstInt0 => 0,
+            SerialTypeKind::ConstInt1 => 0,
+            SerialTypeKind::Text => (self.0 as usize - 13) / 2,
+            SerialTypeKind::Blob => (self.0 as usize - 12) / 2,
+        }
+    }
+}
+
+impl From<&Value> for SerialType {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => SerialType::null(),
+            Value::Integer(i) => match i {
+                0 => SerialType::const_int0(),
+                1 => SerialType::const_int1(),
+                i if *i >= I8_LOW && *i <= I8_HIGH => SerialType::i8(),
+                i if *i >= I16_LOW && *i <= I16_HIGH => SerialType::i16(),
+                i if *i >= I24_LOW && *i <= I24_HIGH => SerialType::i24(),
+                i if *i >= I32_LOW && *i <= I32_HIGH => SerialType::i32(),
+                i if *i >= I48_LOW && *i <= I48_HIGH => SerialType::i48(),
+                _ => SerialType::i64(),
+            },
+            Value::Float(_) => SerialType::f64(),
+            Value::Text(t) => SerialType::text(t.value.len() as u64),
+            Value::Blob(b) => SerialType::blob(b.len() as u64),
+        }
+    }
+}
+
+impl From<SerialType> for u64 {
+    fn from(serial_type: SerialType) -> Self {
+        serial_type.0
+    }
+}
+
+impl TryFrom<u64> for SerialType {
+    type Error = LimboError;
+
+    fn try_from(uint: u64) -> Result<Self> {
+        if uint == 10 || uint == 11 {
+            return Err(LimboError::Corrupt(format!(
+                "Invalid serial type: {}",
+                uint
+            )));
+        }
+        Ok(SerialType(uint))
+    }
+}
+
+impl Record {
+    pub fn new(values: Vec<Value>) -> Self {
+        Self {
+            values: values.into_iter().collect(),
+        }
+    }
+
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        if self.values.iter().all(|v| matches!(v, Value::Null)) {
+            Self::serialize_nulls(self.values.len(), buf);
+            return;
+        }
+        // The header size counts its own varint, so the serial types are gathered first. The
+        // record is then written front to back and the body never has to be shifted.
+        let (serial_types, content_len) = self.serial_types();
+        let mut varint_buf = [0u8; 9];
+        let size_len = write_header_size(serial_types.len(), &mut varint_buf);
+        buf.reserve(size_len + serial_types.len() + content_len);
+        buf.extend_from_slice(&varint_buf[..size_len]);
+        buf.extend_from_slice(&serial_types);
+        for value in &self.values {
+            value.serialize_serial(buf);
+        }
+    }
+
+    /// Serializes the record into the start of `dst`, such as the payload area of a btree
+    /// cell, and returns the number of bytes written. The output is the same as
+    /// [Record::serialize] gives. Nothing is written if `dst` is too small.
+    pub fn serialize_into(&self, dst: &mut [u8]) -> Result<usize> {
+        let (serial_types, content_len) = self.serial_types();
+        let mut varint_buf = [0u8; 9];
+        let size_len = write_header_size(serial_types.len(), &mut varint_buf);
+        let total = size_len + serial_types.len() + content_len;
+        if dst.len() < total {
+            return Err(LimboError::InternalError(format!(
+                "record needs {total} bytes but only {} are available",
+                dst.len()
+            )));
+        }
+        Ok(self.write_record(&serial_types, &mut &mut dst[..])?)
+    }
+
+    /// The serial type varints of the record header, and the number of content bytes they
+    /// describe.
+    fn serial_types(&self) -> (Vec<u8>, usize) {
+        let mut serial_types = Vec::with_capacity(self.values.len());
+        let mut content_len = 0;
+        for value in &self.values {
+            let serial_type = SerialType::from(value);
+            content_len += serial_type.size();
+            write_varint_to_vec(serial_type.into(), &mut serial_types);
+        }
+        (serial_types, content_len)
+    }
+
+    /// Serializes like [Record::serialize], but stores each float that is integral and fits an
+    /// i64 with an integer serial type, as SQLite does for REAL columns to save space. Such a
+    /// column reads back as an integer, so the reader must apply REAL affinity. `-0.0` keeps
+    /// its sign bit and NaN and infinities are left as floats.
+    pub fn serialize_compact(&self, buf: &mut Vec<u8>) {
+        self.map_values(|value| match value {
+            Value::Float(f)
+                if f.fract() == 0.0
+                    && !(*f == 0.0 && f.is_sign_negative())
+                    && *f >= i64::MIN as f64
+                    && *f < i64::MAX as f64 =>
+            {
+                Value::Integer(*f as i64)
+            }
+            value => value.clone(),
+        })
+        .serialize(buf)
+    }
+
+    /// Compares two records by a list of ORDER BY keys, each giving a column index, a
+    /// direction and the collation used when both sides are text. Later keys only break ties
+    /// left by earlier ones; NULLs sort first in ascending order.
+    pub fn order_key_cmp(
+        &self,
+        other: &Record,
+        keys: &[(usize, SortOrder, CollationSeq)],
+    ) -> std::cmp::Ordering {
+        for (idx, order, collation) in keys {
+            let (l, r) = (self.get_value(*idx), other.get_value(*idx));
+            let cmp = match (l, r) {
+                (Value::Text(left), Value::Text(right)) => {
+                    collation.compare_strings(left.as_str(), right.as_str())
+                }
+                _ => l.partial_cmp(r).unwrap_or(std::cmp::Ordering::Equal),
+            };
+            if !cmp.is_eq() {
+                return match order {
+                    SortOrder::Asc => cmp,
+                    SortOrder::Desc => cmp.reverse(),
+                };
+            }
+        }
+        std::cmp::Ordering::Equal
+    }
+
+    /// Decodes a record previously written by [Record::serialize].
+    pub fn deserialize(payload: &[u8]) -> Result<Self> {
+        let mut record = ImmutableRecord::new(payload.len(), 0);
+        read_record(payload, &mut record)?;
+        Ok(Self::new(
+            record.get_values().iter().map(|v| v.to_owned()).collect(),
+        ))
+    }
+
+    /// Appends a record made of `count` NULLs to `buf`. Such a record is just a header of zero
+    /// serial types with no content, so it is written in one go instead of value by value.
+    pub fn serialize_nulls(count: usize, buf: &mut Vec<u8>) {
+        let mut varint_buf = [0u8; 9];
+        let size_len = write_header_size(count, &mut varint_buf);
+        buf.reserve(size_len + count);
+        buf.extend_from_slice(&varint_buf[..size_len]);
+        buf.resize(buf.len() + count, 0);
+    }
+
+    /// Streams the serialized record into `w` and returns the number of bytes written.
+    ///
+    /// The header must precede the content but its size depends on every value, so it is
+    /// assembled in a small scratch buffer first. Value content is then written directly.
+    pub fn serialize_to<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<usize> {
+        let (serial_types, _) = self.serial_types();
+        self.write_record(&serial_types, w)
+    }
+
+    // Writes the header made of `serial_types`, as given by [Record::serial_types], and then
+    // the content of every value.
+    fn write_record<W: std::io::Write>(
+        &self,
+        serial_types: &[u8],
+        w: &mut W,
+    ) -> std::io::Result<usize> {
+        let mut varint_buf = [0u8; 9];
+        let size_len = write_header_size(serial_types.len(), &mut varint_buf);
+        w.write_all(&varint_buf[..size_len])?;
+        w.write_all(serial_types)?;
+        let mut written = size_len + serial_types.len();
+
+        let mut scratch = Vec::with_capacity(8);
+        for value in &self.values {
+            match value {
+                Value::Text(t) => {
+                    w.write_all(&t.value)?;
+                    written += t.value.len();
+                }
+                Value::Blob(b) => {
+                    w.write_all(b)?;
+                    written += b.len();
+                }
+                _ => {
+                    scratch.clear();
+                    value.serialize_serial(&mut scratch);
+                    w.write_all(&scratch)?;
+                    written += scratch.len();
+                }
+            }
+        }
+        Ok(written)
+    }
+}
+
+// Writes the size of a record header whose serial types take `serial_types_len` bytes into
+// `buf`. The size counts its own varint, so a longer varint may be needed to fit it.
+fn write_header_size(serial_types_len: usize, buf: &mut [u8; 9]) -> usize {
+    let mut size_len = 1;
+    loop {
+        let n = write_varint(buf, (serial_types_len + size_len) as u64);
+        if n == size_len {
+            return n;
+        }
+        size_len = n;
+    }
+}
+
+pub enum Cursor {
+    BTree(BTreeCursor),
+    Pseudo(PseudoCursor),
+    Sorter(Sorter),
+    Virtual(VirtualTableCursor),
+}
+
+impl Cursor {
+    pub fn new_btree(cursor: BTreeCursor) -> Self {
+        Self::BTree(cursor)
+    }
+
+    pub fn new_pseudo(cursor: PseudoCursor) -> Self {
+        Self::Pseudo(cursor)
+    }
+
+    pub fn new_sorter(cursor: Sorter) -> Self {
+        Self::Sorter(cursor)
+    }
+
+    pub fn as_btree_mut(&mut self) -> &mut BTreeCursor {
+        match self {
+            Self::BTree(cursor) => cursor,
+            _ => panic!("Cursor is not a btree"),
+        }
+    }
+
+    pub fn as_pseudo_mut(&mut self) -> &mut PseudoCursor {
+        match self {
+            Self::Pseudo(cursor) => cursor,
+            _ => panic!("Cursor is not a pseudo cursor"),
+        }
+    }
+
+    pub fn as_sorter_mut(&mut self) -> &mut Sorter {
+        match self {
+            Self::Sorter(cursor) => cursor,
+            _ => panic!("Cursor is not a sorter cursor"),
+        }
+    }
+
+    pub fn as_virtual_mut(&mut self) -> &mut VirtualTableCursor {
+        match self {
+            Self::Virtual(cursor) => cursor,
+            _ => panic!("Cursor is not a virtual cursor"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CursorResult<T> {
+    Ok(T),
+    IO,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+/// The match condition of a table/index seek.
+pub enum SeekOp {
+    EQ,
+    GE,
+    GT,
+    LE,
+    LT,
+}
+
+impl SeekOp {
+    /// A given seek op implies an iteration direction.
+    ///
+    /// For example, a seek with SeekOp::GT implies:
+    /// Find the first table/index key that compares greater than the seek key
+    /// -> used in forwards iteration.
+    ///
+    /// A seek with SeekOp::LE implies:
+    /// Find the last table/index key that compares less than or equal to the seek key
+    /// -> used in backwards iteration.
+    #[inline(always)]
+    pub fn iteration_direction(&self) -> IterationDirection {
+        match self {
+            SeekOp::EQ | SeekOp::GE | SeekOp::GT => IterationDirection::Forwards,
+            SeekOp::LE | SeekOp::LT => IterationDirection::Backwards,
+        }
+    }
+
+    pub fn reverse(&self) -> Self {
+        match self {
+            SeekOp::EQ => SeekOp::EQ,
+            SeekOp::GE => SeekOp::LE,
+            SeekOp::GT => SeekOp::LT,
+            SeekOp::LE => SeekOp::GE,
+            SeekOp::LT => SeekOp::GT,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SeekKey<'a> {
+    TableRowId(i64),
+    IndexKey(&'a ImmutableRecord),
+}
+
+impl RawSlice {
+    pub fn new(data: *const u8, len: usize) -> Self {
+        Self { data, len }
+    }
+
+    /// Borrows `value` without copying it.
+    ///
+    /// # Safety
+    ///
+    /// The returned slice does not track the borrow, so `value` must outlive it and every
+    /// [RefValue] built from it.
+    pub unsafe fn from_slice(value: &[u8]) -> Self {
+        Self::new(value.as_ptr(), value.len())
+    }
+
+    pub fn to_slice(&self) -> &[u8] {
+        if self.data.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(self.data, self.len) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_null() {
+        let record = Record::new(vec![Value::Null]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        let header_length = record.values.len() + 1;
+        let header = &buf[0..header_length];
+        // First byte should be header size
+        assert_eq!(header[0], header_length as u8);
+        // Second byte should be serial type for NULL
+        assert_eq!(header[1] as u64, u64::from(SerialType::null()));
+        // Check that the buffer is empty after the header
+        assert_eq!(buf.len(), header_length);
+    }
+
+    #[test]
+    fn test_serialize_integers() {
+        let record = Record::new(vec![
+            Value::Integer(0),                 // Should use ConstInt0
+            Value::Integer(1),                 // Should use ConstInt1
+            Value::Integer(42),                // Should use SERIAL_TYPE_I8
+            Value::Integer(1000),              // Should use SERIAL_TYPE_I16
+            Value::Integer(1_000_000),         // Should use SERIAL_TYPE_I24
+            Value::Integer(1_000_000_000),     // Should use SERIAL_TYPE_I32
+            Value::Integer(1_000_000_000_000), // Should use SERIAL_TYPE_I48
+            Value::Integer(i64::MAX),          // Should use SERIAL_TYPE_I64
+        ]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        let header_length = record.values.len() + 1;
+        let header = &buf[0..header_length];
+        // First byte should be header size
+        assert_eq!(header[0], header_length as u8); // Header should be larger than number of values
+
+        // Check that correct serial types were chosen
+        assert_eq!(header[1] as u64, u64::from(SerialType::const_int0())); // 8
+        assert_eq!(header[2] as u64, u64::from(SerialType::const_int1())); // 9
+        assert_eq!(header[3] as u64, u64::from(SerialType::i8())); // 1
+        assert_eq!(header[4] as u64, u64::from(SerialType::i16())); // 2
+        assert_eq!(header[5] as u64, u64::from(SerialType::i24())); // 3
+        assert_eq!(header[6] as u64, u64::from(SerialType::i32())); // 4
+        assert_eq!(header[7] as u64, u64::from(SerialType::i48())); // 5
+        assert_eq!(header[8] as u64, u64::from(SerialType::i64())); // 6
+
+        // test that the bytes after the header can be interpreted as the correct values
+        let mut cur_offset = header_length;
+
+        // Value::Integer(0) - ConstInt0: NO PAYLOAD BYTES
+        // Value::Integer(1) - ConstInt1: NO PAYLOAD BYTES
+
+        // Value::Integer(42) - I8: 1 byte
+        let i8_bytes = &buf[cur_offset..cur_offset + size_of::<i8>()];
+        cur_offset += size_of::<i8>();
+
+        // Value::Integer(1000) - I16: 2 bytes
+        let i16_bytes = &buf[cur_offset..cur_offset + size_of::<i16>()];
+        cur_offset += size_of::<i16>();
+
+        // Value::Integer(1_000_000) - I24: 3 bytes
+        let i24_bytes = &buf[cur_offset..cur_offset + 3];
+        cur_offset += 3;
+
+        // Value::Integer(1_000_000_000) - I32: 4 bytes
+        let i32_bytes = &buf[cur_offset..cur_offset + size_of::<i32>()];
+        cur_offset += size_of::<i32>();
+
+        // Value::Integer(1_000_000_000_000) - I48: 6 bytes
+        let i48_bytes = &buf[cur_offset..cur_offset + 6];
+        cur_offset += 6;
+
+        // Value::Integer(i64::MAX) - I64: 8 bytes
+        let i64_bytes = &buf[cur_offset..cur_offset + size_of::<i64>()];
+
+        // Verify the payload values
+        let val_int8 = i8::from_be_bytes(i8_bytes.try_into().unwrap());
+        let val_int16 = i16::from_be_bytes(i16_bytes.try_into().unwrap());
+
+        let mut i24_with_padding = vec![0];
+        i24_with_padding.extend(i24_bytes);
+        let val_int24 = i32::from_be_bytes(i24_with_padding.try_into().unwrap());
+
+        let val_int32 = i32::from_be_bytes(i32_bytes.try_into().unwrap());
+
+        let mut i48_with_padding = vec![0, 0];
+        i48_with_padding.extend(i48_bytes);
+        let val_int48 = i64::from_be_bytes(i48_with_padding.try_into().unwrap());
+
+        let val_int64 = i64::from_be_bytes(i64_bytes.try_into().unwrap());
+
+        assert_eq!(val_int8, 42);
+        assert_eq!(val_int16, 1000);
+        assert_eq!(val_int24, 1_000_000);
+        assert_eq!(val_int32, 1_000_000_000);
+        assert_eq!(val_int48, 1_000_000_000_000);
+        assert_eq!(val_int64, i64::MAX);
+
+        //Size of buffer = header + payload bytes
+        // ConstInt0 and ConstInt1 contribute 0 bytes to payload
+        assert_eq!(
+            buf.len(),
+            header_length  // 9 bytes (header size + 8 serial types)
+                + 0        // ConstInt0: 0 bytes
+                + 0        // ConstInt1: 0 bytes  
+                + size_of::<i8>()        // I8: 1 byte
+                + size_of::<i16>()        // I16: 2 bytes
+                + (size_of::<i32>() - 1)        // I24: 3 bytes
+                + size_of::<i32>()        // I32: 4 bytes
+                + (size_of::<i64>() - 2)        // I48: 6 bytes
+                + size_of::<i64>() // I64: 8 bytes
+        );
+    }
+
+    #[test]
+    fn test_serialize_const_integers() {
+        let record = Record::new(vec![Value::Integer(0), Value::Integer(1)]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        // [header_size, serial_type_0, serial_type_1] + no payload bytes
+        let expected_header_size = 3; // 1 byte for header size + 2 bytes for serial types
+
+        assert_eq!(buf.len(), expected_header_size);
+
+        // Check header size
+        assert_eq!(buf[0], expected_header_size as u8);
+
+        assert_eq!(buf[1] as u64, u64::from(SerialType::const_int0())); // Should be 8
+        assert_eq!(buf[2] as u64, u64::from(SerialType::const_int1())); // Should be 9
+
+        assert_eq!(buf[1], 8); // ConstInt0 serial type
+        assert_eq!(buf[2], 9); // ConstInt1 serial type
+    }
+
+    #[test]
+    fn test_serialize_single_const_int0() {
+        let record = Record::new(vec![Value::Integer(0)]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        // Expected: [header_size=2, serial_type=8]
+        assert_eq!(buf.len(), 2);
+        assert_eq!(buf[0], 2); // Header size
+        assert_eq!(buf[1], 8); // ConstInt0 serial type
+    }
+
+    #[test]
+    fn test_serialize_float() {
+        #[warn(clippy::approx_constant)]
+        let record = Record::new(vec![Value::Float(3.15555)]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        let header_length = record.values.len() + 1;
+        let header = &buf[0..header_length];
+        // First byte should be header size
+        assert_eq!(header[0], header_length as u8);
+        // Second byte should be serial type for FLOAT
+        assert_eq!(header[1] as u64, u64::from(SerialType::f64()));
+        // Check that the bytes after the header can be interpreted as the float
+        let float_bytes = &buf[header_length..header_length + size_of::<f64>()];
+        let float = f64::from_be_bytes(float_bytes.try_into().unwrap());
+        assert_eq!(float, 3.15555);
+        // Check that buffer length is correct
+        assert_eq!(buf.len(), header_length + size_of::<f64>());
+    }
+
+    #[test]
+    fn test_serialize_text() {
+        let text = "hello";
+        let record = Record::new(vec![Value::Text(Text::new(text))]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        let header_length = record.values.len() + 1;
+        let header = &buf[0..header_length];
+        // First byte should be header size
+        assert_eq!(header[0], header_length as u8);
+        // Second byte should be serial type for TEXT, which is (len * 2 + 13)
+        assert_eq!(header[1], (5 * 2 + 13) as u8);
+        // Check the actual text bytes
+        assert_eq!(&buf[2..7], b"hello");
+        // Check that buffer length is correct
+        assert_eq!(buf.len(), header_length + text.len());
+    }
+
+    #[test]
+    fn test_serialize_blob() {
+        let blob = vec![1, 2, 3, 4, 5];
+        let record = Record::new(vec![Value::Blob(blob.clone())]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        let header_length = record.values.len() + 1;
+        let header = &buf[0..header_length];
+        // First byte should be header size
+        assert_eq!(header[0], header_length as u8);
+        // Second byte should be serial type for BLOB, which is (len * 2 + 12)
+        assert_eq!(header[1], (5 * 2 + 12) as u8);
+        // Check the actual blob bytes
+        assert_eq!(&buf[2..7], &[1, 2, 3, 4, 5]);
+        // Check that buffer length is correct
+        assert_eq!(buf.len(), header_length + blob.len());
+    }
+
+    #[test]
+    fn test_serialize_mixed_types() {
+        let text = "test";
+        let record = Record::new(vec![
+            Value::Null,
+            Value::Integer(42),
+            Value::Float(3.15),
+            Value::Text(Text::new(text)),
+        ]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        let header_length = record.values.len() + 1;
+        let header = &buf[0..header_length];
+        // First byte should be header size
+        assert_eq!(header[0], header_length as u8);
+        // Second byte should be serial type for NULL
+        assert_eq!(header[1] as u64, u64::from(SerialType::null()));
+        // Third byte should be serial type for I8
+        assert_eq!(header[2] as u64, u64::from(SerialType::i8()));
+        // Fourth byte should be serial type for F64
+        assert_eq!(header[3] as u64, u64::from(SerialType::f64()));
+        // Fifth byte should be serial type for TEXT, which is (len * 2 + 13)
+        assert_eq!(header[4] as u64, (4 * 2 + 13) as u64);
+
+        // Check that the bytes after the header can be interpreted as the correct values
+        let mut cur_offset = header_length;
+        let i8_bytes = &buf[cur_offset..cur_offset + size_of::<i8>()];
+        cur_offset += size_of::<i8>();
+        let f64_bytes = &buf[cur_offset..cur_offset + size_of::<f64>()];
+        cur_offset += size_of::<f64>();
+        let text_bytes = &buf[cur_offset..cur_offset + text.len()];
+
+        let val_int8 = i8::from_be_bytes(i8_bytes.try_into().unwrap());
+        let val_float = f64::from_be_bytes(f64_bytes.try_into().unwrap());
+        let val_text = String::from_utf8(text_bytes.to_vec()).unwrap();
+
+        assert_eq!(val_int8, 42);
+        assert_eq!(val_float, 3.15);
+        assert_eq!(val_text, "test");
+
+        // Check that buffer length is correct
+        assert_eq!(
+            buf.len(),
+            header_length + size_of::<i8>() + size_of::<f64>() + text.len()
+        );
+    }
+
+    #[test]
+    fn test_case_conversion_ascii_only() {
+        let value = Value::build_text("Café");
+        assert_eq!(value.to_lower(), Value::build_text("café"));
+        assert_eq!(value.to_upper(), Value::build_text("CAFé"));
+
+        assert_eq!(Value::Integer(10).to_upper(), Value::Integer(10));
+        assert_eq!(Value::Null.to_lower(), Value::Null);
+    }
+
+    #[test]
+    fn test_substr() {
+        let hello = Value::build_text("hello");
+        assert_eq!(hello.substr(2, Some(3)), Value::build_text("ell"));
+        assert_eq!(hello.substr(-3, None), Value::build_text("llo"));
+        assert_eq!(hello.substr(-3, Some(2)), Value::build_text("ll"));
+        assert_eq!(hello.substr(0, Some(2)), Value::build_text("h"));
+        assert_eq!(hello.substr(3, Some(-2)), Value::build_text("he"));
+        assert_eq!(hello.substr(10, None), Value::build_text(""));
+
+        // Text is sliced by characters, not bytes.
+        assert_eq!(
+            Value::build_text("héllo").substr(2, Some(2)),
+            Value::build_text("él")
+        );
+
+        let blob = Value::Blob(vec![1, 2, 3, 4, 5]);
+        assert_eq!(blob.substr(2, Some(3)), Value::Blob(vec![2, 3, 4]));
+        assert_eq!(blob.substr(-2, None), Value::Blob(vec![4, 5]));
+
+        assert_eq!(Value::Null.substr(1, Some(1)), Value::Null);
+    }
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(
+            Value::build_text("  hi  ").trim(None),
+            Value::build_text("hi")
+        );
+        assert_eq!(
+            Value::build_text("xxhixx").trim(Some("x")),
+            Value::build_text("hi")
+        );
+        // The trim argument is a set of characters, not a substring.
+        assert_eq!(
+            Value::build_text("xyhiyx").trim(Some("yx")),
+            Value::build_text("hi")
+        );
+        // Only spaces are stripped by default.
+        assert_eq!(
+            Value::build_text("\thi ").trim(None),
+            Value::build_text("\thi")
+        );
+        assert_eq!(
+            Value::build_text("  hi  ").ltrim(None),
+            Value::build_text("hi  ")
+        );
+        assert_eq!(
+            Value::build_text("  hi  ").rtrim(None),
+            Value::build_text("  hi")
+        );
+        assert_eq!(Value::Integer(100).rtrim(Some("0")), Value::build_text("1"));
+        assert_eq!(Value::Null.trim(None), Value::Null);
+    }
+
+    #[test]
+    fn test_replace() {
+        let value = Value::build_text("aaa bbb aaa");
+        assert_eq!(value.replace("aaa", "c"), Value::build_text("c bbb c"));
+        assert_eq!(
+            Value::build_text("aaaa").replace("aa", "b"),
+            Value::build_text("bb")
+        );
+        assert_eq!(value.replace("", "c"), value);
+        assert_eq!(
+            Value::Integer(1001).replace("0", "1"),
+            Value::build_text("1111")
+        );
+        assert_eq!(Value::Null.replace("a", "b"), Value::Null);
+    }
+
+    #[test]
+    fn test_instr() {
+        let hello = Value::build_text("hello");
+        assert_eq!(hello.instr(&Value::build_text("ll")), Value::Integer(3));
+        assert_eq!(hello.instr(&Value::build_text("xyz")), Value::Integer(0));
+        // Positions are counted in characters for text.
+        assert_eq!(
+            Value::build_text("héllo").instr(&Value::build_text("l")),
+            Value::Integer(3)
+        );
+
+        let blob = Value::Blob(vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(
+            blob.instr(&Value::Blob(vec![0xbe, 0xef])),
+            Value::Integer(3)
+        );
+        assert_eq!(blob.instr(&Value::Blob(vec![0x00])), Value::Integer(0));
+
+        assert_eq!(hello.instr(&Value::Null), Value::Null);
+        assert_eq!(Value::Null.instr(&hello), Value::Null);
+    }
+
+    #[test]
+    fn test_math_helpers() {
+        assert_eq!(Value::Integer(-5).abs(), Value::Integer(5));
+        assert_eq!(Value::Float(-2.5).abs(), Value::Float(2.5));
+        assert_eq!(
+            Value::Integer(i64::MIN).abs(),
+            Value::Float(9223372036854775808.0)
+        );
+        assert_eq!(Value::build_text("-5").abs(), Value::Float(5.0));
+        assert_eq!(Value::Null.abs(), Value::Null);
+
+        assert_eq!(Value::Float(2.5).round(0), Value::Float(3.0));
+        assert_eq!(Value::Float(-2.5).round(0), Value::Float(-3.0));
+        assert_eq!(Value::Float(2.345).round(2), Value::Float(2.35));
+        assert_eq!(Value::Integer(5).round(0), Value::Float(5.0));
+        assert_eq!(Value::build_text("2.5").round(-1), Value::Float(3.0));
+        assert_eq!(Value::Null.round(2), Value::Null);
+
+        assert_eq!(Value::Float(2.1).ceil(), Value::Float(3.0));
+        assert_eq!(Value::Float(-2.1).floor(), Value::Float(-3.0));
+        assert_eq!(Value::Integer(3).ceil(), Value::Integer(3));
+        assert_eq!(Value::build_text("2.5").floor(), Value::Float(2.0));
+        assert_eq!(Value::build_text("abc").ceil(), Value::Null);
+    }
+
+    #[test]
+    fn test_serialize_to_writer() {
+        let record = Record::new(vec![
+            Value::Null,
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::Integer(-1000),
+            Value::Integer(i64::MIN),
+            Value::Float(3.15),
+            Value::build_text("hello"),
+            Value::Blob(vec![1, 2, 3]),
+        ]);
+        let mut expected = Vec::new();
+        record.serialize(&mut expected);
+
+        let mut streamed = Vec::new();
+        let written = record.serialize_to(&mut streamed).unwrap();
+        assert_eq!(written, streamed.len());
+        assert_eq!(streamed, expected);
+    }
+
+    #[test]
+    fn test_serialize_to_writer_large_header() {
+        // 200 serial types need a two byte header size varint.
+        let record = Record::new(vec![Value::Integer(42); 200]);
+        let mut buf = Vec::new();
+        let written = record.serialize_to(&mut buf).unwrap();
+        assert_eq!(written, 2 + 200 + 200);
+        assert_eq!(buf.len(), written);
+
+        let mut reuse = ImmutableRecord::new(0, 0);
+        crate::storage::sqlite3_ondisk::read_record(&buf, &mut reuse).unwrap();
+        assert_eq!(reuse.len(), 200);
+        assert!(reuse
+            .get_values()
+            .iter()
+            .all(|v| *v == RefValue::Integer(42)));
+    }
+
+    #[test]
+    fn test_serialize_all_nulls() {
+        let record = Record::new(vec![Value::Null; 50]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+
+        let mut generic = Vec::new();
+        record.serialize_to(&mut generic).unwrap();
+        assert_eq!(buf, generic);
+        assert_eq!(buf.len(), 51);
+        assert_eq!(buf[0], 51);
+        assert!(buf[1..].iter().all(|b| *b == 0));
+
+        // Appends after existing content like serialize does.
+        let mut buf = vec![0xff];
+        Record::serialize_nulls(200, &mut buf);
+        assert_eq!(&buf[1..3], &[0x81, 0x4a]); // header size 202
+        assert_eq!(buf.len(), 1 + 2 + 200);
+
+        let mut buf = Vec::new();
+        Record::new(vec![]).serialize(&mut buf);
+        assert_eq!(buf, vec![1]);
+    }
+
+    #[test]
+    fn test_heap_size() {
+        let inline = std::mem::size_of::<Value>();
+        assert_eq!(Value::Null.heap_size(), inline);
+        assert_eq!(Value::Integer(42).heap_size(), inline);
+        assert_eq!(Value::Float(4.2).heap_size(), inline);
+
+        let text = Value::build_text("a".repeat(100));
+        assert!(text.heap_size() >= inline + 100);
+        assert!(text.heap_size() < inline + 200);
+
+        let blob = Value::Blob(Vec::with_capacity(64));
+        assert_eq!(blob.heap_size(), inline + 64);
+    }
+
+    #[test]
+    fn test_ref_value_text_from_str() {
+        let value = RefValue::Text(unsafe { TextRef::new("hello") });
+        assert_eq!(<&str>::from_value(&value).unwrap(), "hello");
+        assert_eq!(String::from_value(&value).unwrap(), "hello");
+        assert_eq!(value.to_string(), "hello");
+        assert_eq!(value.to_owned(), Value::build_text("hello"));
+
+        let owned = String::from("slice of a string");
+        let value = RefValue::Text(unsafe { TextRef::new(&owned[9..]) });
+        assert_eq!(<&str>::from_value(&value).unwrap(), "a string");
+    }
+
+    #[test]
+    fn test_ref_value_blob_from_slice() {
+        let bytes = [0xde, 0xad, 0xbe, 0xef];
+        let value = RefValue::Blob(unsafe { RawSlice::from_slice(&bytes[..]) });
+        assert_eq!(<&[u8]>::from_value(&value).unwrap(), &bytes);
+        assert_eq!(Vec::<u8>::from_value(&value).unwrap(), bytes.to_vec());
+        assert_eq!(value.to_owned(), Value::Blob(bytes.to_vec()));
+        assert!(<&str>::from_value(&value).is_err());
+
+        let value = RefValue::Blob(unsafe { RawSlice::from_slice(&b"abc"[1..]) });
+        assert_eq!(value.to_string(), "bc");
+        assert!(<&[u8]>::from_value(&RefValue::Integer(1)).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let values = vec![
+            Value::Null,
+            Value::Integer(-42),
+            Value::Float(1.5),
+            Value::build_text("hello"),
+            Value::Blob(vec![0, 1, 0xff]),
+        ];
+        for value in &values {
+            let json = serde_json::to_string(value).unwrap();
+            let back: Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(&back, value, "{json}");
+        }
+        assert_eq!(serde_json::to_string(&Value::Null).unwrap(), r#""Null""#);
+        assert_eq!(
+            serde_json::to_string(&Value::build_text("hi")).unwrap(),
+            r#"{"Text":{"value":"hi","subtype":"Text"}}"#
+        );
+
+        let record = Record::new(values);
+        let json = serde_json::to_string(&record).unwrap();
+        let back: Record = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, record);
+    }
+
+    #[test]
+    fn test_from_rust_types() {
+        assert_eq!(Value::from(42), Value::Integer(42));
+        let v: Value = 1.5.into();
+        assert_eq!(v, Value::Float(1.5));
+        assert_eq!(Value::from(true), Value::Integer(1));
+        assert_eq!(Value::from(false), Value::Integer(0));
+        assert_eq!(Value::from("abc"), Value::build_text("abc"));
+        assert_eq!(Value::from(String::from("abc")), Value::build_text("abc"));
+        assert_eq!(Value::from(vec![1u8, 2]), Value::Blob(vec![1, 2]));
+        assert_eq!(Value::from(Some(7)), Value::Integer(7));
+        assert_eq!(Value::from(None::<i64>), Value::Null);
+        assert_eq!(Value::from(Some("x")), Value::build_text("x"));
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        assert_eq!(i64::try_from(Value::Integer(7)).unwrap(), 7);
+        assert_eq!(f64::try_from(&Value::Float(1.5)).unwrap(), 1.5);
+        assert_eq!(String::try_from(Value::build_text("abc")).unwrap(), "abc");
+        assert_eq!(String::try_from(&Value::build_text("abc")).unwrap(), "abc");
+        assert_eq!(
+            Vec::<u8>::try_from(Value::Blob(vec![1, 2])).unwrap(),
+            vec![1, 2]
+        );
+        assert_eq!(Vec::<u8>::try_from(&Value::Blob(vec![3])).unwrap(), vec![3]);
+
+        assert!(matches!(
+            i64::try_from(&Value::Float(1.0)),
+            Err(LimboError::ConversionError(_))
+        ));
+        assert!(f64::try_from(Value::Integer(1)).is_err());
+        assert!(String::try_from(Value::Blob(b"abc".to_vec())).is_err());
+        assert!(Vec::<u8>::try_from(&Value::build_text("abc")).is_err());
+        assert!(i64::try_from(Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_coalesce() {
+        assert_eq!(
+            Value::coalesce(&[Value::Null, Value::Null, Value::Integer(3)]),
+            Value::Integer(3)
+        );
+        assert_eq!(
+            Value::coalesce(&[Value::Null, Value::build_text("a"), Value::Integer(3)]),
+            Value::build_text("a")
+        );
+        assert_eq!(Value::coalesce(&[Value::Null, Value::Null]), Value::Null);
+        assert_eq!(Value::coalesce(&[]), Value::Null);
+
+        assert_eq!(Value::Null.ifnull(&Value::Float(1.5)), Value::Float(1.5));
+        assert_eq!(
+            Value::Integer(0).ifnull(&Value::Float(1.5)),
+            Value::Integer(0)
+        );
+        assert_eq!(Value::Null.ifnull(&Value::Null), Value::Null);
+    }
+
+    #[test]
+    fn test_nullif() {
+        assert_eq!(Value::Integer(1).nullif(&Value::Integer(1)), Value::Null);
+        assert_eq!(Value::Integer(1).nullif(&Value::Float(1.0)), Value::Null);
+        assert_eq!(Value::Float(2.0).nullif(&Value::Integer(2)), Value::Null);
+        assert_eq!(
+            Value::build_text("a").nullif(&Value::build_text("a")),
+            Value::Null
+        );
+
+        assert_eq!(
+            Value::Integer(1).nullif(&Value::Integer(2)),
+            Value::Integer(1)
+        );
+        assert_eq!(
+            Value::Integer(1).nullif(&Value::build_text("1")),
+            Value::Integer(1)
+        );
+        assert_eq!(Value::Integer(1).nullif(&Value::Null), Value::Integer(1));
+        assert_eq!(
+            Value::build_text("a").nullif(&Value::build_text("b")),
+            Value::build_text("a")
+        );
+    }
+
+    #[test]
+    fn test_scalar_min_max() {
+        let values = [Value::Integer(1), Value::Float(2.5), Value::Integer(2)];
+        assert_eq!(Value::scalar_max(&values), Value::Float(2.5));
+        assert_eq!(Value::scalar_min(&values), Value::Integer(1));
+
+        let values = [Value::Integer(1), Value::Null, Value::Integer(2)];
+        assert_eq!(Value::scalar_max(&values), Value::Null);
+        assert_eq!(Value::scalar_min(&values), Value::Null);
+        assert_eq!(Value::scalar_max(&[]), Value::Null);
+
+        let values = [
+            Value::Integer(1),
+            Value::build_text("a"),
+            Value::Blob(vec![0]),
+        ];
+        assert_eq!(Value::scalar_max(&values), Value::Blob(vec![0]));
+        assert_eq!(Value::scalar_min(&values), Value::Integer(1));
+
+        // Ties: max keeps the first, min keeps the last.
+        let values = [Value::Integer(2), Value::Float(2.0)];
+        assert!(matches!(Value::scalar_max(&values), Value::Integer(2)));
+        assert!(matches!(Value::scalar_min(&values), Value::Float(_)));
     }
 
-    pub fn add_value(&mut self, value: RefValue) {
-        assert!(self.recreating);
-        self.values.push(value);
+    #[test]
+    fn test_hex_unhex() {
+        let blob = Value::Blob(vec![0xde, 0xad]);
+        assert_eq!(blob.hex(), Value::build_text("DEAD"));
+        assert_eq!(blob.hex().unhex(), blob);
+        assert_eq!(
+            Value::Blob(vec![0x00, 0xef, 0xff]).hex(),
+            Value::build_text("00EFFF")
+        );
+        assert_eq!(Value::build_text("é").hex(), Value::build_text("C3A9"));
+        assert_eq!(Value::Float(1.5).hex(), Value::build_text("312E35"));
+        assert_eq!(Value::Null.hex(), Value::build_text(""));
+
+        assert_eq!(Value::build_text("dead").unhex(), blob);
+        assert_eq!(Value::build_text("abc").unhex(), Value::Null);
+        assert_eq!(Value::build_text("zz").unhex(), Value::Null);
+        assert_eq!(Value::Null.unhex(), Value::Null);
     }
 
-    pub fn invalidate(&mut self) {
-        self.payload.clear();
-        self.values.clear();
+    #[test]
+    fn test_quote() {
+        assert_eq!(Value::Null.quote(), Value::build_text("NULL"));
+        assert_eq!(Value::Integer(-5).quote(), Value::build_text("-5"));
+        assert_eq!(Value::Float(1.5).quote(), Value::build_text("1.5"));
+        assert_eq!(Value::Float(100.0).quote(), Value::build_text("100.0"));
+        assert_eq!(
+            Value::Float(f64::INFINITY).quote(),
+            Value::build_text("9.0e+999")
+        );
+        assert_eq!(
+            Value::build_text("it's 'quoted'").quote(),
+            Value::build_text("'it''s ''quoted'''")
+        );
+        assert_eq!(
+            Value::build_text("ab\0cd").quote(),
+            Value::build_text("'ab'")
+        );
+        assert_eq!(
+            Value::Blob(vec![0x00, 0xff]).quote(),
+            Value::build_text("X'00FF'")
+        );
+        assert_eq!(Value::Blob(vec![]).quote(), Value::build_text("X''"));
     }
 
-    pub fn get_payload(&self) -> &[u8] {
-        &self.payload
-    }
-}
+    #[test]
+    fn test_random() {
+        use rand::{rngs::StdRng, SeedableRng};
 
-impl Display for ImmutableRecord {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for value in &self.values {
-            match value {
-                RefValue::Null => write!(f, "NULL")?,
-                RefValue::Integer(i) => write!(f, "Integer({})", *i)?,
-                RefValue::Float(flo) => write!(f, "Float({})", *flo)?,
-                RefValue::Text(text_ref) => write!(f, "Text({})", text_ref.as_str())?,
-                RefValue::Blob(raw_slice) => {
-                    write!(f, "Blob({})", String::from_utf8_lossy(raw_slice.to_slice()))?
-                }
-            }
-            if value != self.values.last().unwrap() {
-                write!(f, ", ")?;
-            }
-        }
-        Ok(())
-    }
-}
+        let Value::Blob(blob) = Value::randomblob(8) else {
+            panic!("expected a blob");
+        };
+        assert_eq!(blob.len(), 8);
+        assert!(matches!(Value::randomblob(0), Value::Blob(b) if b.len() == 1));
+        assert!(matches!(Value::random(), Value::Integer(_)));
 
-impl Clone for ImmutableRecord {
-    fn clone(&self) -> Self {
-        let mut new_values = Vec::new();
-        let new_payload = self.payload.clone();
-        for value in &self.values {
-            let value = match value {
-                RefValue::Null => RefValue::Null,
-                RefValue::Integer(i) => RefValue::Integer(*i),
-                RefValue::Float(f) => RefValue::Float(*f),
-                RefValue::Text(text_ref) => {
-                    // let's update pointer
-                    let ptr_start = self.payload.as_ptr() as usize;
-                    let ptr_end = text_ref.value.data as usize;
-                    let len = ptr_end - ptr_start;
-                    let new_ptr = unsafe { new_payload.as_ptr().add(len) };
-                    RefValue::Text(TextRef {
-                        value: RawSlice::new(new_ptr, text_ref.value.len),
-                        subtype: text_ref.subtype.clone(),
-                    })
-                }
-                RefValue::Blob(raw_slice) => {
-                    let ptr_start = self.payload.as_ptr() as usize;
-                    let ptr_end = raw_slice.data as usize;
-                    let len = ptr_end - ptr_start;
-                    let new_ptr = unsafe { new_payload.as_ptr().add(len) };
-                    RefValue::Blob(RawSlice::new(new_ptr, raw_slice.len))
-                }
-            };
-            new_values.push(value);
-        }
-        Self {
-            payload: new_payload,
-            values: new_values,
-            recreating: self.recreating,
-        }
+        let mut a = StdRng::seed_from_u64(42);
+        let mut b = StdRng::seed_from_u64(42);
+        assert_eq!(Value::random_with(&mut a), Value::random_with(&mut b));
+        assert_eq!(
+            Value::randomblob_with(16, &mut a),
+            Value::randomblob_with(16, &mut b)
+        );
     }
-}
 
-impl RefValue {
-    pub fn to_ffi(&self) -> ExtValue {
-        match self {
-            Self::Null => ExtValue::null(),
-            Self::Integer(i) => ExtValue::from_integer(*i),
-            Self::Float(fl) => ExtValue::from_float(*fl),
-            Self::Text(text) => ExtValue::from_text(
-                std::str::from_utf8(text.value.to_slice())
-                    .unwrap()
-                    .to_string(),
-            ),
-            Self::Blob(blob) => ExtValue::from_blob(blob.to_slice().to_vec()),
-        }
-    }
+    #[test]
+    fn test_checked_div() {
+        assert_eq!(
+            Value::Integer(7).checked_div(&Value::Integer(2)).unwrap(),
+            Value::Integer(3)
+        );
+        assert_eq!(
+            Value::Integer(3).checked_div(&Value::Float(2.0)).unwrap(),
+            Value::Float(1.5)
+        );
+        assert_eq!(
+            Value::Integer(i64::MIN)
+                .checked_div(&Value::Integer(-1))
+                .unwrap(),
+            Value::Float(-(i64::MIN as f64))
+        );
+        assert_eq!(
+            Value::Null.checked_div(&Value::Integer(0)).unwrap(),
+            Value::Null
+        );
 
-    pub fn to_owned(&self) -> Value {
-        match self {
-            RefValue::Null => Value::Null,
-            RefValue::Integer(i) => Value::Integer(*i),
-            RefValue::Float(f) => Value::Float(*f),
-            RefValue::Text(text_ref) => Value::Text(Text {
-                value: text_ref.value.to_slice().to_vec(),
-                subtype: text_ref.subtype.clone(),
-            }),
-            RefValue::Blob(b) => Value::Blob(b.to_slice().to_vec()),
-        }
-    }
-    pub fn to_blob(&self) -> Option<&[u8]> {
-        match self {
-            Self::Blob(blob) => Some(blob.to_slice()),
-            _ => None,
-        }
+        assert!(matches!(
+            Value::Integer(1).checked_div(&Value::Integer(0)),
+            Err(LimboError::DivisionByZero)
+        ));
+        assert!(matches!(
+            Value::Float(1.0).checked_div(&Value::Float(0.0)),
+            Err(LimboError::DivisionByZero)
+        ));
+        assert!(matches!(
+            Value::Float(1.0).checked_div(&Value::Integer(0)),
+            Err(LimboError::DivisionByZero)
+        ));
+        assert!(matches!(
+            Value::build_text("4").checked_div(&Value::Integer(2)),
+            Err(LimboError::ConversionError(_))
+        ));
     }
-}
 
-impl Display for RefValue {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Null => write!(f, "NULL"),
-            Self::Integer(i) => write!(f, "{}", i),
-            Self::Float(fl) => write!(f, "{:?}", fl),
-            Self::Text(s) => write!(f, "{}", s.as_str()),
-            Self::Blob(b) => write!(f, "{}", String::from_utf8_lossy(b.to_slice())),
-        }
-    }
-}
-impl Eq for RefValue {}
+    #[test]
+    fn test_compare_with_affinity() {
+        use std::cmp::Ordering;
 
-impl Ord for RefValue {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
-    }
-}
+        let ten = Value::build_text("10");
+        let nine = Value::build_text("9");
+        assert_eq!(
+            ten.compare_with_affinity(&nine, Affinity::Text),
+            Ordering::Less
+        );
+        assert_eq!(
+            ten.compare_with_affinity(&nine, Affinity::Numeric),
+            Ordering::Greater
+        );
+        assert_eq!(
+            ten.compare_with_affinity(&nine, Affinity::Integer),
+            Ordering::Greater
+        );
+        assert_eq!(
+            ten.compare_with_affinity(&nine, Affinity::Blob),
+            Ordering::Less
+        );
 
-#[allow(clippy::non_canonical_partial_ord_impl)]
-impl PartialOrd<RefValue> for RefValue {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (Self::Integer(int_left), Self::Integer(int_right)) => int_left.partial_cmp(int_right),
-            (Self::Integer(int_left), Self::Float(float_right)) => {
-                (*int_left as f64).partial_cmp(float_right)
-            }
-            (Self::Float(float_left), Self::Integer(int_right)) => {
-                float_left.partial_cmp(&(*int_right as f64))
-            }
-            (Self::Float(float_left), Self::Float(float_right)) => {
-                float_left.partial_cmp(float_right)
-            }
-            // Numeric vs Text/Blob
-            (Self::Integer(_) | Self::Float(_), Self::Text(_) | Self::Blob(_)) => {
-                Some(std::cmp::Ordering::Less)
-            }
-            (Self::Text(_) | Self::Blob(_), Self::Integer(_) | Self::Float(_)) => {
-                Some(std::cmp::Ordering::Greater)
-            }
+        // Numbers become text under TEXT affinity and text becomes numeric otherwise.
+        assert_eq!(
+            Value::Integer(10).compare_with_affinity(&nine, Affinity::Text),
+            Ordering::Less
+        );
+        assert_eq!(
+            Value::Integer(10).compare_with_affinity(&ten, Affinity::Real),
+            Ordering::Equal
+        );
+        assert_eq!(
+            Value::Float(2.5).compare_with_affinity(&Value::build_text("2.5"), Affinity::Numeric),
+            Ordering::Equal
+        );
+        // Text that isn't a well-formed number stays text.
+        assert_eq!(
+            Value::Integer(10).compare_with_affinity(&Value::build_text("9x"), Affinity::Numeric),
+            Ordering::Less
+        );
+    }
 
-            (Self::Text(text_left), Self::Text(text_right)) => text_left
-                .value
-                .to_slice()
-                .partial_cmp(text_right.value.to_slice()),
-            // Text vs Blob
-            (Self::Text(_), Self::Blob(_)) => Some(std::cmp::Ordering::Less),
-            (Self::Blob(_), Self::Text(_)) => Some(std::cmp::Ordering::Greater),
+    #[test]
+    fn test_glob_match() {
+        let t = |s: &str| Value::build_text(s);
+        assert_eq!(t("foo.txt").glob_match(&t("*.txt")), Value::Integer(1));
+        assert_eq!(t("foo.TXT").glob_match(&t("*.txt")), Value::Integer(0));
+        assert_eq!(t("cat").glob_match(&t("c?t")), Value::Integer(1));
+        assert_eq!(t("ct").glob_match(&t("c?t")), Value::Integer(0));
+        assert_eq!(t("bat").glob_match(&t("[a-c]at")), Value::Integer(1));
+        assert_eq!(t("rat").glob_match(&t("[a-c]at")), Value::Integer(0));
+        assert_eq!(t("rat").glob_match(&t("[^a-c]at")), Value::Integer(1));
+        assert_eq!(Value::Integer(123).glob_match(&t("1*")), Value::Integer(1));
+        assert_eq!(Value::Null.glob_match(&t("*")), Value::Null);
+        assert_eq!(t("a").glob_match(&Value::Null), Value::Null);
+    }
 
-            (Self::Blob(blob_left), Self::Blob(blob_right)) => {
-                blob_left.to_slice().partial_cmp(blob_right.to_slice())
-            }
-            (Self::Null, Self::Null) => Some(std::cmp::Ordering::Equal),
-            (Self::Null, _) => Some(std::cmp::Ordering::Less),
-            (_, Self::Null) => Some(std::cmp::Ordering::Greater),
-        }
+    #[test]
+    fn test_like_match() {
+        let t = |s: &str| Value::build_text(s);
+        assert_eq!(t("Hello").like_match(&t("h%"), None), Value::Integer(1));
+        assert_eq!(t("Hello").like_match(&t("%LL%"), None), Value::Integer(1));
+        assert_eq!(t("Hello").like_match(&t("h_llo"), None), Value::Integer(1));
+        assert_eq!(t("Hello").like_match(&t("h_lo"), None), Value::Integer(0));
+        assert_eq!(
+            t("a_c").like_match(&t(r"a\_c"), Some('\\')),
+            Value::Integer(1)
+        );
+        assert_eq!(
+            t("abc").like_match(&t(r"a\_c"), Some('\\')),
+            Value::Integer(0)
+        );
+        assert_eq!(
+            t("50%").like_match(&t("50!%"), Some('!')),
+            Value::Integer(1)
+        );
+        assert_eq!(
+            Value::Integer(123).like_match(&t("1%"), None),
+            Value::Integer(1)
+        );
+        assert_eq!(t("É").like_match(&t("é"), None), Value::Integer(0));
+        assert_eq!(Value::Null.like_match(&t("%"), None), Value::Null);
+        assert_eq!(t("a").like_match(&Value::Null, None), Value::Null);
     }
-}
 
-/// A bitfield that represents the comparison spec for index keys.
-/// Since indexed columns can individually specify ASC/DESC, each key must
-/// be compared differently.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-#[repr(transparent)]
-pub struct IndexKeySortOrder(u64);
+    #[test]
+    fn test_char_and_unicode() {
+        assert_eq!(
+            Value::char_from_codepoints(&[Value::Integer(104), Value::Integer(105)]),
+            Value::build_text("hi")
+        );
+        assert_eq!(
+            Value::char_from_codepoints(&[Value::Integer(8364)]),
+            Value::build_text("€")
+        );
+        assert_eq!(
+            Value::char_from_codepoints(&[Value::Integer(-1), Value::Integer(0x110000)]),
+            Value::build_text("\u{fffd}\u{fffd}")
+        );
+        assert_eq!(
+            Value::char_from_codepoints(&[Value::Integer(104), Value::Null, Value::Integer(105)]),
+            Value::build_text("h\0i")
+        );
+        assert_eq!(
+            Value::char_from_codepoints(&[
+                Value::Float(65.7),
+                Value::build_text("66"),
+                Value::build_text("67abc"),
+                Value::Blob(b"68".to_vec()),
+                Value::build_text("abc"),
+            ]),
+            Value::build_text("ABCD\0")
+        );
 
-impl IndexKeySortOrder {
-    pub fn get_sort_order_for_col(&self, column_idx: usize) -> SortOrder {
-        assert!(column_idx < 64, "column index out of range: {}", column_idx);
-        match self.0 & (1 << column_idx) {
-            0 => SortOrder::Asc,
-            _ => SortOrder::Desc,
-        }
+        assert_eq!(Value::build_text("A").unicode(), Value::Integer(65));
+        assert_eq!(Value::build_text("€uro").unicode(), Value::Integer(8364));
+        assert_eq!(Value::Integer(12).unicode(), Value::Integer(49));
+        assert_eq!(Value::build_text("").unicode(), Value::Null);
+        assert_eq!(Value::Null.unicode(), Value::Null);
     }
 
-    pub fn from_index(index: &Index) -> Self {
-        let mut spec = 0;
-        for (i, column) in index.columns.iter().enumerate() {
-            spec |= ((column.order == SortOrder::Desc) as u64) << i;
-        }
-        IndexKeySortOrder(spec)
+    #[test]
+    fn test_order_key_cmp() {
+        let row = |n: i64, s: &str| Record::new(vec![Value::Integer(n), Value::build_text(s)]);
+        let mut rows = vec![
+            row(1, "b"),
+            row(2, "B"),
+            row(2, "a"),
+            row(1, "A"),
+            row(2, "c"),
+        ];
+        let keys = [
+            (0, SortOrder::Desc, CollationSeq::Binary),
+            (1, SortOrder::Asc, CollationSeq::NoCase),
+        ];
+        rows.sort_by(|a, b| a.order_key_cmp(b, &keys));
+        assert_eq!(
+            rows,
+            vec![
+                row(2, "a"),
+                row(2, "B"),
+                row(2, "c"),
+                row(1, "A"),
+                row(1, "b"),
+            ]
+        );
+
+        // With BINARY collation upper case sorts before lower case.
+        let keys = [(1, SortOrder::Asc, CollationSeq::Binary)];
+        assert!(row(0, "B").order_key_cmp(&row(0, "a"), &keys).is_lt());
+        let null_row = Record::new(vec![Value::Null, Value::Null]);
+        let keys = [(0, SortOrder::Asc, CollationSeq::Binary)];
+        assert!(null_row.order_key_cmp(&row(0, "a"), &keys).is_lt());
     }
 
-    pub fn from_list(order: &[SortOrder]) -> Self {
-        let mut spec = 0;
-        for (i, order) in order.iter().enumerate() {
-            spec |= ((*order == SortOrder::Desc) as u64) << i;
-        }
-        IndexKeySortOrder(spec)
+    #[test]
+    fn test_deserialize_round_trip() {
+        let record = Record::new(vec![
+            Value::Null,
+            Value::Integer(0),
+            Value::Integer(1),
+            Value::Integer(-300),
+            Value::Integer(i64::MAX),
+            Value::Float(-2.5),
+            Value::build_text("hello"),
+            Value::Blob(vec![1, 2, 3]),
+        ]);
+        let mut buf = Vec::new();
+        record.serialize(&mut buf);
+        assert_eq!(Record::deserialize(&buf).unwrap(), record);
     }
 
-    pub fn default() -> Self {
-        Self(0)
+    #[test]
+    fn test_normalize_numeric() {
+        assert!(matches!(
+            Value::Float(5.0).normalize_numeric(),
+            Value::Integer(5)
+        ));
+        assert!(matches!(
+            Value::Float(-0.0).normalize_numeric(),
+            Value::Integer(0)
+        ));
+        assert!(matches!(
+            Value::Float(i64::MIN as f64).normalize_numeric(),
+            Value::Integer(i64::MIN)
+        ));
+        assert!(matches!(
+            Value::Float(5.5).normalize_numeric(),
+            Value::Float(_)
+        ));
+        assert!(matches!(
+            Value::Float(1e19).normalize_numeric(),
+            Value::Float(_)
+        ));
+        assert!(matches!(
+            Value::Float(f64::NAN).normalize_numeric(),
+            Value::Float(_)
+        ));
+        assert!(matches!(
+            Value::Float(f64::INFINITY).normalize_numeric(),
+            Value::Float(_)
+        ));
+        assert_eq!(Value::Integer(7).normalize_numeric(), Value::Integer(7));
+        assert_eq!(
+            Value::build_text("5.0").normalize_numeric(),
+            Value::build_text("5.0")
+        );
     }
-}
 
-impl Default for IndexKeySortOrder {
-    fn default() -> Self {
-        Self::default()
+    #[test]
+    fn test_add_integer_overflow_promotes_to_float() {
+        let Value::Float(f) = Value::Integer(i64::MAX) + Value::Integer(1) else {
+            panic!("expected a float");
+        };
+        assert!((f - 9.22e18).abs() < 1e16);
+        assert!(matches!(
+            Value::Integer(i64::MIN) + Value::Integer(-1),
+            Value::Float(_)
+        ));
+        assert_eq!(
+            Value::Integer(i64::MAX - 1) + Value::Integer(1),
+            Value::Integer(i64::MAX)
+        );
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-/// Metadata about an index, used for handling and comparing index keys.
-///
-/// This struct provides information about the sorting order of columns,
-/// whether the index includes a row ID, and the total number of columns
-/// in the index.
-pub struct IndexKeyInfo {
-    /// Specifies the sorting order (ascending or descending) for each column in the index.
-    pub sort_order: IndexKeySortOrder,
-    /// Indicates whether the index includes a row ID column.
-    pub has_rowid: bool,
-    /// The total number of columns in the index, including the row ID column if present.
-    pub num_cols: usize,
-}
-
-impl IndexKeyInfo {
-    pub fn new_from_index(index: &Index) -> Self {
-        Self {
-            sort_order: IndexKeySortOrder::from_index(index),
-            has_rowid: index.has_rowid,
-            num_cols: index.columns.len() + (index.has_rowid as usize),
+    #[test]
+    fn test_arith_add_vs_accumulating_add() {
+        assert_eq!(Value::Integer(1).arith_add(&Value::Null), Value::Null);
+        assert_eq!(Value::Null.arith_add(&Value::Float(1.5)), Value::Null);
+        assert_eq!(
+            Value::Integer(1).arith_add(&Value::Float(1.5)),
+            Value::Float(2.5)
+        );
+        assert_eq!(
+            Value::build_text("2").arith_add(&Value::Integer(3)),
+            Value::Integer(5)
+        );
+        assert!(matches!(
+            Value::Integer(i64::MAX).arith_add(&Value::Integer(1)),
+            Value::Float(_)
+        ));
+
+        // The operator impl skips NULLs so aggregates can accumulate over them.
+        assert_eq!(Value::Integer(1) + Value::Null, Value::Integer(1));
+        assert_eq!(Value::Null + Value::Float(1.5), Value::Float(1.5));
+        let mut acc = Value::Null;
+        for v in [Value::Integer(2), Value::Null, Value::Integer(3)] {
+            acc += v;
         }
+        assert_eq!(acc, Value::Integer(5));
     }
-}
 
-pub fn compare_immutable(
-    l: &[RefValue],
-    r: &[RefValue],
-    index_key_sort_order: IndexKeySortOrder,
-    collations: &[CollationSeq],
-) -> std::cmp::Ordering {
-    assert_eq!(l.len(), r.len());
-    for (i, (l, r)) in l.iter().zip(r).enumerate() {
-        let column_order = index_key_sort_order.get_sort_order_for_col(i);
-        let collation = collations.get(i).copied().unwrap_or_default();
-        let cmp = match (l, r) {
-            (RefValue::Text(left), RefValue::Text(right)) => {
-                collation.compare_strings(left.as_str(), right.as_str())
-            }
-            _ => l.partial_cmp(r).unwrap(),
-        };
-        if !cmp.is_eq() {
-            return match column_order {
-                SortOrder::Asc => cmp,
-                SortOrder::Desc => cmp.reverse(),
-            };
+    #[test]
+    fn test_deserialize_sign_extends_i24_i48() {
+        let cases = [
+            (-1, SerialTypeKind::I8),
+            (I24_LOW, SerialTypeKind::I24),
+            (I24_HIGH, SerialTypeKind::I24),
+            (-32769, SerialTypeKind::I24),
+            (I48_LOW, SerialTypeKind::I48),
+            (I48_HIGH, SerialTypeKind::I48),
+            (I32_LOW - 1, SerialTypeKind::I48),
+        ];
+        for (i, kind) in cases {
+            let value = Value::Integer(i);
+            assert_eq!(SerialType::from(&value).kind(), kind, "{i}");
+            let record = Record::new(vec![value]);
+            let mut buf = Vec::new();
+            record.serialize(&mut buf);
+            assert_eq!(Record::deserialize(&buf).unwrap(), record, "{i}");
         }
     }
-    std::cmp::Ordering::Equal
-}
 
-const I8_LOW: i64 = -128;
-const I8_HIGH: i64 = 127;
-const I16_LOW: i64 = -32768;
-const I16_HIGH: i64 = 32767;
-const I24_LOW: i64 = -8388608;
-const I24_HIGH: i64 = 8388607;
-const I32_LOW: i64 = -2147483648;
-const I32_HIGH: i64 = 2147483647;
-const I48_LOW: i64 = -140737488355328;
-const I48_HIGH: i64 = 140737488355327;
+    #[test]
+    fn test_blob_concat() {
+        let joined =
+            Value::Blob(vec![0xde, 0xad]).blob_concat(&Value::Blob(vec![0xbe, 0xef, 0x00]));
+        assert_eq!(joined, Value::Blob(vec![0xde, 0xad, 0xbe, 0xef, 0x00]));
+        assert_eq!(joined.to_blob().unwrap().len(), 5);
 
-/// Sqlite Serial Types
-/// https://www.sqlite.org/fileformat.html#record_format
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-#[repr(transparent)]
-pub struct SerialType(u64);
+        assert_eq!(
+            Value::Blob(vec![]).blob_concat(&Value::Blob(vec![])),
+            Value::Blob(vec![])
+        );
+        assert_eq!(
+            Value::Blob(vec![1]).blob_concat(&Value::build_text("a")),
+            Value::Blob(vec![1, b'a'])
+        );
+        assert_eq!(
+            Value::Integer(12).blob_concat(&Value::Blob(vec![0])),
+            Value::Blob(vec![b'1', b'2', 0])
+        );
+        assert_eq!(Value::Blob(vec![1]).blob_concat(&Value::Null), Value::Null);
+        assert_eq!(Value::Null.blob_concat(&Value::Blob(vec![1])), Value::Null);
+    }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum SerialTypeKind {
-    Null,
-    I8,
-    I16,
-    I24,
-    I32,
-    I48,
-    I64,
-    F64,
-    ConstInt0,
-    ConstInt1,
-    Text,
-    Blob,
-}
+    #[test]
+    fn test_record_storage_serializes_identically() {
+        let values = vec![
+            Value::Integer(1),
+            Value::build_text("a"),
+            Value::Null,
+            Value::Float(0.5),
+        ];
+        let from_vec = Record::new(values.clone());
+        let from_iter: Record = values.into_iter().collect();
+        assert_eq!(from_vec, from_iter);
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        from_vec.serialize(&mut a);
+        from_iter.serialize(&mut b);
+        assert_eq!(a, b);
+        assert_eq!(a, [5, 9, 15, 0, 7, b'a', 0x3f, 0xe0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(Record::deserialize(&a).unwrap(), from_vec);
+    }
 
-impl SerialType {
-    #[inline(always)]
-    pub fn u64_is_valid_serial_type(n: u64) -> bool {
-        n != 10 && n != 11
+    #[test]
+    fn test_values_cross_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Value>();
+        assert_send_sync::<Text>();
+        assert_send_sync::<Record>();
+
+        let record = Record::new(vec![Value::build_text("shared"), Value::Blob(vec![1, 2])]);
+        let handle = std::thread::spawn(move || record.get_value(0).to_string());
+        assert_eq!(handle.join().unwrap(), "shared");
     }
 
-    const NULL: Self = Self(0);
-    const I8: Self = Self(1);
-    const I16: Self = Self(2);
-    const I24: Self = Self(3);
-    const I32: Self = Self(4);
-    const I48: Self = Self(5);
-    const I64: Self = Self(6);
-    const F64: Self = Self(7);
-    const CONST_INT0: Self = Self(8);
-    const CONST_INT1: Self = Self(9);
+    #[test]
+    fn test_borrowed_views() {
+        let text = Value::build_text("hello");
+        let Value::Text(inner) = &text else {
+            unreachable!()
+        };
+        assert_eq!(text.to_text(), Some("hello"));
+        assert_eq!(text.to_text().unwrap().as_ptr(), inner.value.as_ptr());
+        assert_eq!(text.as_bytes().unwrap().as_ptr(), inner.value.as_ptr());
 
-    pub fn null() -> Self {
-        Self::NULL
-    }
+        let blob = Value::Blob(vec![1, 2, 3]);
+        let Value::Blob(inner) = &blob else {
+            unreachable!()
+        };
+        assert_eq!(blob.as_bytes(), Some(&[1u8, 2, 3][..]));
+        assert_eq!(blob.as_bytes().unwrap().as_ptr(), inner.as_ptr());
+        assert_eq!(blob.to_text(), None);
 
-    pub fn i8() -> Self {
-        Self::I8
+        assert_eq!(Value::Integer(1).as_bytes(), None);
+        assert_eq!(Value::Null.as_bytes(), None);
     }
 
-    pub fn i16() -> Self {
-        Self::I16
-    }
+    #[test]
+    fn test_serial_type_u64_round_trip() {
+        let fixed = [
+            (0, SerialTypeKind::Null, 0),
+            (1, SerialTypeKind::I8, 1),
+            (2, SerialTypeKind::I16, 2),
+            (3, SerialTypeKind::I24, 3),
+            (4, SerialTypeKind::I32, 4),
+            (5, SerialTypeKind::I48, 6),
+            (6, SerialTypeKind::I64, 8),
+            (7, SerialTypeKind::F64, 8),
+            (8, SerialTypeKind::ConstInt0, 0),
+            (9, SerialTypeKind::ConstInt1, 0),
+        ];
+        for (n, kind, size) in fixed {
+            let serial_type = SerialType::try_from(n).unwrap();
+            assert_eq!(serial_type.kind(), kind);
+            assert_eq!(serial_type.size(), size);
+            assert_eq!(u64::from(serial_type), n);
+        }
 
-    pub fn i24() -> Self {
-        Self::I24
-    }
+        for len in [0u64, 1, 2, 100, 1_000_000] {
+            let blob = SerialType::try_from(12 + len * 2).unwrap();
+            assert_eq!(blob, SerialType::blob(len));
+            assert_eq!(blob.kind(), SerialTypeKind::Blob);
+            assert_eq!(blob.size(), len as usize);
+            assert_eq!(u64::from(blob), 12 + len * 2);
+
+            let text = SerialType::try_from(13 + len * 2).unwrap();
+            assert_eq!(text, SerialType::text(len));
+            assert_eq!(text.kind(), SerialTypeKind::Text);
+            assert_eq!(text.size(), len as usize);
+            assert_eq!(u64::from(text), 13 + len * 2);
+        }
 
-    pub fn i32() -> Self {
-        Self::I32
+        assert!(SerialType::try_from(10).is_err());
+        assert!(SerialType::try_from(11).is_err());
     }
 
-    pub fn i48() -> Self {
-        Self::I48
+    #[test]
+    fn test_serial_content_size() {
+        assert_eq!(Value::Null.serial_content_size(), 0);
+        assert_eq!(Value::Integer(0).serial_content_size(), 0);
+        assert_eq!(Value::Integer(1).serial_content_size(), 0);
+        assert_eq!(Value::Integer(2).serial_content_size(), 1);
+        assert_eq!(Value::Integer(127).serial_content_size(), 1);
+        assert_eq!(Value::Integer(128).serial_content_size(), 2);
+        assert_eq!(Value::Integer(-128).serial_content_size(), 1);
+        assert_eq!(Value::Integer(-129).serial_content_size(), 2);
+        assert_eq!(Value::Integer(32767).serial_content_size(), 2);
+        assert_eq!(Value::Integer(32768).serial_content_size(), 3);
+        assert_eq!(Value::Integer(8_388_608).serial_content_size(), 4);
+        assert_eq!(Value::Integer(2_147_483_648).serial_content_size(), 6);
+        assert_eq!(Value::Integer(i64::MAX).serial_content_size(), 8);
+        assert_eq!(Value::Float(1.5).serial_content_size(), 8);
+        assert_eq!(Value::build_text("").serial_content_size(), 0);
+        assert_eq!(Value::build_text("hello").serial_content_size(), 5);
+        assert_eq!(Value::Blob(vec![1, 2, 3]).serial_content_size(), 3);
     }
 
-    pub fn i64() -> Self {
-        Self::I64
-    }
+    #[test]
+    fn test_defaults() {
+        assert_eq!(Value::default(), Value::Null);
+        assert!(Record::default().get_values().is_empty());
 
-    pub fn f64() -> Self {
-        Self::F64
+        let mut value = Value::Integer(7);
+        assert_eq!(std::mem::take(&mut value), Value::Integer(7));
+        assert_eq!(value, Value::Null);
     }
 
-    pub fn const_int0() -> Self {
-        Self::CONST_INT0
-    }
+    #[test]
+    fn test_eq_agrees_with_cmp() {
+        assert_eq!(Value::Integer(1), Value::Float(1.0));
+        assert_eq!(Value::Float(-3.0), Value::Integer(-3));
+        assert_ne!(Value::Integer(1), Value::Float(1.5));
+        assert_ne!(Value::Integer(1), Value::build_text("1"));
 
-    pub fn const_int1() -> Self {
-        Self::CONST_INT1
+        let values = [
+            Value::Null,
+            Value::Integer(1),
+            Value::Float(1.0),
+            Value::Float(2.5),
+            Value::build_text("1"),
+            Value::build_text("a"),
+            Value::Blob(vec![1]),
+        ];
+        for a in &values {
+            for b in &values {
+                assert_eq!(
+                    a == b,
+                    a.cmp(b) == std::cmp::Ordering::Equal,
+                    "{a:?} vs {b:?}"
+                );
+            }
+        }
     }
 
-    pub fn blob(size: u64) -> Self {
-        Self(12 + size * 2)
-    }
+    #[test]
+    fn test_cmp_with_nulls() {
+        let column = vec![
+            Value::Integer(3),
+            Value::Null,
+            Value::build_text("b"),
+            Value::Integer(1),
+            Value::Null,
+        ];
 
-    pub fn text(size: u64) -> Self {
-        Self(13 + size * 2)
-    }
+        let mut nulls_first = column.clone();
+        nulls_first.sort_by(|a, b| a.cmp_with_nulls(b, false));
+        assert_eq!(
+            nulls_first,
+            vec![
+                Value::Null,
+                Value::Null,
+                Value::Integer(1),
+                Value::Integer(3),
+                Value::build_text("b"),
+            ]
+        );
 
-    pub fn kind(&self) -> SerialTypeKind {
-        match self.0 {
-            0 => SerialTypeKind::Null,
-            1 => SerialTypeKind::I8,
-            2 => SerialTypeKind::I16,
-            3 => SerialTypeKind::I24,
-            4 => SerialTypeKind::I32,
-            5 => SerialTypeKind::I48,
-            6 => SerialTypeKind::I64,
-            7 => SerialTypeKind::F64,
-            8 => SerialTypeKind::ConstInt0,
-            9 => SerialTypeKind::ConstInt1,
-            n if n >= 12 => match n % 2 {
-                0 => SerialTypeKind::Blob,
-                1 => SerialTypeKind::Text,
-                _ => unreachable!(),
-            },
-            _ => unreachable!(),
-        }
+        let mut nulls_last = column;
+        nulls_last.sort_by(|a, b| a.cmp_with_nulls(b, true));
+        assert_eq!(
+            nulls_last,
+            vec![
+                Value::Integer(1),
+                Value::Integer(3),
+                Value::build_text("b"),
+                Value::Null,
+                Value::Null,
+            ]
+        );
     }
 
-    pub fn size(&self) -> usize {
-        match self.kind() {
-            SerialTypeKind::Null => 0,
-            SerialTypeKind::I8 => 1,
-            SerialTypeKind::I16 => 2,
-            SerialTypeKind::I24 => 3,
-            SerialTypeKind::I32 => 4,
-            SerialTypeKind::I48 => 6,
-            SerialTypeKind::I64 => 8,
-            SerialTypeKind::F64 => 8,
-            SerialTypeKind::ConstInt0 => 0,
-            SerialTypeKind::ConstInt1 => 0,
-            SerialTypeKind::Text => (self.0 as usize - 13) / 2,
-            SerialTypeKind::Blob => (self.0 as usize - 12) / 2,
-        }
+    #[test]
+    fn test_add_assign_i64() {
+        let mut count = Value::Null;
+        count += 1;
+        assert_eq!(count, Value::Integer(1));
+        count += 41;
+        assert_eq!(count, Value::Integer(42));
+
+        let mut float = Value::Float(0.5);
+        float += 2;
+        assert_eq!(float, Value::Float(2.5));
+
+        let mut overflow = Value::Integer(i64::MAX);
+        overflow += 1;
+        assert_eq!(overflow, Value::Float(i64::MAX as f64 + 1.0));
+
+        let mut text = Value::build_text("abc");
+        text += 1;
+        assert_eq!(text, Value::build_text("abc"));
+
+        let mut blob = Value::Blob(vec![1, 2]);
+        blob += 1;
+        assert_eq!(blob, Value::Blob(vec![1, 2]));
     }
-}
 
-impl From<&Value> for SerialType {
-    fn from(value: &Value) -> Self {
-        match value {
-            Value::Null => SerialType::null(),
-            Value::Integer(i) => match i {
-                0 => SerialType::const_int0(),
-                1 => SerialType::const_int1(),
-                i if *i >= I8_LOW && *i <= I8_HIGH => SerialType::i8(),
-                i if *i >= I16_LOW && *i <= I16_HIGH => SerialType::i16(),
-                i if *i >= I24_LOW && *i <= I24_HIGH => SerialType::i24(),
-                i if *i >= I32_LOW && *i <= I32_HIGH => SerialType::i32(),
-                i if *i >= I48_LOW && *i <= I48_HIGH => SerialType::i48(),
-                _ => SerialType::i64(),
-            },
-            Value::Float(_) => SerialType::f64(),
-            Value::Text(t) => SerialType::text(t.value.len() as u64),
-            Value::Blob(b) => SerialType::blob(b.len() as u64),
-        }
+    #[test]
+    fn test_record_map_values() {
+        let record = Record::new(vec![
+            Value::build_text("42"),
+            Value::build_text("1.5"),
+            Value::build_text("abc"),
+            Value::Null,
+        ]);
+        let mapped = record.map_values(|v| v.with_affinity(Affinity::Numeric).into_owned());
+        assert_eq!(
+            mapped.get_values(),
+            &[
+                Value::Integer(42),
+                Value::Float(1.5),
+                Value::build_text("abc"),
+                Value::Null,
+            ]
+        );
+        assert_eq!(record.get_value(0), &Value::build_text("42"));
     }
-}
 
-impl From<SerialType> for u64 {
-    fn from(serial_type: SerialType) -> Self {
-        serial_type.0
+    #[test]
+    fn test_record_project() {
+        let record = Record::new(vec![
+            Value::Integer(1),
+            Value::build_text("two"),
+            Value::Float(3.0),
+        ]);
+        let projected = record.project(&[2, 0]).unwrap();
+        assert_eq!(
+            projected.get_values(),
+            &[Value::Float(3.0), Value::Integer(1)]
+        );
+        assert_eq!(record.project(&[]).unwrap().len(), 0);
+        assert_eq!(record.project(&[1, 1]).unwrap().len(), 2);
+        assert!(matches!(
+            record.project(&[0, 3]),
+            Err(LimboError::InvalidArgument(_))
+        ));
     }
-}
 
-impl TryFrom<u64> for SerialType {
-    type Error = LimboError;
+    #[test]
+    fn test_record_concat() {
+        let outer = Record::new(vec![Value::Integer(1), Value::build_text("a")]);
+        let inner = Record::new(vec![Value::Null, Value::Float(2.5), Value::Blob(vec![7])]);
+        let joined = outer.concat(&inner);
+        assert_eq!(joined.len(), 5);
+        assert_eq!(
+            joined.get_values(),
+            &[
+                Value::Integer(1),
+                Value::build_text("a"),
+                Value::Null,
+                Value::Float(2.5),
+                Value::Blob(vec![7]),
+            ]
+        );
+    }
 
-    fn try_from(uint: u64) -> Result<Self> {
-        if uint == 10 || uint == 11 {
-            return Err(LimboError::Corrupt(format!(
-                "Invalid serial type: {}",
-                uint
-            )));
+    #[test]
+    fn test_record_iter() {
+        let record = Record::new(vec![
+            Value::Integer(1),
+            Value::build_text("skip"),
+            Value::Integer(2),
+            Value::Null,
+            Value::Integer(39),
+        ]);
+        let sum: i64 = record
+            .iter()
+            .filter_map(|v| match v {
+                Value::Integer(i) => Some(*i),
+                _ => None,
+            })
+            .sum();
+        assert_eq!(sum, 42);
+
+        let mut borrowed = 0;
+        for value in &record {
+            if matches!(value, Value::Integer(_)) {
+                borrowed += 1;
+            }
         }
-        Ok(SerialType(uint))
-    }
-}
+        assert_eq!(borrowed, 3);
 
-impl Record {
-    pub fn new(values: Vec<Value>) -> Self {
-        Self { values }
+        let owned: Vec<Value> = record.into_iter().collect();
+        assert_eq!(owned.len(), 5);
+        assert_eq!(owned[1], Value::build_text("skip"));
     }
 
-    pub fn serialize(&self, buf: &mut Vec<u8>) {
-        let initial_i = buf.len();
-
-        // write serial types
-        for value in &self.values {
-            let serial_type = SerialType::from(value);
-            buf.resize(buf.len() + 9, 0); // Ensure space for varint (1-9 bytes in length)
-            let len = buf.len();
-            let n = write_varint(&mut buf[len - 9..], serial_type.into());
-            buf.truncate(buf.len() - 9 + n); // Remove unused bytes
-        }
+    #[test]
+    fn test_avg_final_value() {
+        let mut avg = AggContext::Avg(Value::Float(0.0), Value::Integer(0));
+        assert_eq!(avg.final_value(), Value::Null);
 
-        let mut header_size = buf.len() - initial_i;
-        // write content
-        for value in &self.values {
-            match value {
-                Value::Null => {}
-                Value::Integer(i) => {
-                    let serial_type = SerialType::from(value);
-                    match serial_type.kind() {
-                        SerialTypeKind::ConstInt0 | SerialTypeKind::ConstInt1 => {}
-                        SerialTypeKind::I8 => buf.extend_from_slice(&(*i as i8).to_be_bytes()),
-                        SerialTypeKind::I16 => buf.extend_from_slice(&(*i as i16).to_be_bytes()),
-                        SerialTypeKind::I24 => {
-                            buf.extend_from_slice(&(*i as i32).to_be_bytes()[1..])
-                        } // remove most significant byte
-                        SerialTypeKind::I32 => buf.extend_from_slice(&(*i as i32).to_be_bytes()),
-                        SerialTypeKind::I48 => buf.extend_from_slice(&i.to_be_bytes()[2..]), // remove 2 most significant bytes
-                        SerialTypeKind::I64 => buf.extend_from_slice(&i.to_be_bytes()),
-                        _ => unreachable!(),
-                    }
-                }
-                Value::Float(f) => buf.extend_from_slice(&f.to_be_bytes()),
-                Value::Text(t) => buf.extend_from_slice(&t.value),
-                Value::Blob(b) => buf.extend_from_slice(b),
+        for v in [Value::Integer(2), Value::Integer(4)] {
+            let AggContext::Avg(sum, count) = &mut avg else {
+                unreachable!();
             };
+            *sum = sum.arith_add(&v);
+            *count += 1;
         }
-
-        let mut header_bytes_buf: Vec<u8> = Vec::new();
-        if header_size <= 126 {
-            // common case
-            header_size += 1;
-        } else {
-            todo!("calculate big header size extra bytes");
-            // get header varint len
-            // header_size += n;
-            // if( nVarint<sqlite3VarintLen(nHdr) ) nHdr++;
-        }
-        assert!(header_size <= 126);
-        header_bytes_buf.extend(std::iter::repeat(0).take(9));
-        let n = write_varint(header_bytes_buf.as_mut_slice(), header_size as u64);
-        header_bytes_buf.truncate(n);
-        buf.splice(initial_i..initial_i, header_bytes_buf.iter().cloned());
+        assert_eq!(avg.final_value(), Value::Float(3.0));
+        assert_eq!(avg.final_value(), Value::Float(3.0));
     }
-}
 
-pub enum Cursor {
-    BTree(BTreeCursor),
-    Pseudo(PseudoCursor),
-    Sorter(Sorter),
-    Virtual(VirtualTableCursor),
-}
+    #[test]
+    fn test_arith_coerces_text() {
+        assert_eq!(
+            Value::build_text("3").arith_add(&Value::Integer(4)),
+            Value::Integer(7)
+        );
+        assert_eq!(
+            Value::build_text("abc").arith_add(&Value::Integer(1)),
+            Value::Integer(1)
+        );
+        assert_eq!(
+            Value::build_text("10").arith_sub(&Value::build_text("x")),
+            Value::Integer(10)
+        );
+        assert_eq!(
+            Value::build_text("1.5").arith_mul(&Value::Integer(2)),
+            Value::Float(3.0)
+        );
+        assert_eq!(
+            Value::build_text("10").arith_div(&Value::build_text("4")),
+            Value::Integer(2)
+        );
+        assert_eq!(Value::Integer(1).arith_div(&Value::Integer(0)), Value::Null);
+        assert_eq!(Value::Null.arith_mul(&Value::Integer(2)), Value::Null);
 
-impl Cursor {
-    pub fn new_btree(cursor: BTreeCursor) -> Self {
-        Self::BTree(cursor)
+        // The accumulating Add never joins text; it sums numerically like sum().
+        assert_eq!(
+            Value::build_text("3") + Value::Integer(4),
+            Value::Integer(7)
+        );
+        assert!(matches!(
+            Value::build_text("1") + Value::build_text("2"),
+            Value::Integer(3)
+        ));
+        assert!(matches!(
+            Value::build_text("abc") + Value::Integer(1),
+            Value::Float(1.0)
+        ));
+        assert!(matches!(
+            Value::build_text("2.5") + Value::Null,
+            Value::Float(2.5)
+        ));
+        assert!(matches!(
+            Value::Null + Value::build_text("5"),
+            Value::Integer(5)
+        ));
     }
 
-    pub fn new_pseudo(cursor: PseudoCursor) -> Self {
-        Self::Pseudo(cursor)
+    #[test]
+    fn test_agg_context_initial_values() {
+        assert_eq!(AggContext::new_sum().final_value(), Value::Null);
+        assert!(matches!(
+            AggContext::new_total().final_value(),
+            Value::Float(0.0)
+        ));
+        assert!(matches!(
+            AggContext::new_count().final_value(),
+            Value::Integer(0)
+        ));
+        assert_eq!(AggContext::new_avg().final_value(), Value::Null);
+        assert_eq!(AggContext::new_min().final_value(), Value::Null);
+        assert_eq!(AggContext::new_max().final_value(), Value::Null);
+        assert_eq!(AggContext::new_group_concat().final_value(), Value::Null);
     }
 
-    pub fn new_sorter(cursor: Sorter) -> Self {
-        Self::Sorter(cursor)
-    }
+    #[test]
+    fn test_agg_context_step() {
+        let inputs = [Value::Null, Value::Integer(2), Value::Integer(4)];
+        let run = |mut agg: AggContext| {
+            for value in &inputs {
+                agg.step(value);
+            }
+            agg.final_value()
+        };
 
-    pub fn as_btree_mut(&mut self) -> &mut BTreeCursor {
-        match self {
-            Self::BTree(cursor) => cursor,
-            _ => panic!("Cursor is not a btree"),
-        }
-    }
+        assert_eq!(run(AggContext::new_avg()), Value::Float(3.0));
+        assert!(matches!(run(AggContext::new_sum()), Value::Integer(6)));
+        assert!(matches!(run(AggContext::new_total()), Value::Float(6.0)));
+        assert!(matches!(run(AggContext::new_count()), Value::Integer(2)));
+        assert_eq!(run(AggContext::new_min()), Value::Integer(2));
+        assert_eq!(run(AggContext::new_max()), Value::Integer(4));
+        assert_eq!(
+            run(AggContext::new_group_concat()),
+            Value::build_text("2,4")
+        );
 
-    pub fn as_pseudo_mut(&mut self) -> &mut PseudoCursor {
-        match self {
-            Self::Pseudo(cursor) => cursor,
-            _ => panic!("Cursor is not a pseudo cursor"),
+        let mut max = AggContext::new_max();
+        for value in [Value::Integer(3), Value::Float(3.5), Value::build_text("a")] {
+            max.step(&value);
         }
-    }
+        assert_eq!(max.final_value(), Value::build_text("a"));
 
-    pub fn as_sorter_mut(&mut self) -> &mut Sorter {
-        match self {
-            Self::Sorter(cursor) => cursor,
-            _ => panic!("Cursor is not a sorter cursor"),
+        let mut concat = AggContext::new_group_concat();
+        for value in [Value::build_text("a"), Value::Null, Value::Float(1.5)] {
+            concat.step_group_concat(&value, &Value::build_text("; "));
         }
+        assert_eq!(concat.final_value(), Value::build_text("a; 1.5"));
     }
 
-    pub fn as_virtual_mut(&mut self) -> &mut VirtualTableCursor {
-        match self {
-            Self::Virtual(cursor) => cursor,
-            _ => panic!("Cursor is not a virtual cursor"),
+    #[test]
+    fn test_agg_context_count_all_vs_count_col() {
+        let inputs = [Value::Integer(1), Value::Null, Value::Integer(3)];
+        let mut count_col = AggContext::new_count();
+        let mut count_all = AggContext::new_count_all();
+        for value in &inputs {
+            count_col.step(value);
+            count_all.step(value);
         }
+        assert!(matches!(count_col.final_value(), Value::Integer(2)));
+        assert!(matches!(count_all.final_value(), Value::Integer(3)));
     }
-}
 
-#[derive(Debug)]
-pub enum CursorResult<T> {
-    Ok(T),
-    IO,
-}
+    #[test]
+    fn test_serialize_compact_floats() {
+        let record = Record::new(vec![
+            Value::Float(3.0),
+            Value::Float(-0.0),
+            Value::Float(2.5),
+        ]);
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
-/// The match condition of a table/index seek.
-pub enum SeekOp {
-    EQ,
-    GE,
-    GT,
-    LE,
-    LT,
-}
+        let mut exact = Vec::new();
+        record.serialize(&mut exact);
+        assert_eq!(&exact[1..4], &[7, 7, 7]);
+
+        let mut compact = Vec::new();
+        record.serialize_compact(&mut compact);
+        // 3.0 is stored as an 8-bit integer; -0.0 and 2.5 stay 8-byte floats.
+        assert_eq!(&compact[..4], &[4, 1, 7, 7]);
+        assert_eq!(compact[4], 3);
+        assert_eq!(&compact[5..13], &(-0.0f64).to_be_bytes());
+        assert_eq!(&compact[13..], &2.5f64.to_be_bytes());
+
+        let mut nan = Vec::new();
+        Record::new(vec![Value::Float(f64::NAN), Value::Float(1e300)]).serialize_compact(&mut nan);
+        assert_eq!(&nan[..3], &[3, 7, 7]);
+    }
 
-impl SeekOp {
-    /// A given seek op implies an iteration direction.
-    ///
-    /// For example, a seek with SeekOp::GT implies:
-    /// Find the first table/index key that compares greater than the seek key
-    /// -> used in forwards iteration.
-    ///
-    /// A seek with SeekOp::LE implies:
-    /// Find the last table/index key that compares less than or equal to the seek key
-    /// -> used in backwards iteration.
-    #[inline(always)]
-    pub fn iteration_direction(&self) -> IterationDirection {
-        match self {
-            SeekOp::EQ | SeekOp::GE | SeekOp::GT => IterationDirection::Forwards,
-            SeekOp::LE | SeekOp::LT => IterationDirection::Backwards,
-        }
+    #[test]
+    fn test_conversion_error_names_types() {
+        let err = i64::try_from(&Value::build_text("1")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Conversion error: Expected integer value, found text"
+        );
+        let err = f64::try_from(Value::Null).unwrap_err();
+        assert!(err.to_string().ends_with("Expected real value, found null"));
+        let err = Vec::<u8>::try_from(Value::Float(1.5)).unwrap_err();
+        assert!(err.to_string().ends_with("Expected blob value, found real"));
+
+        let value = RefValue::Blob(unsafe { RawSlice::from_slice(&b"ab"[..]) });
+        let err = String::from_value(&value).unwrap_err();
+        assert!(matches!(
+            err,
+            LimboError::ConversionError(ref msg) if msg == "Expected text value, found blob"
+        ));
+        let err = i64::from_value(&RefValue::Float(2.0)).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Expected integer value, found real"));
     }
 
-    pub fn reverse(&self) -> Self {
-        match self {
-            SeekOp::EQ => SeekOp::EQ,
-            SeekOp::GE => SeekOp::LE,
-            SeekOp::GT => SeekOp::LT,
-            SeekOp::LE => SeekOp::GE,
-            SeekOp::LT => SeekOp::GT,
+    #[test]
+    fn test_type_name() {
+        let text = String::from("abc");
+        let cases = [
+            (RefValue::Null, "null"),
+            (RefValue::Integer(1), "integer"),
+            (RefValue::Float(1.5), "real"),
+            (RefValue::Text(unsafe { TextRef::new(&text) }), "text"),
+            (
+                RefValue::Blob(unsafe { RawSlice::from_slice(text.as_bytes()) }),
+                "blob",
+            ),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(value.type_name(), expected);
+            assert_eq!(value.to_owned().type_name(), expected);
         }
     }
-}
 
-#[derive(Clone, PartialEq, Debug)]
-pub enum SeekKey<'a> {
-    TableRowId(i64),
-    IndexKey(&'a ImmutableRecord),
-}
+    #[test]
+    fn test_serialize_large_record() {
+        // 200 columns need a two-byte header size varint.
+        let values: Vec<Value> = (0..200)
+            .map(|i| match i % 4 {
+                0 => Value::Integer(i * 1_000_003),
+                1 => Value::Float(i as f64 / 3.0),
+                2 => Value::build_text("x".repeat(i as usize)),
+                _ => Value::Null,
+            })
+            .collect();
+        let record = Record::new(values.clone());
+
+        let mut buf = vec![0xaa];
+        record.serialize(&mut buf);
+        let mut expected = vec![0xaa];
+        record.serialize_to(&mut expected).unwrap();
+        assert_eq!(buf, expected);
+        assert!(buf[1] & 0x80 != 0);
 
-impl RawSlice {
-    pub fn new(data: *const u8, len: usize) -> Self {
-        Self { data, len }
+        assert_eq!(
+            Record::deserialize(&buf[1..]).unwrap().get_values(),
+            &values
+        );
     }
-    pub fn to_slice(&self) -> &[u8] {
-        if self.data.is_null() {
-            &[]
-        } else {
-            unsafe { std::slice::from_raw_parts(self.data, self.len) }
+
+    #[test]
+    fn test_encode_sort_key_matches_cmp() {
+        let values = [
+            Value::Null,
+            Value::Integer(i64::MIN),
+            Value::Float(-1e300),
+            Value::Integer(-2),
+            Value::Float(-1.5),
+            Value::Float(-0.0),
+            Value::Integer(0),
+            Value::Float(0.5),
+            Value::Integer(1),
+            Value::Float(1.0),
+            Value::Integer(1 << 53),
+            Value::Integer((1 << 53) + 1),
+            Value::Integer(i64::MAX),
+            Value::Float(f64::INFINITY),
+            Value::build_text(""),
+            Value::build_text("a"),
+            Value::build_text("a\0b"),
+            Value::build_text("ab"),
+            Value::build_text("b"),
+            Value::Blob(vec![]),
+            Value::Blob(vec![0x00]),
+            Value::Blob(vec![0x00, 0x00]),
+            Value::Blob(vec![0xff]),
+        ];
+        let encode = |value: &Value, desc: bool| {
+            let mut buf = Vec::new();
+            value.encode_sort_key(desc, &mut buf);
+            buf
+        };
+        // Equal numbers are told apart by type so that they decode back unchanged.
+        let numeric_kind = |value: &Value| match value {
+            Value::Float(f) if *f == 0.0 && f.is_sign_negative() => 2,
+            Value::Float(_) => 1,
+            _ => 0,
+        };
+        for a in &values {
+            for b in &values {
+                let expected = a.cmp(b).then_with(|| numeric_kind(a).cmp(&numeric_kind(b)));
+                assert_eq!(
+                    encode(a, false).cmp(&encode(b, false)),
+                    expected,
+                    "{a:?} {b:?}"
+                );
+                assert_eq!(
+                    encode(a, true).cmp(&encode(b, true)),
+                    expected.reverse(),
+                    "{a:?} {b:?}"
+                );
+            }
         }
+
+        // Keys for later columns only break ties left by earlier ones.
+        let key = |a: &Value, b: &Value| {
+            let mut buf = Vec::new();
+            a.encode_sort_key(false, &mut buf);
+            b.encode_sort_key(true, &mut buf);
+            buf
+        };
+        let (a, ab) = (Value::build_text("a"), Value::build_text("ab"));
+        assert!(key(&a, &Value::Integer(1)) < key(&ab, &Value::Integer(2)));
+        assert!(key(&a, &Value::Integer(2)) < key(&a, &Value::Integer(1)));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_decode_sort_key_round_trip() {
+        let values = [
+            Value::Null,
+            Value::Integer(0),
+            Value::Integer(-1),
+            Value::Integer(42),
+            Value::Integer(i64::MIN),
+            Value::Integer(i64::MAX),
+            Value::Float(1.0),
+            Value::Float(-2.75),
+            Value::Float(-0.0),
+            Value::Float(f64::NEG_INFINITY),
+            Value::Float(1e-300),
+            Value::build_text(""),
+            Value::build_text("hello\0world"),
+            Value::Blob(vec![]),
+            Value::Blob(vec![0x00, 0xff, 0x00, 0x00, 0x01]),
+        ];
+        for desc in [false, true] {
+            let mut buf = Vec::new();
+            for value in &values {
+                value.encode_sort_key(desc, &mut buf);
+            }
+            let mut rest = &buf[..];
+            for value in &values {
+                let (decoded, len) = Value::decode_sort_key(rest).unwrap();
+                assert_eq!(&decoded, value);
+                assert_eq!(decoded.value_type(), value.value_type());
+                if let (Value::Float(a), Value::Float(b)) = (&decoded, value) {
+                    assert_eq!(a.to_bits(), b.to_bits());
+                }
+                rest = &rest[len..];
+            }
+            assert!(rest.is_empty());
+        }
+
+        assert!(Value::decode_sort_key(&[]).is_err());
+        assert!(Value::decode_sort_key(&[0x02, 0x80]).is_err());
+        assert!(Value::decode_sort_key(&[0x03, b'a', 0x00]).is_err());
+        assert!(Value::decode_sort_key(&[0x07]).is_err());
+    }
 
     #[test]
-    fn test_serialize_null() {
-        let record = Record::new(vec![Value::Null]);
-        let mut buf = Vec::new();
-        record.serialize(&mut buf);
+    fn test_as_rowid() {
+        assert_eq!(Value::Integer(-7).as_rowid(), Some(-7));
+        assert_eq!(Value::Float(42.0).as_rowid(), Some(42));
+        assert_eq!(Value::Float(-0.0).as_rowid(), Some(0));
+        assert_eq!(Value::build_text("123").as_rowid(), Some(123));
+        assert_eq!(Value::build_text(" -5 ").as_rowid(), Some(-5));
+        assert_eq!(
+            Value::build_text("9223372036854775807").as_rowid(),
+            Some(i64::MAX)
+        );
 
-        let header_length = record.values.len() + 1;
-        let header = &buf[0..header_length];
-        // First byte should be header size
-        assert_eq!(header[0], header_length as u8);
-        // Second byte should be serial type for NULL
-        assert_eq!(header[1] as u64, u64::from(SerialType::null()));
-        // Check that the buffer is empty after the header
-        assert_eq!(buf.len(), header_length);
+        assert_eq!(Value::build_text("1.5").as_rowid(), None);
+        assert_eq!(Value::build_text("1.0").as_rowid(), None);
+        assert_eq!(Value::build_text("12abc").as_rowid(), None);
+        assert_eq!(Value::build_text("9223372036854775808").as_rowid(), None);
+        assert_eq!(Value::Float(1.5).as_rowid(), None);
+        assert_eq!(Value::Float(1e19).as_rowid(), None);
+        assert_eq!(Value::Float(f64::NAN).as_rowid(), None);
+        assert_eq!(Value::Null.as_rowid(), None);
+        assert_eq!(Value::Blob(b"1".to_vec()).as_rowid(), None);
     }
 
     #[test]
-    fn test_serialize_integers() {
-        let record = Record::new(vec![
-            Value::Integer(0),                 // Should use ConstInt0
-            Value::Integer(1),                 // Should use ConstInt1
-            Value::Integer(42),                // Should use SERIAL_TYPE_I8
-            Value::Integer(1000),              // Should use SERIAL_TYPE_I16
-            Value::Integer(1_000_000),         // Should use SERIAL_TYPE_I24
-            Value::Integer(1_000_000_000),     // Should use SERIAL_TYPE_I32
-            Value::Integer(1_000_000_000_000), // Should use SERIAL_TYPE_I48
-            Value::Integer(i64::MAX),          // Should use SERIAL_TYPE_I64
-        ]);
-        let mut buf = Vec::new();
-        record.serialize(&mut buf);
+    fn test_build_blob() {
+        let bytes = [0x00, 0x01, 0xfe, 0xff];
+        assert_eq!(Value::build_blob(&bytes), Value::Blob(bytes.to_vec()));
+        assert_eq!(
+            Value::build_blob(&bytes[1..3]).to_blob(),
+            Some(&bytes[1..3])
+        );
+        assert_eq!(Value::build_blob(&[]), Value::from_blob(vec![]));
+        assert_ne!(Value::build_blob(b"abc"), Value::build_text("abc"));
+    }
 
-        let header_length = record.values.len() + 1;
-        let header = &buf[0..header_length];
-        // First byte should be header size
-        assert_eq!(header[0], header_length as u8); // Header should be larger than number of values
+    #[test]
+    fn test_agg_context_group_concat_nulls_and_order() {
+        let sep = Value::build_text("-");
+        let mut concat = AggContext::new_group_concat();
+        for value in [
+            Value::Null,
+            Value::build_text("b"),
+            Value::Null,
+            Value::Integer(1),
+        ] {
+            concat.step_group_concat(&value, &sep);
+        }
+        assert_eq!(concat.final_value(), Value::build_text("b-1"));
+
+        let mut nulls = AggContext::new_group_concat();
+        nulls.step_group_concat(&Value::Null, &sep);
+        assert_eq!(nulls.final_value(), Value::Null);
+
+        // group_concat(name, '-' ORDER BY rank)
+        let mut ordered = AggContext::new_ordered_group_concat();
+        for (name, rank) in [("c", 3), ("a", 1), ("x", 2), ("b", 2)] {
+            ordered.step_ordered_group_concat(
+                &Value::build_text(name),
+                &sep,
+                &Value::Integer(rank),
+            );
+        }
+        ordered.step_ordered_group_concat(&Value::Null, &sep, &Value::Integer(0));
+        assert_eq!(ordered.final_value(), Value::build_text("a-x-b-c"));
 
-        // Check that correct serial types were chosen
-        assert_eq!(header[1] as u64, u64::from(SerialType::const_int0())); // 8
-        assert_eq!(header[2] as u64, u64::from(SerialType::const_int1())); // 9
-        assert_eq!(header[3] as u64, u64::from(SerialType::i8())); // 1
-        assert_eq!(header[4] as u64, u64::from(SerialType::i16())); // 2
-        assert_eq!(header[5] as u64, u64::from(SerialType::i24())); // 3
-        assert_eq!(header[6] as u64, u64::from(SerialType::i32())); // 4
-        assert_eq!(header[7] as u64, u64::from(SerialType::i48())); // 5
-        assert_eq!(header[8] as u64, u64::from(SerialType::i64())); // 6
+        let mut by_value = AggContext::new_ordered_group_concat();
+        for value in [
+            Value::build_text("pear"),
+            Value::Null,
+            Value::build_text("apple"),
+        ] {
+            by_value.step(&value);
+        }
+        assert_eq!(by_value.final_value(), Value::build_text("apple,pear"));
+        assert_eq!(
+            AggContext::new_ordered_group_concat().final_value(),
+            Value::Null
+        );
+    }
 
-        // test that the bytes after the header can be interpreted as the correct values
-        let mut cur_offset = header_length;
+    #[test]
+    fn test_signum() {
+        assert_eq!(Value::Float(-2.5).signum(), Value::Integer(-1));
+        assert_eq!(Value::Integer(0).signum(), Value::Integer(0));
+        assert_eq!(Value::Float(-0.0).signum(), Value::Integer(0));
+        assert_eq!(Value::Integer(7).signum(), Value::Integer(1));
+        assert_eq!(Value::Integer(i64::MIN).signum(), Value::Integer(-1));
+        assert_eq!(Value::Null.signum(), Value::Null);
+
+        assert_eq!(Value::build_text(" 5 ").signum(), Value::Integer(1));
+        assert_eq!(Value::build_text("-1e999").signum(), Value::Integer(-1));
+        assert_eq!(Value::build_text("-0.0").signum(), Value::Integer(0));
+        assert_eq!(Value::build_text("5abc").signum(), Value::Null);
+        assert_eq!(Value::build_text("inf").signum(), Value::Null);
+    }
 
-        // Value::Integer(0) - ConstInt0: NO PAYLOAD BYTES
-        // Value::Integer(1) - ConstInt1: NO PAYLOAD BYTES
+    #[test]
+    fn test_agg_context_median_and_percentile() {
+        let run = |mut agg: AggContext, values: &[Value]| {
+            for value in values {
+                agg.step(value);
+            }
+            agg.final_value()
+        };
+        let ints = |values: &[i64]| {
+            values
+                .iter()
+                .map(|&i| Value::Integer(i))
+                .collect::<Vec<_>>()
+        };
 
-        // Value::Integer(42) - I8: 1 byte
-        let i8_bytes = &buf[cur_offset..cur_offset + size_of::<i8>()];
-        cur_offset += size_of::<i8>();
+        assert_eq!(
+            run(AggContext::new_median(), &ints(&[1, 2, 3, 4])),
+            Value::Float(2.5)
+        );
+        assert_eq!(
+            run(AggContext::new_median(), &ints(&[3, 1, 2])),
+            Value::Float(2.0)
+        );
+        assert_eq!(
+            run(
+                AggContext::new_median(),
+                &[Value::Null, Value::Float(0.5), Value::Integer(2)]
+            ),
+            Value::Float(1.25)
+        );
+        assert_eq!(run(AggContext::new_median(), &[Value::Null]), Value::Null);
+        assert_eq!(
+            run(
+                AggContext::new_median(),
+                &[
+                    Value::Integer(4),
+                    Value::build_text("abc"),
+                    Value::build_text("1.5"),
+                    Value::Blob(vec![1, 2]),
+                ]
+            ),
+            Value::Float(4.0)
+        );
+        assert_eq!(
+            run(
+                AggContext::new_percentile(50.0).unwrap(),
+                &[Value::build_text("abc")]
+            ),
+            Value::Null
+        );
 
-        // Value::Integer(1000) - I16: 2 bytes
-        let i16_bytes = &buf[cur_offset..cur_offset + size_of::<i16>()];
-        cur_offset += size_of::<i16>();
+        let values = ints(&[10, 40, 20, 30, 50]);
+        assert_eq!(
+            run(AggContext::new_percentile(0.0).unwrap(), &values),
+            Value::Float(10.0)
+        );
+        assert_eq!(
+            run(AggContext::new_percentile(25.0).unwrap(), &values),
+            Value::Float(20.0)
+        );
+        assert_eq!(
+            run(AggContext::new_percentile(90.0).unwrap(), &values),
+            Value::Float(46.0)
+        );
+        assert_eq!(
+            run(AggContext::new_percentile(100.0).unwrap(), &values),
+            Value::Float(50.0)
+        );
 
-        // Value::Integer(1_000_000) - I24: 3 bytes
-        let i24_bytes = &buf[cur_offset..cur_offset + 3];
-        cur_offset += 3;
+        for p in [-0.1, 100.5, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                AggContext::new_percentile(p),
+                Err(LimboError::InvalidArgument(_))
+            ));
+        }
+    }
 
-        // Value::Integer(1_000_000_000) - I32: 4 bytes
-        let i32_bytes = &buf[cur_offset..cur_offset + size_of::<i32>()];
-        cur_offset += size_of::<i32>();
+    #[test]
+    fn test_agg_context_bit_and_or() {
+        let run = |mut agg: AggContext, values: &[Value]| {
+            for value in values {
+                agg.step(value);
+            }
+            agg.final_value()
+        };
+        let values = [Value::Integer(6), Value::Null, Value::Integer(3)];
+        assert_eq!(run(AggContext::new_bit_and(), &values), Value::Integer(2));
+        assert_eq!(run(AggContext::new_bit_or(), &values), Value::Integer(7));
 
-        // Value::Integer(1_000_000_000_000) - I48: 6 bytes
-        let i48_bytes = &buf[cur_offset..cur_offset + 6];
-        cur_offset += 6;
+        let values = [Value::Float(12.9), Value::build_text("10")];
+        assert_eq!(run(AggContext::new_bit_and(), &values), Value::Integer(8));
+        assert_eq!(run(AggContext::new_bit_or(), &values), Value::Integer(14));
 
-        // Value::Integer(i64::MAX) - I64: 8 bytes
-        let i64_bytes = &buf[cur_offset..cur_offset + size_of::<i64>()];
+        assert_eq!(run(AggContext::new_bit_and(), &[]), Value::Integer(-1));
+        assert_eq!(
+            run(AggContext::new_bit_or(), &[Value::Null]),
+            Value::Integer(0)
+        );
+    }
 
-        // Verify the payload values
-        let val_int8 = i8::from_be_bytes(i8_bytes.try_into().unwrap());
-        let val_int16 = i16::from_be_bytes(i16_bytes.try_into().unwrap());
+    #[test]
+    fn test_arith_add_strict() {
+        let abc = Value::build_text("abc");
+        assert_eq!(Value::Integer(1).arith_add(&abc), Value::Integer(1));
+        let err = Value::Integer(1).arith_add_strict(&abc).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Expected numeric value, found text"));
+        assert!(Value::Blob(vec![0xff])
+            .arith_add_strict(&Value::Integer(1))
+            .is_err());
+        assert!(Value::build_text("1x")
+            .arith_add_strict(&Value::Integer(1))
+            .is_err());
 
-        let mut i24_with_padding = vec![0];
-        i24_with_padding.extend(i24_bytes);
-        let val_int24 = i32::from_be_bytes(i24_with_padding.try_into().unwrap());
+        assert_eq!(
+            Value::build_text(" 2 ")
+                .arith_add_strict(&Value::Integer(1))
+                .unwrap(),
+            Value::Integer(3)
+        );
+        assert_eq!(
+            Value::Float(0.5)
+                .arith_add_strict(&Value::build_text("1.5"))
+                .unwrap(),
+            Value::Float(2.0)
+        );
+        assert_eq!(
+            Value::Null.arith_add_strict(&Value::Integer(1)).unwrap(),
+            Value::Null
+        );
+    }
 
-        let val_int32 = i32::from_be_bytes(i32_bytes.try_into().unwrap());
+    #[test]
+    fn test_record_prefix_and_pad_to() {
+        let key = Record::new(vec![
+            Value::Integer(1),
+            Value::build_text("a"),
+            Value::Float(2.5),
+            Value::Null,
+        ]);
+        let prefix = key.prefix(2);
+        assert_eq!(
+            prefix.get_values(),
+            &[Value::Integer(1), Value::build_text("a")]
+        );
+        assert_eq!(key.prefix(0).len(), 0);
+        assert_eq!(key.prefix(10), key);
 
-        let mut i48_with_padding = vec![0, 0];
-        i48_with_padding.extend(i48_bytes);
-        let val_int48 = i64::from_be_bytes(i48_with_padding.try_into().unwrap());
+        let padded = prefix.pad_to(4);
+        assert_eq!(
+            padded.get_values(),
+            &[
+                Value::Integer(1),
+                Value::build_text("a"),
+                Value::Null,
+                Value::Null
+            ]
+        );
+        assert_eq!(padded.pad_to(2), padded);
+    }
 
-        let val_int64 = i64::from_be_bytes(i64_bytes.try_into().unwrap());
+    #[test]
+    fn test_blob_slice() {
+        let blob = Value::build_blob(b"0123456789");
+        assert_eq!(blob.blob_slice(3, 4).unwrap(), b"3456");
+        assert_eq!(blob.blob_slice(0, 10).unwrap(), b"0123456789");
+        assert_eq!(blob.blob_slice(10, 0).unwrap(), b"");
+
+        assert!(matches!(
+            blob.blob_slice(8, 3),
+            Err(LimboError::InvalidArgument(_))
+        ));
+        assert!(blob.blob_slice(11, 0).is_err());
+        assert!(blob.blob_slice(usize::MAX, 2).is_err());
+        assert!(matches!(
+            Value::build_text("0123").blob_slice(0, 1),
+            Err(LimboError::ConversionError(_))
+        ));
+    }
 
-        assert_eq!(val_int8, 42);
-        assert_eq!(val_int16, 1000);
-        assert_eq!(val_int24, 1_000_000);
-        assert_eq!(val_int32, 1_000_000_000);
-        assert_eq!(val_int48, 1_000_000_000_000);
-        assert_eq!(val_int64, i64::MAX);
+    #[test]
+    fn test_try_as_f64() {
+        assert_eq!(Value::Integer(-3).try_as_f64(), Some(-3.0));
+        assert_eq!(Value::Float(2.5).try_as_f64(), Some(2.5));
+        assert_eq!(Value::build_text("1e2").try_as_f64(), Some(100.0));
+        assert_eq!(Value::build_text(" 7 ").try_as_f64(), Some(7.0));
+        assert_eq!(Value::build_text("-0.25").try_as_f64(), Some(-0.25));
+
+        assert_eq!(Value::build_text("5abc").try_as_f64(), None);
+        assert_eq!(Value::build_text("inf").try_as_f64(), None);
+        assert_eq!(Value::build_text("").try_as_f64(), None);
+        assert_eq!(Value::Null.try_as_f64(), None);
+        assert_eq!(Value::Blob(b"1".to_vec()).try_as_f64(), None);
+    }
 
-        //Size of buffer = header + payload bytes
-        // ConstInt0 and ConstInt1 contribute 0 bytes to payload
+    #[test]
+    fn test_try_as_i64() {
+        assert_eq!(Value::Integer(-3).try_as_i64(), Some(-3));
+        assert_eq!(Value::Float(3.9).try_as_i64(), Some(3));
+        assert_eq!(Value::Float(-3.9).try_as_i64(), Some(-3));
+        assert_eq!(Value::Float(1e30).try_as_i64(), Some(i64::MAX));
+        assert_eq!(Value::Float(-1e30).try_as_i64(), Some(i64::MIN));
+        assert_eq!(Value::Float(f64::NAN).try_as_i64(), None);
+
+        assert_eq!(Value::build_text("42abc").try_as_i64(), Some(42));
+        assert_eq!(Value::build_text("  -7.5").try_as_i64(), Some(-7));
+        assert_eq!(Value::build_text("+5").try_as_i64(), Some(5));
         assert_eq!(
-            buf.len(),
-            header_length  // 9 bytes (header size + 8 serial types)
-                + 0        // ConstInt0: 0 bytes
-                + 0        // ConstInt1: 0 bytes  
-                + size_of::<i8>()        // I8: 1 byte
-                + size_of::<i16>()        // I16: 2 bytes
-                + (size_of::<i32>() - 1)        // I24: 3 bytes
-                + size_of::<i32>()        // I32: 4 bytes
-                + (size_of::<i64>() - 2)        // I48: 6 bytes
-                + size_of::<i64>() // I64: 8 bytes
+            Value::build_text("99999999999999999999").try_as_i64(),
+            Some(i64::MAX)
         );
+        assert_eq!(Value::build_text("abc").try_as_i64(), None);
+        assert_eq!(Value::build_text("-").try_as_i64(), None);
+        assert_eq!(Value::Null.try_as_i64(), None);
+        assert_eq!(Value::Blob(b"1".to_vec()).try_as_i64(), None);
     }
 
     #[test]
-    fn test_serialize_const_integers() {
-        let record = Record::new(vec![Value::Integer(0), Value::Integer(1)]);
-        let mut buf = Vec::new();
-        record.serialize(&mut buf);
+    fn test_compare_text_with_encoding() {
+        use std::cmp::Ordering;
 
-        // [header_size, serial_type_0, serial_type_1] + no payload bytes
-        let expected_header_size = 3; // 1 byte for header size + 2 bytes for serial types
+        // U+FF61 sorts before U+1F600 by code point, but the surrogate pair 0xD83D 0xDE00
+        // sorts before 0xFF61 as UTF-16 code units.
+        let halfwidth = Value::build_text("\u{ff61}");
+        let emoji = Value::build_text("\u{1f600}");
+        assert_eq!(
+            halfwidth.compare_text_with_encoding(&emoji, Encoding::default()),
+            Ordering::Less
+        );
+        assert_eq!(
+            halfwidth.compare_text_with_encoding(&emoji, Encoding::Utf16be),
+            Ordering::Greater
+        );
 
-        assert_eq!(buf.len(), expected_header_size);
+        // Little-endian bytes put the low byte first: 0x0101 is [01 01], 0x0200 is [00 02].
+        let (a, b) = (Value::build_text("\u{101}"), Value::build_text("\u{200}"));
+        assert_eq!(
+            a.compare_text_with_encoding(&b, Encoding::Utf16be),
+            Ordering::Less
+        );
+        assert_eq!(
+            a.compare_text_with_encoding(&b, Encoding::Utf16le),
+            Ordering::Greater
+        );
 
-        // Check header size
-        assert_eq!(buf[0], expected_header_size as u8);
+        let abc = Value::build_text("abc");
+        for encoding in [Encoding::Utf8, Encoding::Utf16le, Encoding::Utf16be] {
+            assert_eq!(
+                abc.compare_text_with_encoding(&Value::build_text("abd"), encoding),
+                Ordering::Less
+            );
+            assert_eq!(
+                abc.compare_text_with_encoding(&Value::Integer(1), encoding),
+                Ordering::Greater
+            );
+        }
 
-        assert_eq!(buf[1] as u64, u64::from(SerialType::const_int0())); // Should be 8
-        assert_eq!(buf[2] as u64, u64::from(SerialType::const_int1())); // Should be 9
+        assert_eq!(Encoding::try_from(3).unwrap(), Encoding::Utf16be);
+        assert!(Encoding::try_from(4).is_err());
+    }
 
-        assert_eq!(buf[1], 8); // ConstInt0 serial type
-        assert_eq!(buf[2], 9); // ConstInt1 serial type
+    #[test]
+    fn test_apply_affinity_converts_real_text() {
+        let apply = |text: &str, affinity: Affinity| {
+            let mut value = Value::build_text(text);
+            value.apply_affinity(affinity);
+            value
+        };
+        for affinity in [Affinity::Integer, Affinity::Numeric] {
+            assert_eq!(apply("1.5", affinity), Value::Float(1.5));
+            assert_eq!(apply("3.0", affinity), Value::Integer(3));
+            assert_eq!(apply("abc", affinity), Value::build_text("abc"));
+        }
     }
 
     #[test]
-    fn test_serialize_single_const_int0() {
-        let record = Record::new(vec![Value::Integer(0)]);
-        let mut buf = Vec::new();
-        record.serialize(&mut buf);
+    fn test_record_apply_affinities() {
+        let record = Record::new(vec![
+            Value::build_text("42"),
+            Value::build_text("7"),
+            Value::build_text("12"),
+            Value::build_text("abc"),
+        ]);
+        let applied =
+            record.apply_affinities(&[Affinity::Integer, Affinity::Text, Affinity::Numeric]);
+        let types: Vec<_> = applied.get_values().iter().map(Value::type_name).collect();
+        assert_eq!(types, ["integer", "text", "integer", "text"]);
+        assert_eq!(
+            applied.get_values(),
+            &[
+                Value::Integer(42),
+                Value::build_text("7"),
+                Value::Integer(12),
+                Value::build_text("abc"),
+            ]
+        );
 
-        // Expected: [header_size=2, serial_type=8]
-        assert_eq!(buf.len(), 2);
-        assert_eq!(buf[0], 2); // Header size
-        assert_eq!(buf[1], 8); // ConstInt0 serial type
+        let numbers = Record::new(vec![Value::Integer(3), Value::Float(2.0), Value::Null]);
+        let applied = numbers.apply_affinities(&[Affinity::Real, Affinity::Text, Affinity::Text]);
+        assert_eq!(
+            applied.get_values(),
+            &[Value::Float(3.0), Value::build_text("2.0"), Value::Null]
+        );
     }
 
     #[test]
-    fn test_serialize_float() {
-        #[warn(clippy::approx_constant)]
-        let record = Record::new(vec![Value::Float(3.15555)]);
-        let mut buf = Vec::new();
-        record.serialize(&mut buf);
+    fn test_serialize_into_slice() {
+        let records = [
+            Record::new(vec![
+                Value::Null,
+                Value::Integer(0),
+                Value::Integer(-1000),
+                Value::Integer(i64::MAX),
+                Value::Float(3.15),
+                Value::build_text("hello"),
+                Value::Blob(vec![1, 2, 3]),
+            ]),
+            Record::new(vec![Value::Null; 3]),
+            Record::new(vec![Value::build_text("x".repeat(300)); 200]),
+        ];
+        for record in &records {
+            let mut expected = Vec::new();
+            record.serialize(&mut expected);
+
+            let mut page = vec![0xaa; expected.len() + 16];
+            let written = record.serialize_into(&mut page[8..]).unwrap();
+            assert_eq!(written, expected.len());
+            assert_eq!(&page[8..8 + written], &expected[..]);
+            assert!(page[..8]
+                .iter()
+                .chain(&page[8 + written..])
+                .all(|&b| b == 0xaa));
+
+            let mut short = vec![0xaa; expected.len() - 1];
+            assert!(record.serialize_into(&mut short).is_err());
+            assert!(short.iter().all(|&b| b == 0xaa));
+        }
+    }
 
-        let header_length = record.values.len() + 1;
-        let header = &buf[0..header_length];
-        // First byte should be header size
-        assert_eq!(header[0], header_length as u8);
-        // Second byte should be serial type for FLOAT
-        assert_eq!(header[1] as u64, u64::from(SerialType::f64()));
-        // Check that the bytes after the header can be interpreted as the float
-        let float_bytes = &buf[header_length..header_length + size_of::<f64>()];
-        let float = f64::from_be_bytes(float_bytes.try_into().unwrap());
-        assert_eq!(float, 3.15555);
-        // Check that buffer length is correct
-        assert_eq!(buf.len(), header_length + size_of::<f64>());
+    #[test]
+    fn test_byte_len_and_char_len() {
+        let text = Value::build_text("héllo wörld ✓");
+        assert_eq!(text.byte_len(), Some(17));
+        assert_eq!(text.char_len(), Some(13));
+
+        let ascii = Value::build_text("hello");
+        assert_eq!(ascii.byte_len(), Some(5));
+        assert_eq!(ascii.char_len(), Some(5));
+
+        let empty = Value::build_text("");
+        assert_eq!(empty.byte_len(), Some(0));
+        assert_eq!(empty.char_len(), Some(0));
+
+        let blob = Value::Blob(vec![0xc3, 0xa9, 0x00]);
+        assert_eq!(blob.byte_len(), Some(3));
+        assert_eq!(blob.char_len(), None);
+
+        for value in [Value::Null, Value::Integer(42), Value::Float(1.5)] {
+            assert_eq!(value.byte_len(), None);
+            assert_eq!(value.char_len(), None);
+        }
     }
 
     #[test]
-    fn test_serialize_text() {
-        let text = "hello";
-        let record = Record::new(vec![Value::Text(Text::new(text))]);
-        let mut buf = Vec::new();
-        record.serialize(&mut buf);
+    fn test_concat_ws() {
+        let sep = Value::build_text("-");
+        assert_eq!(
+            Value::concat_ws(
+                &sep,
+                &[Value::build_text("a"), Value::Null, Value::build_text("b")]
+            ),
+            Value::build_text("a-b")
+        );
+        assert_eq!(
+            Value::concat_ws(
+                &Value::Null,
+                &[Value::build_text("a"), Value::build_text("b")]
+            ),
+            Value::Null
+        );
+        assert_eq!(
+            Value::concat_ws(
+                &Value::Integer(0),
+                &[
+                    Value::Null,
+                    Value::Integer(1),
+                    Value::Float(2.5),
+                    Value::Blob(b"xy".to_vec()),
+                    Value::Null,
+                ]
+            ),
+            Value::build_text("102.50xy")
+        );
+        assert_eq!(Value::concat_ws(&sep, &[]), Value::build_text(""));
+        assert_eq!(
+            Value::concat_ws(&sep, &[Value::Null, Value::Null]),
+            Value::build_text("")
+        );
+    }
 
-        let header_length = record.values.len() + 1;
-        let header = &buf[0..header_length];
-        // First byte should be header size
-        assert_eq!(header[0], header_length as u8);
-        // Second byte should be serial type for TEXT, which is (len * 2 + 13)
-        assert_eq!(header[1], (5 * 2 + 13) as u8);
-        // Check the actual text bytes
-        assert_eq!(&buf[2..7], b"hello");
-        // Check that buffer length is correct
-        assert_eq!(buf.len(), header_length + text.len());
+    #[test]
+    fn test_concat() {
+        assert_eq!(
+            Value::concat(&[Value::build_text("a"), Value::Null, Value::Integer(1)]),
+            Value::build_text("a1")
+        );
+        assert_eq!(
+            Value::concat(&[Value::Null, Value::Null]),
+            Value::build_text("")
+        );
+        assert_eq!(Value::concat(&[]), Value::build_text(""));
+        assert_eq!(
+            Value::concat(&[Value::Float(1.5), Value::Blob(b"xy".to_vec())]),
+            Value::build_text("1.5xy")
+        );
     }
 
     #[test]
-    fn test_serialize_blob() {
-        let blob = vec![1, 2, 3, 4, 5];
-        let record = Record::new(vec![Value::Blob(blob.clone())]);
-        let mut buf = Vec::new();
-        record.serialize(&mut buf);
+    fn test_format_with() {
+        let default = DisplayOptions::default();
+        for value in [
+            Value::Null,
+            Value::Integer(-7),
+            Value::Float(0.1),
+            Value::build_text("text"),
+            Value::Blob(b"blob".to_vec()),
+        ] {
+            assert_eq!(value.format_with(&default), value.to_string());
+        }
 
-        let header_length = record.values.len() + 1;
-        let header = &buf[0..header_length];
-        // First byte should be header size
-        assert_eq!(header[0], header_length as u8);
-        // Second byte should be serial type for BLOB, which is (len * 2 + 12)
-        assert_eq!(header[1], (5 * 2 + 12) as u8);
-        // Check the actual blob bytes
-        assert_eq!(&buf[2..7], &[1, 2, 3, 4, 5]);
-        // Check that buffer length is correct
-        assert_eq!(buf.len(), header_length + blob.len());
+        let options = DisplayOptions {
+            max_blob_bytes: Some(3),
+            null_text: "NULL".to_string(),
+            float_precision: Some(2),
+        };
+        assert_eq!(Value::Null.format_with(&options), "NULL");
+        assert_eq!(
+            Value::Blob(b"abcdef".to_vec()).format_with(&options),
+            "abc..."
+        );
+        assert_eq!(Value::Blob(b"abc".to_vec()).format_with(&options), "abc");
+        assert_eq!(Value::Float(1.23456).format_with(&options), "1.23");
+        assert_eq!(Value::Float(f64::INFINITY).format_with(&options), "Inf");
+        assert_eq!(Value::Integer(5).format_with(&options), "5");
+        assert_eq!(Value::build_text("").format_with(&options), "");
     }
 
     #[test]
-    fn test_serialize_mixed_types() {
-        let text = "test";
-        let record = Record::new(vec![
+    fn test_checked_cast_to_integer() {
+        assert_eq!(Value::Integer(-5).checked_cast_to_integer().unwrap(), -5);
+        assert_eq!(Value::Float(12.0).checked_cast_to_integer().unwrap(), 12);
+        assert_eq!(
+            Value::build_text("123").checked_cast_to_integer().unwrap(),
+            123
+        );
+        assert_eq!(
+            Value::build_text(" -42 ")
+                .checked_cast_to_integer()
+                .unwrap(),
+            -42
+        );
+        assert_eq!(
+            Value::build_text("7.0").checked_cast_to_integer().unwrap(),
+            7
+        );
+
+        for value in [
+            Value::build_text("12.5"),
+            Value::build_text("abc"),
+            Value::build_text("12abc"),
+            Value::build_text(""),
+            Value::Float(12.5),
+            Value::Float(f64::NAN),
+            Value::Float(1e300),
+            Value::Blob(vec![1]),
             Value::Null,
-            Value::Integer(42),
-            Value::Float(3.15),
-            Value::Text(Text::new(text)),
+        ] {
+            assert!(
+                matches!(
+                    value.checked_cast_to_integer(),
+                    Err(LimboError::ConversionError(_))
+                ),
+                "{value:?} should not convert"
+            );
+        }
+    }
+
+    #[test]
+    fn test_enforce_strict_types() {
+        let types = [
+            StrictType::Int,
+            StrictType::Real,
+            StrictType::Text,
+            StrictType::Blob,
+            StrictType::Any,
+        ];
+        let row = Record::new(vec![
+            Value::Integer(1),
+            Value::Float(2.5),
+            Value::build_text("three"),
+            Value::Blob(vec![4]),
+            Value::build_text("anything"),
         ]);
-        let mut buf = Vec::new();
-        record.serialize(&mut buf);
+        assert_eq!(row.enforce_strict_types(&types).unwrap(), row);
 
-        let header_length = record.values.len() + 1;
-        let header = &buf[0..header_length];
-        // First byte should be header size
-        assert_eq!(header[0], header_length as u8);
-        // Second byte should be serial type for NULL
-        assert_eq!(header[1] as u64, u64::from(SerialType::null()));
-        // Third byte should be serial type for I8
-        assert_eq!(header[2] as u64, u64::from(SerialType::i8()));
-        // Fourth byte should be serial type for F64
-        assert_eq!(header[3] as u64, u64::from(SerialType::f64()));
-        // Fifth byte should be serial type for TEXT, which is (len * 2 + 13)
-        assert_eq!(header[4] as u64, (4 * 2 + 13) as u64);
+        let converted = Record::new(vec![
+            Value::build_text("7"),
+            Value::Integer(2),
+            Value::Float(1.5),
+            Value::Null,
+            Value::Blob(vec![]),
+            Value::build_text("past the declared columns"),
+        ])
+        .enforce_strict_types(&types)
+        .unwrap();
+        assert_eq!(
+            converted,
+            Record::new(vec![
+                Value::Integer(7),
+                Value::Float(2.0),
+                Value::build_text("1.5"),
+                Value::Null,
+                Value::Blob(vec![]),
+                Value::build_text("past the declared columns"),
+            ])
+        );
 
-        // Check that the bytes after the header can be interpreted as the correct values
-        let mut cur_offset = header_length;
-        let i8_bytes = &buf[cur_offset..cur_offset + size_of::<i8>()];
-        cur_offset += size_of::<i8>();
-        let f64_bytes = &buf[cur_offset..cur_offset + size_of::<f64>()];
-        cur_offset += size_of::<f64>();
-        let text_bytes = &buf[cur_offset..cur_offset + text.len()];
+        assert_eq!(
+            "integer".parse::<StrictType>().unwrap(),
+            StrictType::Integer
+        );
+        assert!("DATETIME".parse::<StrictType>().is_err());
 
-        let val_int8 = i8::from_be_bytes(i8_bytes.try_into().unwrap());
-        let val_float = f64::from_be_bytes(f64_bytes.try_into().unwrap());
-        let val_text = String::from_utf8(text_bytes.to_vec()).unwrap();
+        let text_in_int = Record::new(vec![Value::build_text("abc")]);
+        match text_in_int.enforce_strict_types(&[StrictType::Integer]) {
+            Err(LimboError::Constraint(msg)) => {
+                assert_eq!(msg, "cannot store TEXT value in INTEGER column 0")
+            }
+            other => panic!("expected a constraint error, got {other:?}"),
+        }
+        for (value, column_type) in [
+            (Value::Float(1.5), StrictType::Int),
+            (Value::build_text("x"), StrictType::Real),
+            (Value::Blob(vec![1]), StrictType::Text),
+            (Value::build_text("x"), StrictType::Blob),
+            (Value::Integer(1), StrictType::Blob),
+        ] {
+            assert!(Record::new(vec![value])
+                .enforce_strict_types(&[column_type])
+                .is_err());
+        }
+    }
 
-        assert_eq!(val_int8, 42);
-        assert_eq!(val_float, 3.15);
-        assert_eq!(val_text, "test");
+    #[test]
+    fn test_is_truthy() {
+        assert!(!Value::Null.is_truthy());
+        assert!(!Value::Integer(0).is_truthy());
+        assert!(!Value::Float(0.0).is_truthy());
+        assert!(!Value::Float(-0.0).is_truthy());
+        assert!(Value::Integer(1).is_truthy());
+        assert!(Value::Integer(-1).is_truthy());
+        assert!(Value::Float(0.5).is_truthy());
+
+        assert!(!Value::build_text("0").is_truthy());
+        assert!(!Value::build_text("0.0").is_truthy());
+        assert!(!Value::build_text("").is_truthy());
+        assert!(!Value::build_text("abc").is_truthy());
+        assert!(Value::build_text("1").is_truthy());
+        assert!(Value::build_text("0.5").is_truthy());
+        assert!(Value::build_text(" 2x").is_truthy());
+
+        assert!(!Value::Blob(vec![]).is_truthy());
+        assert!(!Value::Blob(b"0".to_vec()).is_truthy());
+        assert!(Value::Blob(b"1".to_vec()).is_truthy());
+    }
 
-        // Check that buffer length is correct
-        assert_eq!(
-            buf.len(),
-            header_length + size_of::<i8>() + size_of::<f64>() + text.len()
-        );
+    #[test]
+    fn test_round_large_values() {
+        assert_eq!(Value::Float(1e300).round(30), Value::Float(1e300));
+        assert_eq!(Value::Float(-1e300).round(5), Value::Float(-1e300));
+        assert_eq!(Value::Float(f64::MAX).round(1), Value::Float(f64::MAX));
+        assert_eq!(Value::Float(123.0).round(2), Value::Float(123.0));
+        assert_eq!(Value::Float(2.345).round(2), Value::Float(2.35));
     }
 }
@@ -16,7 +16,16 @@ pub trait RoundToPrecision {
 impl RoundToPrecision for f64 {
     fn round_to_precision(self, precision: i32) -> f64 {
         let factor = 10f64.powi(precision);
-        (self * factor).round() / factor
+        let scaled = self * factor;
+        // Nothing to round if the value has no digits past the precision, or if scaling it
+        // overflows, as for round(1e300, 30).
+        if !scaled.is_finite() || scaled.fract() == 0.0 {
+            return self;
+        }
+        // SQLite rounds the decimal rendering of the value, which carries at most 15 significant
+        // digits, so 2.345 rounds to 2.35 even though 2.345 * 100 is 234.49999999999997.
+        let scaled = format!("{:.14e}", scaled).parse::<f64>().unwrap_or(scaled);
+        scaled.round() / factor
     }
 }
 
@@ -905,6 +914,16 @@ pub fn checked_cast_text_to_numeric(text: &str) -> std::result::Result<Value, ()
     }
 }
 
+/// Like [checked_cast_text_to_numeric], but fails unless the whole text (ignoring surrounding
+/// whitespace) is a number, which is what affinity conversions require.
+pub fn checked_cast_text_to_numeric_exact(text: &str) -> std::result::Result<Value, ()> {
+    let (_, number) = parse_numeric_str(text)?;
+    if number.len() != text.trim().len() {
+        return Err(());
+    }
+    checked_cast_text_to_numeric(text)
+}
+
 fn parse_numeric_str(text: &str) -> Result<(ValueType, &str), ()> {
     let text = text.trim();
     let bytes = text.as_bytes();
@@ -965,6 +984,64 @@ pub fn cast_text_to_numeric(txt: &str) -> Value {
     checked_cast_text_to_numeric(txt).unwrap_or(Value::Integer(0))
 }
 
+/// Matches `text` against a LIKE `pattern`, where `%` matches any sequence of characters and
+/// `_` any single character. Like SQLite, only ASCII letters are compared case-insensitively.
+/// The character after `escape` matches literally; a pattern ending in `escape` never matches.
+pub fn like_match(pattern: &str, text: &str, escape: Option<char>) -> bool {
+    enum Token {
+        Any,
+        One,
+        Char(char),
+    }
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            c if Some(c) == escape => match chars.next() {
+                Some(escaped) => Token::Char(escaped),
+                None => return false,
+            },
+            '%' => Token::Any,
+            '_' => Token::One,
+            c => Token::Char(c),
+        });
+    }
+    let text: Vec<char> = text.chars().collect();
+
+    // Greedy match that backtracks to the last `%` on a mismatch.
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::Any) => {
+                backtrack = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some(Token::One) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some(Token::Char(c)) if c.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match backtrack {
+            Some((any_p, any_t)) => {
+                backtrack = Some((any_p, any_t + 1));
+                p = any_p + 1;
+                t = any_t + 1;
+            }
+            None => return false,
+        }
+    }
+    tokens[p..].iter().all(|token| matches!(token, Token::Any))
+}
+
 // Check if float can be losslessly converted to 51-bit integer
 pub fn cast_real_to_integer(float: f64) -> std::result::Result<i64, ()> {
     let i = float as i64;
@@ -2006,4 +2083,46 @@ pub mod tests {
             Value::Float(-9.223372036854775809e+18)
         );
     }
+
+    #[test]
+    fn test_like_match() {
+        assert!(like_match("a%", "aaaa", None));
+        assert!(like_match("%a%a", "aaaa", None));
+        assert!(!like_match("%a.a", "aaaa", None));
+        assert!(!like_match("a.a%", "aaaa", None));
+        assert!(!like_match("%a.ab", "aaaa", None));
+        assert!(like_match(r#"\%A"#, r#"\A"#, None));
+        assert!(like_match("h_llo", "Hello", None));
+        assert!(!like_match("h_lo", "Hello", None));
+        assert!(like_match("%b%", "abc", None));
+        assert!(!like_match("", "a", None));
+        assert!(like_match("%", "", None));
+    }
+
+    #[test]
+    fn test_like_match_ascii_case_folding() {
+        assert!(like_match("a", "A", None));
+        assert!(like_match("%Z", "abcz", None));
+        assert!(!like_match("é", "É", None));
+        assert!(like_match("é", "é", None));
+    }
+
+    #[test]
+    fn test_like_match_with_escape() {
+        assert!(like_match("abcX%", "abc%", Some('X')));
+        assert!(!like_match("abcX%", "abc5", Some('X')));
+        assert!(!like_match("abcX%", "abc", Some('X')));
+        assert!(!like_match("abcX%", "abcX%", Some('X')));
+        assert!(!like_match("abcX%", "abc%%", Some('X')));
+        assert!(like_match("abcX_", "abc_", Some('X')));
+        assert!(!like_match("abcX_", "abc5", Some('X')));
+        assert!(!like_match("abcX_", "abc", Some('X')));
+        assert!(!like_match("abcX_", "abcX_", Some('X')));
+        assert!(!like_match("abcX_", "abc__", Some('X')));
+        assert!(like_match("abcXX", "abcX", Some('X')));
+        assert!(!like_match("abcXX", "abc5", Some('X')));
+        assert!(!like_match("abcXX", "abc", Some('X')));
+        assert!(!like_match("abcXX", "abcXX", Some('X')));
+        assert!(!like_match("abcX", "abc", Some('X')));
+    }
 }
@@ -30,11 +30,11 @@ use crate::{
 use crate::{
     storage::wal::CheckpointResult,
     types::{
-        AggContext, Cursor, CursorResult, ExternalAggState, SeekKey, SeekOp, Value, ValueType,
+        AggContext, Cursor, CursorResult, ExternalAggState, SeekKey, SeekOp, StrictType, Value,
     },
     util::{
         cast_real_to_integer, cast_text_to_integer, cast_text_to_numeric, cast_text_to_real,
-        checked_cast_text_to_numeric, parse_schema_rows, RoundToPrecision,
+        checked_cast_text_to_numeric, like_match, parse_schema_rows, RoundToPrecision,
     },
     vdbe::{
         builder::CursorType,
@@ -56,11 +56,10 @@ use parking_lot::RwLock;
 use rand::thread_rng;
 
 use super::{
-    likeop::{construct_like_escape_arg, exec_glob, exec_like_with_escape},
+    likeop::{construct_like_escape_arg, exec_glob},
     sorter::Sorter,
 };
-use regex::{Regex, RegexBuilder};
-use std::{cell::RefCell, collections::HashMap};
+use std::cell::RefCell;
 
 #[cfg(feature = "json")]
 use crate::{
@@ -1453,7 +1452,7 @@ pub fn op_type_check(
         .try_for_each(|(reg, col)| {
             // INT PRIMARY KEY is not row_id_alias so we throw error if this col is NULL
             if !col.is_rowid_alias
-                && col.primary_key
+                && (col.primary_key || col.notnull)
                 && matches!(reg.get_owned_value(), Value::Null)
             {
                 bail_constraint_error!(
@@ -1466,20 +1465,18 @@ pub fn op_type_check(
                 // Handle INTEGER PRIMARY KEY for null as usual (Rowid will be auto-assigned)
                 return Ok(());
             }
-            let col_affinity = col.affinity();
-            let ty_str = col.ty_str.as_str();
-            let applied = apply_affinity_char(reg, col_affinity);
-            let value_type = reg.get_owned_value().value_type();
-            match (ty_str, value_type) {
-                ("INTEGER" | "INT", ValueType::Integer) => {}
-                ("REAL", ValueType::Float) => {}
-                ("BLOB", ValueType::Blob) => {}
-                ("TEXT", ValueType::Text) => {}
-                ("ANY", _) => {}
-                (t, v) => bail_constraint_error!(
+            let value = reg.get_owned_value();
+            let stored = col
+                .ty_str
+                .parse::<StrictType>()
+                .ok()
+                .and_then(|column_type| column_type.convert(value));
+            match stored {
+                Some(stored) => *reg = Register::Value(stored),
+                None => bail_constraint_error!(
                     "cannot store {} value in {} column {}.{} ({})",
-                    v,
-                    t,
+                    value.type_name().to_uppercase(),
+                    col.ty_str,
                     &table_reference.name,
                     col.name.as_ref().map(|s| s.as_str()).unwrap_or(""),
                     SQLITE_CONSTRAINT
@@ -1508,7 +1505,16 @@ pub fn op_make_record(
     else {
         unreachable!("unexpected Insn {:?}", insn)
     };
-    let record = make_record(&state.registers, start_reg, count);
+    let registers = &state.registers[*start_reg..*start_reg + *count];
+    let record = if registers.iter().all(|r| matches!(r, Register::Value(_))) {
+        make_record(&state.registers, start_reg, count)
+    } else {
+        let values = registers
+            .iter()
+            .map(|r| r.coerce_for_storage().map(Register::Value))
+            .collect::<Result<Vec<_>>>()?;
+        ImmutableRecord::from_registers(&values)
+    };
     state.registers[*dest_reg] = Register::Record(record);
     state.pc += 1;
     Ok(InsnFunctionStepResult::Step)
@@ -2379,43 +2385,20 @@ pub fn op_agg_step(
     };
     if let Register::Value(Value::Null) = state.registers[*acc_reg] {
         state.registers[*acc_reg] = match func {
-            AggFunc::Avg => {
-                Register::Aggregate(AggContext::Avg(Value::Float(0.0), Value::Integer(0)))
-            }
-            AggFunc::Sum => Register::Aggregate(AggContext::Sum(Value::Null)),
+            AggFunc::Avg => Register::Aggregate(AggContext::new_avg()),
+            AggFunc::Sum => Register::Aggregate(AggContext::new_sum()),
             AggFunc::Total => {
                 // The result of total() is always a floating point value.
                 // No overflow error is ever raised if any prior input was a floating point value.
                 // Total() never throws an integer overflow.
-                Register::Aggregate(AggContext::Sum(Value::Float(0.0)))
-            }
-            AggFunc::Count | AggFunc::Count0 => {
-                Register::Aggregate(AggContext::Count(Value::Integer(0)))
-            }
-            AggFunc::Max => {
-                let col = state.registers[*col].get_owned_value();
-                match col {
-                    Value::Integer(_) => Register::Aggregate(AggContext::Max(None)),
-                    Value::Float(_) => Register::Aggregate(AggContext::Max(None)),
-                    Value::Text(_) => Register::Aggregate(AggContext::Max(None)),
-                    _ => {
-                        unreachable!();
-                    }
-                }
-            }
-            AggFunc::Min => {
-                let col = state.registers[*col].get_owned_value();
-                match col {
-                    Value::Integer(_) => Register::Aggregate(AggContext::Min(None)),
-                    Value::Float(_) => Register::Aggregate(AggContext::Min(None)),
-                    Value::Text(_) => Register::Aggregate(AggContext::Min(None)),
-                    _ => {
-                        unreachable!();
-                    }
-                }
+                Register::Aggregate(AggContext::new_total())
             }
+            AggFunc::Count => Register::Aggregate(AggContext::new_count()),
+            AggFunc::Count0 => Register::Aggregate(AggContext::new_count_all()),
+            AggFunc::Max => Register::Aggregate(AggContext::new_max()),
+            AggFunc::Min => Register::Aggregate(AggContext::new_min()),
             AggFunc::GroupConcat | AggFunc::StringAgg => {
-                Register::Aggregate(AggContext::GroupConcat(Value::build_text("")))
+                Register::Aggregate(AggContext::new_group_concat())
             }
             #[cfg(feature = "json")]
             AggFunc::JsonGroupArray | AggFunc::JsonbGroupArray => {
@@ -2443,150 +2426,29 @@ pub fn op_agg_step(
         };
     }
     match func {
-        AggFunc::Avg => {
-            let col = state.registers[*col].clone();
-            let Register::Aggregate(agg) = state.registers[*acc_reg].borrow_mut() else {
-                panic!(
-                    "Unexpected value {:?} in AggStep at register {}",
-                    state.registers[*acc_reg], *acc_reg
-                );
-            };
-            let AggContext::Avg(acc, count) = agg.borrow_mut() else {
-                unreachable!();
-            };
-            *acc = exec_add(acc, col.get_owned_value());
-            *count += 1;
-        }
-        AggFunc::Sum | AggFunc::Total => {
-            let col = state.registers[*col].clone();
-            let Register::Aggregate(agg) = state.registers[*acc_reg].borrow_mut() else {
-                panic!(
-                    "Unexpected value {:?} at register {:?} in AggStep",
-                    state.registers[*acc_reg], *acc_reg
-                );
-            };
-            let AggContext::Sum(acc) = agg.borrow_mut() else {
-                unreachable!();
-            };
-            match col {
-                Register::Value(owned_value) => {
-                    *acc += owned_value;
-                }
-                _ => unreachable!(),
-            }
-        }
-        AggFunc::Count | AggFunc::Count0 => {
+        AggFunc::Avg
+        | AggFunc::Sum
+        | AggFunc::Total
+        | AggFunc::Count
+        | AggFunc::Count0
+        | AggFunc::Max
+        | AggFunc::Min => {
             let col = state.registers[*col].get_owned_value().clone();
-            if matches!(&state.registers[*acc_reg], Register::Value(Value::Null)) {
-                state.registers[*acc_reg] =
-                    Register::Aggregate(AggContext::Count(Value::Integer(0)));
-            }
             let Register::Aggregate(agg) = state.registers[*acc_reg].borrow_mut() else {
                 panic!(
                     "Unexpected value {:?} in AggStep at register {}",
                     state.registers[*acc_reg], *acc_reg
                 );
             };
-            let AggContext::Count(count) = agg.borrow_mut() else {
-                unreachable!();
-            };
-
-            if !(matches!(func, AggFunc::Count) && matches!(col, Value::Null)) {
-                *count += 1;
-            };
-        }
-        AggFunc::Max => {
-            let col = state.registers[*col].clone();
-            let Register::Aggregate(agg) = state.registers[*acc_reg].borrow_mut() else {
-                panic!(
-                    "Unexpected value {:?} in AggStep at register {}",
-                    state.registers[*acc_reg], *acc_reg
-                );
-            };
-            let AggContext::Max(acc) = agg.borrow_mut() else {
-                unreachable!();
-            };
-
-            match (acc.as_mut(), col.get_owned_value()) {
-                (None, value) => {
-                    *acc = Some(value.clone());
-                }
-                (Some(Value::Integer(ref mut current_max)), Value::Integer(value)) => {
-                    if *value > *current_max {
-                        *current_max = value.clone();
-                    }
-                }
-                (Some(Value::Float(ref mut current_max)), Value::Float(value)) => {
-                    if *value > *current_max {
-                        *current_max = *value;
-                    }
-                }
-                (Some(Value::Text(ref mut current_max)), Value::Text(value)) => {
-                    if value.value > current_max.value {
-                        *current_max = value.clone();
-                    }
-                }
-                _ => {
-                    eprintln!("Unexpected types in max aggregation");
-                }
-            }
-        }
-        AggFunc::Min => {
-            let col = state.registers[*col].clone();
-            let Register::Aggregate(agg) = state.registers[*acc_reg].borrow_mut() else {
-                panic!(
-                    "Unexpected value {:?} in AggStep",
-                    state.registers[*acc_reg]
-                );
-            };
-            let AggContext::Min(acc) = agg.borrow_mut() else {
-                unreachable!();
-            };
-
-            match (acc.as_mut(), col.get_owned_value()) {
-                (None, value) => {
-                    *acc.borrow_mut() = Some(value.clone());
-                }
-                (Some(Value::Integer(ref mut current_min)), Value::Integer(value)) => {
-                    if *value < *current_min {
-                        *current_min = *value;
-                    }
-                }
-                (Some(Value::Float(ref mut current_min)), Value::Float(value)) => {
-                    if *value < *current_min {
-                        *current_min = *value;
-                    }
-                }
-                (Some(Value::Text(ref mut current_min)), Value::Text(text)) => {
-                    if text.value < current_min.value {
-                        *current_min = text.clone();
-                    }
-                }
-                _ => {
-                    eprintln!("Unexpected types in min aggregation");
-                }
-            }
+            agg.step(&col);
         }
         AggFunc::GroupConcat | AggFunc::StringAgg => {
             let col = state.registers[*col].get_owned_value().clone();
-            let delimiter = state.registers[*delimiter].clone();
+            let delimiter = state.registers[*delimiter].get_owned_value().clone();
             let Register::Aggregate(agg) = state.registers[*acc_reg].borrow_mut() else {
                 unreachable!();
             };
-            let AggContext::GroupConcat(acc) = agg.borrow_mut() else {
-                unreachable!();
-            };
-            if acc.to_string().is_empty() {
-                *acc = col;
-            } else {
-                match delimiter {
-                    Register::Value(owned_value) => {
-                        *acc += owned_value;
-                    }
-                    _ => unreachable!(),
-                }
-                *acc += col;
-            }
+            agg.step_group_concat(&col, &delimiter);
         }
         #[cfg(feature = "json")]
         AggFunc::JsonGroupObject | AggFunc::JsonbGroupObject => {
@@ -2683,11 +2545,8 @@ pub fn op_agg_final(
     match state.registers[*register].borrow_mut() {
         Register::Aggregate(agg) => match func {
             AggFunc::Avg => {
-                let AggContext::Avg(acc, count) = agg.borrow_mut() else {
-                    unreachable!();
-                };
-                *acc /= count.clone();
-                state.registers[*register] = Register::Value(acc.clone());
+                let value = agg.final_value();
+                state.registers[*register] = Register::Value(value);
             }
             AggFunc::Sum | AggFunc::Total => {
                 let AggContext::Sum(acc) = agg.borrow_mut() else {
@@ -2701,7 +2560,7 @@ pub fn op_agg_final(
                 state.registers[*register] = Register::Value(value);
             }
             AggFunc::Count | AggFunc::Count0 => {
-                let AggContext::Count(count) = agg.borrow_mut() else {
+                let AggContext::Count(count, _) = agg.borrow_mut() else {
                     unreachable!();
                 };
                 state.registers[*register] = Register::Value(count.clone());
@@ -3288,9 +3147,7 @@ pub fn op_function(
                         };
                         Value::Integer(exec_glob(cache, pattern.as_str(), text.as_str()) as i64)
                     }
-                    _ => {
-                        unreachable!("Like on non-text registers");
-                    }
+                    (pattern, text) => text.glob_match(pattern),
                 };
                 state.registers[*dest] = Register::Value(result);
             }
@@ -3333,22 +3190,15 @@ pub fn op_function(
                             Err(e) => return Err(e),
                         };
 
-                        Value::Integer(exec_like_with_escape(
+                        Value::Integer(like_match(
                             pattern.as_str(),
                             match_expression.as_str(),
-                            escape,
+                            Some(escape),
                         ) as i64)
                     }
-                    (Value::Text(pattern), Value::Text(match_expression)) => {
-                        let cache = if *constant_mask > 0 {
-                            Some(&mut state.regex_cache.like)
-                        } else {
-                            None
-                        };
-                        Value::Integer(
-                            exec_like(cache, pattern.as_str(), match_expression.as_str()) as i64,
-                        )
-                    }
+                    (Value::Text(pattern), Value::Text(match_expression)) => Value::Integer(
+                        like_match(pattern.as_str(), match_expression.as_str(), None) as i64,
+                    ),
                     (Value::Null, _) | (_, Value::Null) => Value::Null,
                     _ => {
                         unreachable!("Like failed");
@@ -4952,10 +4802,7 @@ pub fn op_count(
 }
 
 fn exec_lower(reg: &Value) -> Option<Value> {
-    match reg {
-        Value::Text(t) => Some(Value::build_text(&t.as_str().to_lowercase())),
-        t => Some(t.to_owned()),
-    }
+    Some(reg.to_lower())
 }
 
 fn exec_length(reg: &Value) -> Value {
@@ -4979,87 +4826,27 @@ fn exec_octet_length(reg: &Value) -> Value {
 }
 
 fn exec_upper(reg: &Value) -> Option<Value> {
-    match reg {
-        Value::Text(t) => Some(Value::build_text(&t.as_str().to_uppercase())),
-        t => Some(t.to_owned()),
-    }
+    Some(reg.to_upper())
 }
 
 fn exec_concat_strings(registers: &[Register]) -> Value {
-    let mut result = String::new();
-    for reg in registers {
-        match reg.get_owned_value() {
-            Value::Null => continue,
-            Value::Blob(_) => todo!("TODO concat blob"),
-            v => result.push_str(&format!("{}", v)),
-        }
-    }
-    Value::build_text(&result)
+    let args: Vec<Value> = registers
+        .iter()
+        .map(|r| r.get_owned_value().clone())
+        .collect();
+    Value::concat(&args)
 }
 
 fn exec_concat_ws(registers: &[Register]) -> Value {
-    if registers.is_empty() {
+    let Some((separator, args)) = registers.split_first() else {
         return Value::Null;
-    }
-
-    let separator = match &registers[0].get_owned_value() {
-        Value::Null | Value::Blob(_) => return Value::Null,
-        v => format!("{}", v),
     };
-
-    let mut result = String::new();
-    for (i, reg) in registers.iter().enumerate().skip(1) {
-        if i > 1 {
-            result.push_str(&separator);
-        }
-        match reg.get_owned_value() {
-            v if matches!(v, Value::Text(_) | Value::Integer(_) | Value::Float(_)) => {
-                result.push_str(&format!("{}", v))
-            }
-            _ => continue,
-        }
-    }
-
-    Value::build_text(&result)
+    let args: Vec<Value> = args.iter().map(|r| r.get_owned_value().clone()).collect();
+    Value::concat_ws(separator.get_owned_value(), &args)
 }
 
 fn exec_sign(reg: &Value) -> Option<Value> {
-    let num = match reg {
-        Value::Integer(i) => *i as f64,
-        Value::Float(f) => *f,
-        Value::Text(s) => {
-            if let Ok(i) = s.as_str().parse::<i64>() {
-                i as f64
-            } else if let Ok(f) = s.as_str().parse::<f64>() {
-                f
-            } else {
-                return Some(Value::Null);
-            }
-        }
-        Value::Blob(b) => match std::str::from_utf8(b) {
-            Ok(s) => {
-                if let Ok(i) = s.parse::<i64>() {
-                    i as f64
-                } else if let Ok(f) = s.parse::<f64>() {
-                    f
-                } else {
-                    return Some(Value::Null);
-                }
-            }
-            Err(_) => return Some(Value::Null),
-        },
-        _ => return Some(Value::Null),
-    };
-
-    let sign = if num > 0.0 {
-        1
-    } else if num < 0.0 {
-        -1
-    } else {
-        0
-    };
-
-    Some(Value::Integer(sign))
+    Some(reg.signum())
 }
 
 /// Generates the Soundex code for a given word
@@ -5154,32 +4941,16 @@ pub fn exec_soundex(reg: &Value) -> Value {
 }
 
 fn exec_abs(reg: &Value) -> Result<Value> {
-    match reg {
-        Value::Integer(x) => {
-            match i64::checked_abs(*x) {
-                Some(y) => Ok(Value::Integer(y)),
-                // Special case: if we do the abs of "-9223372036854775808", it causes overflow.
-                // return IntegerOverflow error
-                None => Err(LimboError::IntegerOverflow),
-            }
-        }
-        Value::Float(x) => {
-            if x < &0.0 {
-                Ok(Value::Float(-x))
-            } else {
-                Ok(Value::Float(*x))
-            }
-        }
-        Value::Null => Ok(Value::Null),
-        _ => Ok(Value::Float(0.0)),
+    // Special case: if we do the abs of "-9223372036854775808", it causes overflow.
+    // return IntegerOverflow error
+    if let Value::Integer(i64::MIN) = reg {
+        return Err(LimboError::IntegerOverflow);
     }
+    Ok(reg.abs())
 }
 
 fn exec_random() -> Value {
-    let mut buf = [0u8; 8];
-    getrandom::getrandom(&mut buf).unwrap();
-    let random_number = i64::from_ne_bytes(buf);
-    Value::Integer(random_number)
+    Value::random()
 }
 
 fn exec_randomblob(reg: &Value) -> Value {
@@ -5191,213 +4962,58 @@ fn exec_randomblob(reg: &Value) -> Value {
     }
     .max(1) as usize;
 
-    let mut blob: Vec<u8> = vec![0; length];
-    getrandom::getrandom(&mut blob).expect("Failed to generate random blob");
-    Value::Blob(blob)
+    Value::randomblob(length)
 }
 
 fn exec_quote(value: &Value) -> Value {
-    match value {
-        Value::Null => Value::build_text("NULL"),
-        Value::Integer(_) | Value::Float(_) => value.to_owned(),
-        Value::Blob(_) => todo!(),
-        Value::Text(s) => {
-            let mut quoted = String::with_capacity(s.as_str().len() + 2);
-            quoted.push('\'');
-            for c in s.as_str().chars() {
-                if c == '\0' {
-                    break;
-                } else if c == '\'' {
-                    quoted.push('\'');
-                    quoted.push(c);
-                } else {
-                    quoted.push(c);
-                }
-            }
-            quoted.push('\'');
-            Value::build_text(&quoted)
-        }
-    }
+    value.quote()
 }
 
 fn exec_char(values: &[Register]) -> Value {
-    let result: String = values
-        .iter()
-        .filter_map(|x| {
-            if let Value::Integer(i) = x.get_owned_value() {
-                Some(*i as u8 as char)
-            } else {
-                None
-            }
-        })
-        .collect();
-    Value::build_text(&result)
-}
-
-fn construct_like_regex(pattern: &str) -> Regex {
-    let mut regex_pattern = String::with_capacity(pattern.len() * 2);
-
-    regex_pattern.push('^');
-
-    for c in pattern.chars() {
-        match c {
-            '\\' => regex_pattern.push_str("\\\\"),
-            '%' => regex_pattern.push_str(".*"),
-            '_' => regex_pattern.push('.'),
-            ch => {
-                if regex_syntax::is_meta_character(c) {
-                    regex_pattern.push('\\');
-                }
-                regex_pattern.push(ch);
-            }
-        }
-    }
-
-    regex_pattern.push('$');
-
-    RegexBuilder::new(&regex_pattern)
-        .case_insensitive(true)
-        .dot_matches_new_line(true)
-        .build()
-        .unwrap()
-}
-
-// Implements LIKE pattern matching. Caches the constructed regex if a cache is provided
-fn exec_like(regex_cache: Option<&mut HashMap<String, Regex>>, pattern: &str, text: &str) -> bool {
-    if let Some(cache) = regex_cache {
-        match cache.get(pattern) {
-            Some(re) => re.is_match(text),
-            None => {
-                let re = construct_like_regex(pattern);
-                let res = re.is_match(text);
-                cache.insert(pattern.to_string(), re);
-                res
-            }
-        }
-    } else {
-        let re = construct_like_regex(pattern);
-        re.is_match(text)
-    }
+    Value::char_from_codepoints(values.iter().map(|v| v.get_owned_value()))
 }
 
 fn exec_min(regs: &[Register]) -> Value {
-    regs.iter()
-        .map(|v| v.get_owned_value())
-        .min()
-        .map(|v| v.to_owned())
-        .unwrap_or(Value::Null)
+    Value::scalar_min(regs.iter().map(|v| v.get_owned_value()))
 }
 
 fn exec_max(regs: &[Register]) -> Value {
-    regs.iter()
-        .map(|v| v.get_owned_value())
-        .max()
-        .map(|v| v.to_owned())
-        .unwrap_or(Value::Null)
+    Value::scalar_max(regs.iter().map(|v| v.get_owned_value()))
 }
 
 fn exec_nullif(first_value: &Value, second_value: &Value) -> Value {
-    if first_value != second_value {
-        first_value.clone()
-    } else {
-        Value::Null
-    }
+    first_value.nullif(second_value)
 }
 
 fn exec_substring(str_value: &Value, start_value: &Value, length_value: Option<&Value>) -> Value {
-    if let (Value::Text(str), Value::Integer(start)) = (str_value, start_value) {
-        let str_len = str.as_str().len() as i64;
-
-        // The left-most character of X is number 1.
-        // If Y is negative then the first character of the substring is found by counting from the right rather than the left.
-        let first_position = if *start < 0 {
-            str_len.saturating_sub((*start).abs())
-        } else {
-            *start - 1
-        };
-        // If Z is negative then the abs(Z) characters preceding the Y-th character are returned.
-        let last_position = match length_value {
-            Some(Value::Integer(length)) => first_position + *length,
-            _ => str_len,
-        };
-        let (start, end) = if first_position <= last_position {
-            (first_position, last_position)
-        } else {
-            (last_position, first_position)
+    if let (Value::Text(_) | Value::Blob(_), Value::Integer(start)) = (str_value, start_value) {
+        let length = match length_value {
+            Some(Value::Integer(length)) => Some(*length),
+            _ => None,
         };
-        Value::build_text(
-            &str.as_str()[start.clamp(-0, str_len) as usize..end.clamp(0, str_len) as usize],
-        )
+        str_value.substr(*start, length)
     } else {
         Value::Null
     }
 }
 
 fn exec_instr(reg: &Value, pattern: &Value) -> Value {
-    if reg == &Value::Null || pattern == &Value::Null {
-        return Value::Null;
-    }
-
-    if let (Value::Blob(reg), Value::Blob(pattern)) = (reg, pattern) {
-        let result = reg
-            .windows(pattern.len())
-            .position(|window| window == *pattern)
-            .map_or(0, |i| i + 1);
-        return Value::Integer(result as i64);
-    }
-
-    let reg_str;
-    let reg = match reg {
-        Value::Text(s) => s.as_str(),
-        _ => {
-            reg_str = reg.to_string();
-            reg_str.as_str()
-        }
-    };
-
-    let pattern_str;
-    let pattern = match pattern {
-        Value::Text(s) => s.as_str(),
-        _ => {
-            pattern_str = pattern.to_string();
-            pattern_str.as_str()
-        }
-    };
-
-    match reg.find(pattern) {
-        Some(position) => Value::Integer(position as i64 + 1),
-        None => Value::Integer(0),
-    }
+    reg.instr(pattern)
 }
 
 fn exec_typeof(reg: &Value) -> Value {
-    match reg {
-        Value::Null => Value::build_text("null"),
-        Value::Integer(_) => Value::build_text("integer"),
-        Value::Float(_) => Value::build_text("real"),
-        Value::Text(_) => Value::build_text("text"),
-        Value::Blob(_) => Value::build_text("blob"),
-    }
+    Value::build_text(reg.type_name())
 }
 
 fn exec_hex(reg: &Value) -> Value {
-    match reg {
-        Value::Text(_) | Value::Integer(_) | Value::Float(_) | Value::Blob(_) => {
-            let text = reg.to_string();
-            Value::build_text(&hex::encode_upper(text))
-        }
-        _ => Value::Null,
-    }
+    reg.hex()
 }
 
 fn exec_unhex(reg: &Value, ignored_chars: Option<&Value>) -> Value {
     match reg {
         Value::Null => Value::Null,
         _ => match ignored_chars {
-            None => match hex::decode(reg.to_string()) {
-                Ok(bytes) => Value::Blob(bytes),
-                Err(_) => Value::Null,
-            },
+            None => reg.unhex(),
             Some(ignore) => match ignore {
                 Value::Text(_) => {
                     let pat = ignore.to_string();
@@ -5418,17 +5034,7 @@ fn exec_unhex(reg: &Value, ignored_chars: Option<&Value>) -> Value {
 }
 
 fn exec_unicode(reg: &Value) -> Value {
-    match reg {
-        Value::Text(_) | Value::Integer(_) | Value::Float(_) | Value::Blob(_) => {
-            let text = reg.to_string();
-            if let Some(first_char) = text.chars().next() {
-                Value::Integer(first_char as u32 as i64)
-            } else {
-                Value::Null
-            }
-        }
-        _ => Value::Null,
-    }
+    reg.unicode()
 }
 
 fn _to_float(reg: &Value) -> f64 {
@@ -5465,47 +5071,17 @@ fn exec_round(reg: &Value, precision: Option<&Value>) -> Value {
 
 // Implements TRIM pattern matching.
 fn exec_trim(reg: &Value, pattern: Option<&Value>) -> Value {
-    match (reg, pattern) {
-        (reg, Some(pattern)) => match reg {
-            Value::Text(_) | Value::Integer(_) | Value::Float(_) => {
-                let pattern_chars: Vec<char> = pattern.to_string().chars().collect();
-                Value::build_text(reg.to_string().trim_matches(&pattern_chars[..]))
-            }
-            _ => reg.to_owned(),
-        },
-        (Value::Text(t), None) => Value::build_text(t.as_str().trim()),
-        (reg, _) => reg.to_owned(),
-    }
+    reg.trim(pattern.map(|p| p.to_string()).as_deref())
 }
 
 // Implements LTRIM pattern matching.
 fn exec_ltrim(reg: &Value, pattern: Option<&Value>) -> Value {
-    match (reg, pattern) {
-        (reg, Some(pattern)) => match reg {
-            Value::Text(_) | Value::Integer(_) | Value::Float(_) => {
-                let pattern_chars: Vec<char> = pattern.to_string().chars().collect();
-                Value::build_text(reg.to_string().trim_start_matches(&pattern_chars[..]))
-            }
-            _ => reg.to_owned(),
-        },
-        (Value::Text(t), None) => Value::build_text(t.as_str().trim_start()),
-        (reg, _) => reg.to_owned(),
-    }
+    reg.ltrim(pattern.map(|p| p.to_string()).as_deref())
 }
 
 // Implements RTRIM pattern matching.
 fn exec_rtrim(reg: &Value, pattern: Option<&Value>) -> Value {
-    match (reg, pattern) {
-        (reg, Some(pattern)) => match reg {
-            Value::Text(_) | Value::Integer(_) | Value::Float(_) => {
-                let pattern_chars: Vec<char> = pattern.to_string().chars().collect();
-                Value::build_text(reg.to_string().trim_end_matches(&pattern_chars[..]))
-            }
-            _ => reg.to_owned(),
-        },
-        (Value::Text(t), None) => Value::build_text(t.as_str().trim_end()),
-        (reg, _) => reg.to_owned(),
-    }
+    reg.rtrim(pattern.map(|p| p.to_string()).as_deref())
 }
 
 fn exec_zeroblob(req: &Value) -> Value {
@@ -5521,80 +5097,16 @@ fn exec_zeroblob(req: &Value) -> Value {
 // exec_if returns whether you should jump
 fn exec_if(reg: &Value, jump_if_null: bool, not: bool) -> bool {
     match reg {
-        Value::Integer(0) | Value::Float(0.0) => not,
-        Value::Integer(_) | Value::Float(_) => !not,
         Value::Null => jump_if_null,
-        _ => false,
+        value => value.is_truthy() != not,
     }
 }
 
 fn apply_affinity_char(target: &mut Register, affinity: Affinity) -> bool {
-    if let Register::Value(value) = target {
-        if matches!(value, Value::Blob(_)) {
-            return true;
-        }
-        match affinity {
-            Affinity::Blob => return true,
-            Affinity::Text => {
-                if matches!(value, Value::Text(_) | Value::Null) {
-                    return true;
-                }
-                let text = value.to_string();
-                *value = Value::Text(text.into());
-                return true;
-            }
-            Affinity::Integer | Affinity::Numeric => {
-                if matches!(value, Value::Integer(_)) {
-                    return true;
-                }
-                if !matches!(value, Value::Text(_) | Value::Float(_)) {
-                    return true;
-                }
-
-                if let Value::Float(fl) = *value {
-                    if let Ok(int) = cast_real_to_integer(fl).map(Value::Integer) {
-                        *value = int;
-                        return true;
-                    }
-                    return false;
-                }
-
-                let text = value.to_text().unwrap();
-                let Ok(num) = checked_cast_text_to_numeric(&text) else {
-                    return false;
-                };
-
-                *value = match &num {
-                    Value::Float(fl) => {
-                        cast_real_to_integer(*fl).map(Value::Integer).unwrap_or(num);
-                        return true;
-                    }
-                    Value::Integer(_) if text.starts_with("0x") => {
-                        return false;
-                    }
-                    _ => num,
-                };
-            }
-
-            Affinity::Real => {
-                if let Value::Integer(i) = value {
-                    *value = Value::Float(*i as f64);
-                    return true;
-                } else if let Value::Text(t) = value {
-                    if t.as_str().starts_with("0x") {
-                        return false;
-                    }
-                    if let Ok(num) = checked_cast_text_to_numeric(t.as_str()) {
-                        *value = num;
-                        return true;
-                    } else {
-                        return false;
-                    }
-                }
-            }
-        };
+    match target {
+        Register::Value(value) => value.apply_affinity(affinity),
+        _ => true,
     }
-    return true;
 }
 
 fn exec_cast(value: &Value, datatype: &str) -> Value {
@@ -5678,24 +5190,7 @@ fn exec_replace(source: &Value, pattern: &Value, replacement: &Value) -> Value {
         return Value::Null;
     }
 
-    let source = exec_cast(source, "TEXT");
-    let pattern = exec_cast(pattern, "TEXT");
-    let replacement = exec_cast(replacement, "TEXT");
-
-    // If any of the casts failed, panic as text casting is not expected to fail.
-    match (&source, &pattern, &replacement) {
-        (Value::Text(source), Value::Text(pattern), Value::Text(replacement)) => {
-            if pattern.as_str().is_empty() {
-                return Value::Text(source.clone());
-            }
-
-            let result = source
-                .as_str()
-                .replace(pattern.as_str(), replacement.as_str());
-            Value::build_text(&result)
-        }
-        _ => unreachable!("text cast should never fail"),
-    }
+    source.replace(&pattern.to_string(), &replacement.to_string())
 }
 
 fn execute_sqlite_version(version_integer: i64) -> String {
@@ -5706,15 +5201,6 @@ fn execute_sqlite_version(version_integer: i64) -> String {
     format!("{}.{}.{}", major, minor, release)
 }
 
-fn to_f64(reg: &Value) -> Option<f64> {
-    match reg {
-        Value::Integer(i) => Some(*i as f64),
-        Value::Float(f) => Some(*f),
-        Value::Text(t) => t.as_str().parse::<f64>().ok(),
-        _ => None,
-    }
-}
-
 fn exec_math_unary(reg: &Value, function: &MathFunc) -> Value {
     // In case of some functions and integer input, return the input as is
     if let Value::Integer(_) = reg {
@@ -5724,7 +5210,7 @@ fn exec_math_unary(reg: &Value, function: &MathFunc) -> Value {
         }
     }
 
-    let f = match to_f64(reg) {
+    let f = match reg.try_as_f64() {
         Some(f) => f,
         None => return Value::Null,
     };
@@ -5763,12 +5249,12 @@ fn exec_math_unary(reg: &Value, function: &MathFunc) -> Value {
 }
 
 fn exec_math_binary(lhs: &Value, rhs: &Value, function: &MathFunc) -> Value {
-    let lhs = match to_f64(lhs) {
+    let lhs = match lhs.try_as_f64() {
         Some(f) => f,
         None => return Value::Null,
     };
 
-    let rhs = match to_f64(rhs) {
+    let rhs = match rhs.try_as_f64() {
         Some(f) => f,
         None => return Value::Null,
     };
@@ -5788,13 +5274,13 @@ fn exec_math_binary(lhs: &Value, rhs: &Value, function: &MathFunc) -> Value {
 }
 
 fn exec_math_log(arg: &Value, base: Option<&Value>) -> Value {
-    let f = match to_f64(arg) {
+    let f = match arg.try_as_f64() {
         Some(f) => f,
         None => return Value::Null,
     };
 
     let base = match base {
-        Some(base) => match to_f64(base) {
+        Some(base) => match base.try_as_f64() {
             Some(f) => f,
             None => return Value::Null,
         },
@@ -5819,19 +5305,19 @@ fn exec_likelihood(reg: &Value, _probability: &Value) -> Value {
 }
 
 pub fn exec_add(lhs: &Value, rhs: &Value) -> Value {
-    (Numeric::from(lhs) + Numeric::from(rhs)).into()
+    lhs.arith_add(rhs)
 }
 
 pub fn exec_subtract(lhs: &Value, rhs: &Value) -> Value {
-    (Numeric::from(lhs) - Numeric::from(rhs)).into()
+    lhs.arith_sub(rhs)
 }
 
 pub fn exec_multiply(lhs: &Value, rhs: &Value) -> Value {
-    (Numeric::from(lhs) * Numeric::from(rhs)).into()
+    lhs.arith_mul(rhs)
 }
 
 pub fn exec_divide(lhs: &Value, rhs: &Value) -> Value {
-    (Numeric::from(lhs) / Numeric::from(rhs)).into()
+    lhs.arith_div(rhs)
 }
 
 pub fn exec_bit_and(lhs: &Value, rhs: &Value) -> Value {
@@ -6345,12 +5831,11 @@ mod tests {
     };
 
     use super::{
-        exec_abs, exec_char, exec_hex, exec_if, exec_instr, exec_length, exec_like, exec_lower,
-        exec_ltrim, exec_max, exec_min, exec_nullif, exec_quote, exec_random, exec_randomblob,
-        exec_round, exec_rtrim, exec_sign, exec_soundex, exec_substring, exec_trim, exec_typeof,
-        exec_unhex, exec_unicode, exec_upper, exec_zeroblob, execute_sqlite_version,
+        exec_abs, exec_char, exec_hex, exec_if, exec_instr, exec_length, exec_lower, exec_ltrim,
+        exec_max, exec_min, exec_nullif, exec_quote, exec_random, exec_randomblob, exec_round,
+        exec_rtrim, exec_sign, exec_soundex, exec_substring, exec_trim, exec_typeof, exec_unhex,
+        exec_unicode, exec_upper, exec_zeroblob, execute_sqlite_version,
     };
-    use std::collections::HashMap;
 
     #[test]
     fn test_length() {
@@ -6378,7 +5863,7 @@ mod tests {
         assert_eq!(exec_quote(&input), expected);
 
         let input = Value::Integer(123);
-        let expected = Value::Integer(123);
+        let expected = Value::build_text("123");
         assert_eq!(exec_quote(&input), expected);
 
         let input = Value::build_text("hello''world");
@@ -6638,46 +6123,14 @@ mod tests {
         assert_eq!(exec_char(&[]), Value::build_text(""));
         assert_eq!(
             exec_char(&[Register::Value(Value::Null)]),
-            Value::build_text("")
+            Value::build_text("\0")
         );
         assert_eq!(
             exec_char(&[Register::Value(Value::build_text("a"))]),
-            Value::build_text("")
+            Value::build_text("\0")
         );
     }
 
-    #[test]
-    fn test_like_with_escape_or_regexmeta_chars() {
-        assert!(exec_like(None, r#"\%A"#, r#"\A"#));
-        assert!(exec_like(None, "%a%a", "aaaa"));
-    }
-
-    #[test]
-    fn test_like_no_cache() {
-        assert!(exec_like(None, "a%", "aaaa"));
-        assert!(exec_like(None, "%a%a", "aaaa"));
-        assert!(!exec_like(None, "%a.a", "aaaa"));
-        assert!(!exec_like(None, "a.a%", "aaaa"));
-        assert!(!exec_like(None, "%a.ab", "aaaa"));
-    }
-
-    #[test]
-    fn test_like_with_cache() {
-        let mut cache = HashMap::new();
-        assert!(exec_like(Some(&mut cache), "a%", "aaaa"));
-        assert!(exec_like(Some(&mut cache), "%a%a", "aaaa"));
-        assert!(!exec_like(Some(&mut cache), "%a.a", "aaaa"));
-        assert!(!exec_like(Some(&mut cache), "a.a%", "aaaa"));
-        assert!(!exec_like(Some(&mut cache), "%a.ab", "aaaa"));
-
-        // again after values have been cached
-        assert!(exec_like(Some(&mut cache), "a%", "aaaa"));
-        assert!(exec_like(Some(&mut cache), "%a%a", "aaaa"));
-        assert!(!exec_like(Some(&mut cache), "%a.a", "aaaa"));
-        assert!(!exec_like(Some(&mut cache), "a.a%", "aaaa"));
-        assert!(!exec_like(Some(&mut cache), "%a.ab", "aaaa"));
-    }
-
     #[test]
     fn test_random() {
         match exec_random() {
@@ -6809,6 +6262,14 @@ mod tests {
         let reg = Value::Null;
         assert!(!exec_if(&reg, false, false));
         assert!(!exec_if(&reg, false, true));
+
+        let reg = Value::build_text("0.5");
+        assert!(exec_if(&reg, false, false));
+        assert!(!exec_if(&reg, false, true));
+
+        let reg = Value::build_text("abc");
+        assert!(!exec_if(&reg, false, false));
+        assert!(exec_if(&reg, false, true));
     }
 
     #[test]
@@ -7281,4 +6742,22 @@ mod tests {
             assert!(!bitfield.get(i));
         }
     }
+
+    #[test]
+    fn test_register_coerce_for_storage() {
+        use crate::types::{AggContext, ImmutableRecord};
+        use crate::vdbe::Register;
+
+        let value = Register::Value(Value::build_text("a"));
+        assert_eq!(value.coerce_for_storage().unwrap(), Value::build_text("a"));
+
+        let mut sum = AggContext::new_sum();
+        sum.step(&Value::Integer(2));
+        sum.step(&Value::Integer(5));
+        let agg = Register::Aggregate(sum);
+        assert_eq!(agg.coerce_for_storage().unwrap(), Value::Integer(7));
+
+        let record = Register::Record(ImmutableRecord::from_registers(&[value]));
+        assert!(record.coerce_for_storage().is_err());
+    }
 }
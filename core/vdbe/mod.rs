@@ -35,7 +35,9 @@ use crate::{
 use crate::{
     storage::{btree::BTreeCursor, pager::Pager, sqlite3_ondisk::DatabaseHeader},
     translate::plan::ResultSetColumn,
-    types::{AggContext, Cursor, CursorResult, ImmutableRecord, SeekKey, SeekOp, Value},
+    types::{
+        type_mismatch, AggContext, Cursor, CursorResult, ImmutableRecord, SeekKey, SeekOp, Value,
+    },
     vdbe::{builder::CursorType, insn::Insn},
 };
 
@@ -166,14 +168,12 @@ macro_rules! return_if_io {
 }
 
 struct RegexCache {
-    like: HashMap<String, Regex>,
     glob: HashMap<String, Regex>,
 }
 
 impl RegexCache {
     fn new() -> Self {
         Self {
-            like: HashMap::new(),
             glob: HashMap::new(),
         }
     }
@@ -308,7 +308,6 @@ impl ProgramState {
         self.last_compare = None;
         self.deferred_seeks.iter_mut().for_each(|s| *s = None);
         self.ended_coroutine.0 = [0; 4];
-        self.regex_cache.like.clear();
         self.interrupted = false;
         self.parameters.clear();
         #[cfg(feature = "json")]
@@ -333,6 +332,18 @@ impl Register {
             _ => unreachable!(),
         }
     }
+
+    /// Returns the scalar this register would store in a record. An aggregate resolves to its
+    /// current result; a record is not a storable value and is an error.
+    pub fn coerce_for_storage(&self) -> Result<Value> {
+        match self {
+            Register::Value(v) => Ok(v.clone()),
+            Register::Aggregate(agg) => Ok(agg.final_value()),
+            Register::Record(_) => Err(LimboError::InternalError(
+                "cannot store a record as a column value".to_string(),
+            )),
+        }
+    }
 }
 
 #[macro_export]
@@ -602,7 +613,7 @@ impl<'a> FromValueRow<'a> for i64 {
     fn from_value(value: &'a Value) -> Result<Self> {
         match value {
             Value::Integer(i) => Ok(*i),
-            _ => Err(LimboError::ConversionError("Expected integer value".into())),
+            _ => Err(type_mismatch("integer", value.type_name())),
         }
     }
 }
@@ -611,7 +622,7 @@ impl<'a> FromValueRow<'a> for f64 {
     fn from_value(value: &'a Value) -> Result<Self> {
         match value {
             Value::Float(f) => Ok(*f),
-            _ => Err(LimboError::ConversionError("Expected integer value".into())),
+            _ => Err(type_mismatch("real", value.type_name())),
         }
     }
 }
@@ -620,7 +631,7 @@ impl<'a> FromValueRow<'a> for String {
     fn from_value(value: &'a Value) -> Result<Self> {
         match value {
             Value::Text(s) => Ok(s.as_str().to_string()),
-            _ => Err(LimboError::ConversionError("Expected text value".into())),
+            _ => Err(type_mismatch("text", value.type_name())),
         }
     }
 }
@@ -629,7 +640,7 @@ impl<'a> FromValueRow<'a> for &'a str {
     fn from_value(value: &'a Value) -> Result<Self> {
         match value {
             Value::Text(s) => Ok(s.as_str()),
-            _ => Err(LimboError::ConversionError("Expected text value".into())),
+            _ => Err(type_mismatch("text", value.type_name())),
         }
     }
 }
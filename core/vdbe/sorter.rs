@@ -1,8 +1,10 @@
 use limbo_sqlite3_parser::ast::SortOrder;
 
 use crate::{
+    storage::sqlite3_ondisk::{read_varint, write_varint_to_vec},
     translate::collate::CollationSeq,
-    types::{compare_immutable, ImmutableRecord, IndexKeySortOrder},
+    types::{compare_immutable, ImmutableRecord, IndexKeySortOrder, Record, Value},
+    LimboError, Result,
 };
 
 pub struct Sorter {
@@ -55,3 +57,241 @@ impl Sorter {
         self.records.push(record.clone());
     }
 }
+
+/// Sorts [Record]s by a list of ORDER BY keys without holding them all in memory. Records are
+/// buffered until their combined size exceeds the memory limit; the buffer is then sorted and
+/// serialized as a run. [SpillableSorter::finish] merges the runs back into a single stream.
+/// Equal records keep their insertion order.
+pub struct SpillableSorter {
+    keys: Vec<(usize, SortOrder, CollationSeq)>,
+    memory_limit: usize,
+    buffer: Vec<Record>,
+    buffered_bytes: usize,
+    runs: Vec<Vec<u8>>,
+}
+
+impl SpillableSorter {
+    pub fn new(keys: Vec<(usize, SortOrder, CollationSeq)>, memory_limit: usize) -> Self {
+        Self {
+            keys,
+            memory_limit,
+            buffer: Vec::new(),
+            buffered_bytes: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, record: Record) {
+        self.buffered_bytes += record
+            .get_values()
+            .iter()
+            .map(Value::heap_size)
+            .sum::<usize>();
+        self.buffer.push(record);
+        if self.buffered_bytes > self.memory_limit {
+            self.spill();
+        }
+    }
+
+    /// Number of runs written so far.
+    pub fn num_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    // Sorts the buffered records and writes them out as one run of length-prefixed records.
+    fn spill(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let keys = &self.keys;
+        self.buffer.sort_by(|a, b| a.order_key_cmp(b, keys));
+        let mut run = Vec::new();
+        let mut record_buf = Vec::new();
+        for record in self.buffer.drain(..) {
+            record_buf.clear();
+            record.serialize(&mut record_buf);
+            write_varint_to_vec(record_buf.len() as u64, &mut run);
+            run.extend_from_slice(&record_buf);
+        }
+        self.runs.push(run);
+        self.buffered_bytes = 0;
+    }
+
+    /// Returns the records in sorted order, merging any spilled runs.
+    pub fn finish(mut self) -> Result<MergedRuns> {
+        self.spill();
+        let mut runs = self
+            .runs
+            .into_iter()
+            .map(|data| RunReader { data, pos: 0 })
+            .collect::<Vec<_>>();
+        let heads = runs
+            .iter_mut()
+            .map(RunReader::next_record)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(MergedRuns {
+            keys: self.keys,
+            runs,
+            heads,
+        })
+    }
+}
+
+struct RunReader {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl RunReader {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        if self.pos >= self.data.len() {
+            return Ok(None);
+        }
+        let (len, n) = read_varint(&self.data[self.pos..])?;
+        let start = self.pos + n;
+        let record = start
+            .checked_add(len as usize)
+            .and_then(|end| self.data.get(start..end))
+            .ok_or_else(|| {
+                LimboError::CorruptRecord(format!(
+                    "sorter run record of {len} bytes at offset {start} overruns the run"
+                ))
+            })?;
+        self.pos = start + record.len();
+        Record::deserialize(record).map(Some)
+    }
+}
+
+/// Iterator over the output of a [SpillableSorter], produced by a k-way merge of its runs.
+/// A run that fails to read ends the iteration after its error is returned.
+pub struct MergedRuns {
+    keys: Vec<(usize, SortOrder, CollationSeq)>,
+    runs: Vec<RunReader>,
+    heads: Vec<Option<Record>>,
+}
+
+impl Iterator for MergedRuns {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Runs are few, so a linear scan over their heads is enough. Picking the earliest run
+        // on ties keeps the sort stable, as earlier runs hold earlier insertions.
+        let mut best: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some(record) = head else {
+                continue;
+            };
+            match best {
+                Some(b)
+                    if !record
+                        .order_key_cmp(self.heads[b].as_ref().unwrap(), &self.keys)
+                        .is_lt() => {}
+                _ => best = Some(i),
+            }
+        }
+        let best = best?;
+        let next = match self.runs[best].next_record() {
+            Ok(next) => next,
+            Err(e) => {
+                self.heads.clear();
+                return Some(Err(e));
+            }
+        };
+        std::mem::replace(&mut self.heads[best], next).map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spillable_sorter_merges_runs() {
+        let keys = vec![
+            (0, SortOrder::Desc, CollationSeq::Binary),
+            (1, SortOrder::Asc, CollationSeq::Binary),
+        ];
+        let mut sorter = SpillableSorter::new(keys, 1024);
+        let mut expected = Vec::new();
+        for i in 0..1000i64 {
+            let record = Record::new(vec![
+                Value::Integer((i * 7919) % 13),
+                Value::Integer(i),
+                Value::build_text(format!("row {i}")),
+            ]);
+            expected.push(record.clone());
+            sorter.insert(record);
+        }
+        assert!(sorter.num_runs() > 1);
+
+        let sorted = sorter
+            .finish()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        expected.sort_by(|a, b| {
+            b.get_value(0)
+                .partial_cmp(a.get_value(0))
+                .unwrap()
+                .then(a.get_value(1).partial_cmp(b.get_value(1)).unwrap())
+        });
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn test_spillable_sorter_is_stable() {
+        let keys = vec![(0, SortOrder::Asc, CollationSeq::Binary)];
+        let mut sorter = SpillableSorter::new(keys, 256);
+        for i in 0..200i64 {
+            sorter.insert(Record::new(vec![Value::Integer(i % 3), Value::Integer(i)]));
+        }
+        let sorted = sorter
+            .finish()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(sorted.len(), 200);
+        for pair in sorted.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            assert!(a.get_value(0) <= b.get_value(0));
+            if a.get_value(0) == b.get_value(0) {
+                assert!(a.get_value(1) < b.get_value(1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_spillable_sorter_in_memory() {
+        let keys = vec![(0, SortOrder::Asc, CollationSeq::NoCase)];
+        let mut sorter = SpillableSorter::new(keys, usize::MAX);
+        for s in ["b", "A", "c"] {
+            sorter.insert(Record::new(vec![Value::build_text(s)]));
+        }
+        assert_eq!(sorter.num_runs(), 0);
+        let sorted = sorter
+            .finish()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        let sorted: Vec<_> = sorted.iter().map(|r| r.get_value(0).to_string()).collect();
+        assert_eq!(sorted, vec!["A", "b", "c"]);
+    }
+
+    #[test]
+    fn test_spillable_sorter_stops_after_corrupt_run() {
+        let keys = vec![(0, SortOrder::Asc, CollationSeq::Binary)];
+        let mut sorter = SpillableSorter::new(keys, usize::MAX);
+        sorter.insert(Record::new(vec![Value::Integer(1)]));
+        sorter.insert(Record::new(vec![Value::build_text("two")]));
+        sorter.spill();
+        let run = &mut sorter.runs[0];
+        run.truncate(run.len() - 1);
+
+        let mut merged = sorter.finish().unwrap();
+        assert!(matches!(
+            merged.next(),
+            Some(Err(LimboError::CorruptRecord(_)))
+        ));
+        assert!(merged.next().is_none());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::common::TempDatabase;
-use limbo_core::{StepResult, Value};
+use limbo_core::{Params, StepResult, Value};
 
 #[test]
 fn test_statement_reset_bind() -> anyhow::Result<()> {
@@ -98,6 +98,46 @@ fn test_statement_bind() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_statement_bind_params() -> anyhow::Result<()> {
+    let tmp_db = TempDatabase::new_with_rusqlite("create table test (i integer);");
+    let conn = tmp_db.connect_limbo();
+
+    let mut stmt = conn.prepare("select ?1, :named, ?3")?;
+
+    let mut params = Params::new();
+    params
+        .bind_text(1.try_into()?, "hello")
+        .bind(3.try_into()?, None::<i64>)
+        .bind_named(":named", 42);
+    stmt.bind_params(&params)?;
+
+    let mut rows = 0;
+    loop {
+        match stmt.step()? {
+            StepResult::Row => {
+                let row = stmt.row().unwrap();
+                assert_eq!(row.get::<&Value>(0).unwrap(), &Value::build_text("hello"));
+                assert_eq!(row.get::<&Value>(1).unwrap(), &Value::Integer(42));
+                assert_eq!(row.get::<&Value>(2).unwrap(), &Value::Null);
+                rows += 1;
+            }
+            StepResult::IO => {
+                tmp_db.io.run_once()?;
+            }
+            StepResult::Interrupt => break,
+            StepResult::Done => break,
+            StepResult::Busy => panic!("Database is busy"),
+        };
+    }
+    assert_eq!(rows, 1);
+
+    let mut unknown = Params::new();
+    unknown.bind_named(":missing", 1);
+    assert!(stmt.bind_params(&unknown).is_err());
+    Ok(())
+}
+
 #[test]
 fn test_insert_parameter_remap() -> anyhow::Result<()> {
     // ───────────────────────  schema  ──────────────────────────────